@@ -1,7 +1,9 @@
 mod keyboard;
 mod mouse;
+mod touch;
 
 pub use keyboard::*;
 pub use mouse::*;
+pub use touch::*;
 
 pub type State = winit::event::ElementState;
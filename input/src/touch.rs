@@ -0,0 +1,72 @@
+use nalgebra as na;
+use std::collections::HashMap;
+
+pub type TouchPhase = winit::event::TouchPhase;
+
+/// Camera movement from two fingers.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Gesture {
+	/// Movement of the center between the fingers.
+	pub drag: na::SVector<f32, 2>,
+	/// Ratio between the previous and current finger distance, above 1.0 if pinched together.
+	pub pinch: f32,
+}
+
+pub struct Touches {
+	active: HashMap<u64, na::Point<f32, 2>>,
+}
+
+impl Touches {
+	pub fn new() -> Self {
+		Self { active: HashMap::new() }
+	}
+
+	/// Update the touch point, returns the gesture if exactly two fingers are moved.
+	pub fn update(
+		&mut self,
+		id: u64,
+		phase: TouchPhase,
+		position: na::Point<f32, 2>,
+	) -> Option<Gesture> {
+		match phase {
+			TouchPhase::Started => {
+				self.active.insert(id, position);
+				None
+			},
+			TouchPhase::Ended | TouchPhase::Cancelled => {
+				self.active.remove(&id);
+				None
+			},
+			TouchPhase::Moved => {
+				let before = self.pair();
+				if let Some(previous) = self.active.get_mut(&id) {
+					*previous = position;
+				}
+				let (before, after) = (before?, self.pair()?);
+				let distance = (after.1 - after.0).norm();
+				if distance <= 0.0 {
+					return None;
+				}
+				Some(Gesture {
+					drag: na::center(&after.0, &after.1) - na::center(&before.0, &before.1),
+					pinch: (before.1 - before.0).norm() / distance,
+				})
+			},
+		}
+	}
+
+	/// Positions of both fingers, `None` if not exactly two are active.
+	fn pair(&self) -> Option<(na::Point<f32, 2>, na::Point<f32, 2>)> {
+		if self.active.len() != 2 {
+			return None;
+		}
+		let mut positions = self.active.values().copied();
+		Some((positions.next()?, positions.next()?))
+	}
+}
+
+impl Default for Touches {
+	fn default() -> Self {
+		Self::new()
+	}
+}
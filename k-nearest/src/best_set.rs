@@ -81,24 +81,23 @@ where
 	}
 }
 
-pub struct DynamicSet<Value> {
+pub struct DynamicSet<'a, Value> {
 	distance: Value,
-	values: Vec<Entry<Value>>,
+	values: &'a mut Vec<Entry<Value>>,
 }
 
-impl<Value: PartialOrd> DynamicSet<Value> {
-	pub fn new(distance: Value) -> Self {
-		Self { distance, values: Vec::new() }
+impl<'a, Value: PartialOrd> DynamicSet<'a, Value> {
+	pub fn new(distance: Value, values: &'a mut Vec<Entry<Value>>) -> Self {
+		values.clear();
+		Self { distance, values }
 	}
 
-	pub fn result(self) -> Vec<Entry<Value>> {
-		let mut values = self.values;
-		values.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
-		values
+	pub fn result(self) {
+		self.values.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
 	}
 }
 
-impl<Value: Copy> BestSet<Value> for DynamicSet<Value> {
+impl<'a, Value: Copy> BestSet<Value> for DynamicSet<'a, Value> {
 	fn distance(&self) -> Value {
 		self.distance
 	}
@@ -1,4 +1,8 @@
-use std::cmp::Ordering;
+use std::{
+	cmp::Ordering,
+	collections::{HashMap, HashSet},
+	ops::Not,
+};
 
 use crate::{
 	best_set::{BestSet, DynamicSet, EmptySet, FixedSet},
@@ -15,6 +19,9 @@ unsafe impl<Value: bytemuck::Pod> bytemuck::Pod for Entry<Value> {}
 
 unsafe impl<Value: bytemuck::Zeroable> bytemuck::Zeroable for Entry<Value> {}
 
+// minimal number of changes before the tree is rebuild
+const MIN_REBUILD_CHANGES: usize = 32;
+
 pub struct KDTree<const N: usize, Value, Point, Ada, Met>
 where
 	Value: Copy + Default + PartialOrd,
@@ -22,6 +29,14 @@ where
 	Met: Metric<N, Value>,
 {
 	tree: Vec<([Value; N], usize)>,
+	// inserted points since the last rebuild, searched linear
+	added: Vec<([Value; N], usize)>,
+	// position in added for every inserted index
+	added_positions: HashMap<usize, usize>,
+	// removed indices since the last rebuild, still in the tree
+	removed: HashSet<usize>,
+	// sorted indices in the tree, created by the first remove after a rebuild
+	indices: Option<Vec<usize>>,
 	phantom: std::marker::PhantomData<(Point, Ada, Met)>,
 }
 
@@ -38,7 +53,75 @@ where
 			tree.push((Ada::get_all(p), i));
 		}
 		Self::create_tree(0, &mut tree);
-		Self { tree, phantom: std::marker::PhantomData }
+		Self {
+			tree,
+			added: Vec::new(),
+			added_positions: HashMap::new(),
+			removed: HashSet::new(),
+			indices: None,
+			phantom: std::marker::PhantomData,
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.tree.len() + self.added.len() - self.removed.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	// add a point with an index, which must not be in the tree already
+	//   the tree is rebuild after enough changes, so it stays balanced
+	pub fn insert(&mut self, point: &Point, index: usize) {
+		self.added_positions.insert(index, self.added.len());
+		self.added.push((Ada::get_all(point), index));
+		self.maybe_rebuild();
+	}
+
+	// remove the point with the index, returns false if the index is not in the tree
+	pub fn remove(&mut self, index: usize) -> bool {
+		if let Some(position) = self.added_positions.remove(&index) {
+			self.added.swap_remove(position);
+			if let Some(&(_, moved)) = self.added.get(position) {
+				self.added_positions.insert(moved, position);
+			}
+			return true;
+		}
+		if self.removed.contains(&index) || self.tree_indices().binary_search(&index).is_err() {
+			return false;
+		}
+		self.removed.insert(index);
+		self.maybe_rebuild();
+		true
+	}
+
+	fn tree_indices(&mut self) -> &[usize] {
+		let tree = &self.tree;
+		self.indices.get_or_insert_with(|| {
+			let mut indices = tree.iter().map(|&(_, i)| i).collect::<Vec<_>>();
+			indices.sort_unstable();
+			indices
+		})
+	}
+
+	fn maybe_rebuild(&mut self) {
+		let changes = self.added.len() + self.removed.len();
+		if changes >= MIN_REBUILD_CHANGES.max(self.tree.len() / 8) {
+			self.rebuild();
+		}
+	}
+
+	// build a balanced tree with all inserted points and without the removed points
+	pub fn rebuild(&mut self) {
+		let removed = std::mem::take(&mut self.removed);
+		self.tree.retain(|(_, index)| removed.contains(index).not());
+		self.tree.append(&mut self.added);
+		self.added_positions.clear();
+		self.indices = None;
+		if self.tree.is_empty().not() {
+			Self::create_tree(0, &mut self.tree);
+		}
 	}
 
 	// choose middle with bias to the right, so recursion is deeper on the left
@@ -84,34 +167,70 @@ where
 	}
 
 	pub fn nearest(&self, point: &Point, max_distance: Value) -> Vec<Entry<Value>> {
-		let mut best_set = DynamicSet::new(max_distance);
-		Self::search_nearest(&self.tree, &Ada::get_all(point), 0, &mut best_set);
-		best_set.result()
+		let mut result = Vec::new();
+		self.nearest_into(point, &mut result, max_distance);
+		result
+	}
+
+	// same as nearest, but reuses the allocation of result
+	//   the previous content of result is cleared
+	pub fn nearest_into(&self, point: &Point, result: &mut Vec<Entry<Value>>, max_distance: Value) {
+		let mut best_set = DynamicSet::new(max_distance, result);
+		self.search(&Ada::get_all(point), &mut best_set);
+		best_set.result();
 	}
 
 	pub fn nearest_to_position(&self, position: &[Value; N], data: &mut [Entry<Value>], max_distance: Value) -> usize {
 		let mut best_set = FixedSet::new(max_distance, data);
-		Self::search_nearest(&self.tree, position, 0, &mut best_set);
+		self.search(position, &mut best_set);
+		best_set.result()
+	}
+
+	// same as k_nearest, but stops searching other sections after max_checks points are checked
+	//   the result may miss some of the nearest points, but is faster for large trees
+	pub fn k_nearest_approximate(&self, point: &Point, data: &mut [Entry<Value>], max_distance: Value, max_checks: usize) -> usize {
+		let mut best_set = FixedSet::new(max_distance, data);
+		self.search_limited(&Ada::get_all(point), &mut best_set, max_checks);
 		best_set.result()
 	}
 
 	pub fn empty(&self, point: &Point, max_distance: Value) -> bool {
 		let mut best_set = EmptySet::new(max_distance);
-		Self::search_nearest(&self.tree, &Ada::get_all(point), 0, &mut best_set);
+		self.search(&Ada::get_all(point), &mut best_set);
 		best_set.empty()
 	}
 
+	fn search(&self, position: &[Value; N], best_set: &mut impl BestSet<Value>) {
+		self.search_limited(position, best_set, usize::MAX);
+	}
+
+	fn search_limited(&self, position: &[Value; N], best_set: &mut impl BestSet<Value>, max_checks: usize) {
+		if self.tree.is_empty().not() {
+			let mut checks = max_checks;
+			Self::search_nearest(&self.tree, &self.removed, position, 0, best_set, &mut checks);
+		}
+		for &(point, point_index) in self.added.iter() {
+			let diff = Met::distance(&point, position);
+			if diff < best_set.distance() {
+				best_set.insert(Entry { distance: diff, index: point_index });
+			}
+		}
+	}
+
 	fn search_nearest(
 		tree: &[([Value; N], usize)],
+		removed: &HashSet<usize>,
 		position: &[Value; N],
 		dim: usize,
 		best_set: &mut impl BestSet<Value>,
+		checks: &mut usize,
 	) {
 		//linear search small sections
 		if tree.len() < 32 {
+			*checks = checks.saturating_sub(tree.len());
 			for &(point, point_index) in tree {
 				let diff = Met::distance(&point, position);
-				if diff < best_set.distance() {
+				if diff < best_set.distance() && removed.contains(&point_index).not() {
 					best_set.insert(Entry { distance: diff, index: point_index });
 				}
 			}
@@ -121,8 +240,9 @@ where
 		//check median point
 		let middle = tree.len() / 2;
 		let (point, point_index) = tree[middle];
+		*checks = checks.saturating_sub(1);
 		let diff = Met::distance(&point, position);
-		if diff < best_set.distance() {
+		if diff < best_set.distance() && removed.contains(&point_index).not() {
 			best_set.insert(Entry { distance: diff, index: point_index });
 		}
 
@@ -130,17 +250,18 @@ where
 		let next_dim = (dim + 1) % N;
 		let is_left = position[dim] < point[dim];
 		if is_left {
-			Self::search_nearest(&tree[..middle], position, next_dim, best_set);
+			Self::search_nearest(&tree[..middle], removed, position, next_dim, best_set, checks);
 		} else {
-			Self::search_nearest(&tree[(middle + 1)..], position, next_dim, best_set);
+			Self::search_nearest(&tree[(middle + 1)..], removed, position, next_dim, best_set, checks);
 		}
 
 		//only recurve into the section without the point if the distance is less then to the current worst point found
-		if Met::distance_plane(position, point[dim], dim) < best_set.distance() {
+		//  and the limit for checked points is not reached
+		if *checks > 0 && Met::distance_plane(position, point[dim], dim) < best_set.distance() {
 			if is_left {
-				Self::search_nearest(&tree[(middle + 1)..], position, next_dim, best_set);
+				Self::search_nearest(&tree[(middle + 1)..], removed, position, next_dim, best_set, checks);
 			} else {
-				Self::search_nearest(&tree[..middle], position, next_dim, best_set);
+				Self::search_nearest(&tree[..middle], removed, position, next_dim, best_set, checks);
 			}
 		}
 	}
@@ -10,4 +10,6 @@ pub use kd_tree::Entry;
 pub use kd_tree::KDTree;
 pub use metric::Metric;
 
+pub use metric::ChebyshevDistance;
 pub use metric::EuclideanDistanceSquared;
+pub use metric::ManhattanDistance;
@@ -30,3 +30,53 @@ where
 		diff * diff
 	}
 }
+
+pub struct ManhattanDistance;
+
+impl<const N: usize, Value> Metric<N, Value> for ManhattanDistance
+where
+	Value: PartialOrd
+		+ Default
+		+ Copy
+		+ std::ops::Add<Output = Value>
+		+ std::ops::Sub<Output = Value>,
+{
+	fn distance(left: &[Value; N], right: &[Value; N]) -> Value {
+		(0..N)
+			.map(|d| difference(left[d], right[d]))
+			.fold(Value::default(), |sum, v| sum + v)
+	}
+
+	fn distance_plane(position: &[Value; N], plane: Value, dimension: usize) -> Value {
+		difference(position[dimension], plane)
+	}
+}
+
+pub struct ChebyshevDistance;
+
+impl<const N: usize, Value> Metric<N, Value> for ChebyshevDistance
+where
+	Value: PartialOrd + Default + Copy + std::ops::Sub<Output = Value>,
+{
+	fn distance(left: &[Value; N], right: &[Value; N]) -> Value {
+		(0..N)
+			.map(|d| difference(left[d], right[d]))
+			.fold(Value::default(), |max, v| if v > max { v } else { max })
+	}
+
+	fn distance_plane(position: &[Value; N], plane: Value, dimension: usize) -> Value {
+		difference(position[dimension], plane)
+	}
+}
+
+/// Absolute difference, without requiring signed values.
+fn difference<Value>(left: Value, right: Value) -> Value
+where
+	Value: PartialOrd + std::ops::Sub<Output = Value>,
+{
+	if left < right {
+		right - left
+	} else {
+		left - right
+	}
+}
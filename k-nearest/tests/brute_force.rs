@@ -0,0 +1,166 @@
+use std::ops::Not;
+
+use k_nearest::{
+	ChebyshevDistance, Entry, EuclideanDistanceSquared, KDTree, ManhattanDistance, Metric,
+};
+
+struct Adapter;
+
+impl k_nearest::Adapter<3, f32, [f32; 3]> for Adapter {
+	fn get(point: &[f32; 3], dimension: usize) -> f32 {
+		point[dimension]
+	}
+
+	fn get_all(point: &[f32; 3]) -> [f32; 3] {
+		*point
+	}
+}
+
+/// Deterministic points, so failures are reproducible without a rng dependency.
+fn points(count: usize, seed: u64) -> Vec<[f32; 3]> {
+	let mut state = seed;
+	let mut next = || {
+		state = state
+			.wrapping_mul(6364136223846793005)
+			.wrapping_add(1442695040888963407);
+		(state >> 40) as f32 / (1u64 << 24) as f32
+	};
+	(0..count).map(|_| [next(), next(), next()]).collect()
+}
+
+/// All points closer than `max_distance`, sorted by distance.
+fn brute_force<Met: Metric<3, f32>>(
+	points: &[[f32; 3]],
+	valid: impl Fn(usize) -> bool,
+	position: &[f32; 3],
+	max_distance: f32,
+) -> Vec<Entry<f32>> {
+	let mut result = points
+		.iter()
+		.enumerate()
+		.filter(|&(index, _)| valid(index))
+		.map(|(index, point)| Entry {
+			distance: Met::distance(point, position),
+			index,
+		})
+		.filter(|entry| entry.distance < max_distance)
+		.collect::<Vec<_>>();
+	result.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+	result
+}
+
+fn assert_same(
+	points: &[[f32; 3]],
+	position: &[f32; 3],
+	expected: &[Entry<f32>],
+	found: &[Entry<f32>],
+	distance: impl Fn(&[f32; 3], &[f32; 3]) -> f32,
+) {
+	assert_eq!(expected.len(), found.len());
+	for (expected, found) in expected.iter().zip(found) {
+		assert_eq!(expected.distance, found.distance);
+		assert_eq!(distance(&points[found.index], position), found.distance);
+	}
+}
+
+fn compare<Met: Metric<3, f32>>() {
+	let points = points(2_000, 1);
+	let queries = self::points(50, 2);
+	let tree = KDTree::<3, f32, [f32; 3], Adapter, Met>::new(&points);
+
+	for max_distance in [0.01, 0.1, 0.5, f32::MAX] {
+		for query in queries.iter().chain(points.iter().take(10)) {
+			let expected = brute_force::<Met>(&points, |_| true, query, max_distance);
+
+			for k in [1, 5, 32, 100] {
+				let mut data = vec![Entry { distance: 0.0, index: 0 }; k];
+				let count = tree.k_nearest(query, &mut data, max_distance);
+				let take = expected.len().min(k);
+				assert_same(
+					&points,
+					query,
+					&expected[..take],
+					&data[..count],
+					Met::distance,
+				);
+			}
+
+			if max_distance < f32::MAX {
+				let found = tree.nearest(query, max_distance);
+				assert_same(&points, query, &expected, &found, Met::distance);
+				assert_eq!(tree.empty(query, max_distance), expected.is_empty());
+			}
+		}
+	}
+}
+
+fn compare_changed<Met: Metric<3, f32>>() {
+	let mut points = points(1_000, 3);
+	let mut tree = KDTree::<3, f32, [f32; 3], Adapter, Met>::new(&points[..600]);
+	for (index, point) in points.iter().enumerate().skip(600) {
+		tree.insert(point, index);
+	}
+	for index in (0..points.len()).step_by(3) {
+		assert!(tree.remove(index));
+	}
+	assert!(tree.remove(0).not());
+	// move points by replacing the same index, some of them twice
+	let moved = self::points(200, 5);
+	for (step, point) in moved.into_iter().enumerate() {
+		let index = (step * 3 + 1) % 300;
+		assert!(tree.remove(index));
+		tree.insert(&point, index);
+		points[index] = point;
+	}
+	let valid = |index: usize| index % 3 != 0;
+	assert_eq!(
+		tree.len(),
+		(0..points.len()).filter(|&index| valid(index)).count()
+	);
+
+	for query in self::points(50, 4).iter() {
+		let expected = brute_force::<Met>(&points, valid, query, 0.2);
+		let found = tree.nearest(query, 0.2);
+		assert_same(&points, query, &expected, &found, Met::distance);
+
+		let mut data = [Entry { distance: 0.0, index: 0 }; 8];
+		let count = tree.k_nearest(query, &mut data, 0.2);
+		assert_same(
+			&points,
+			query,
+			&expected[..expected.len().min(8)],
+			&data[..count],
+			Met::distance,
+		);
+	}
+}
+
+#[test]
+fn euclidean() {
+	compare::<EuclideanDistanceSquared>();
+}
+
+#[test]
+fn manhattan() {
+	compare::<ManhattanDistance>();
+}
+
+#[test]
+fn chebyshev() {
+	compare::<ChebyshevDistance>();
+}
+
+#[test]
+fn euclidean_changed() {
+	compare_changed::<EuclideanDistanceSquared>();
+}
+
+#[test]
+fn manhattan_changed() {
+	compare_changed::<ManhattanDistance>();
+}
+
+#[test]
+fn chebyshev_changed() {
+	compare_changed::<ChebyshevDistance>();
+}
@@ -0,0 +1,71 @@
+use k_nearest::{Entry, EuclideanDistanceSquared, KDTree, Metric};
+
+struct Adapter;
+
+impl k_nearest::Adapter<3, f64, [f64; 3]> for Adapter {
+	fn get(point: &[f64; 3], dimension: usize) -> f64 {
+		point[dimension]
+	}
+
+	fn get_all(point: &[f64; 3]) -> [f64; 3] {
+		*point
+	}
+}
+
+/// Typical UTM easting and northing, where f32 only resolves about half a meter.
+const OFFSET: [f64; 3] = [500_000.0, 5_000_000.0, 400.0];
+
+/// Grid with one centimeter spacing around the offset.
+fn grid() -> Vec<[f64; 3]> {
+	let mut points = Vec::new();
+	for x in 0..10 {
+		for y in 0..10 {
+			for z in 0..10 {
+				points.push([
+					OFFSET[0] + x as f64 * 0.01,
+					OFFSET[1] + y as f64 * 0.01,
+					OFFSET[2] + z as f64 * 0.01,
+				]);
+			}
+		}
+	}
+	points
+}
+
+#[test]
+fn utm_coordinates() {
+	// the same grid collapses to a few positions in f32
+	let collapsed = grid()
+		.iter()
+		.map(|p| p.map(|v| (v as f32).to_bits()))
+		.collect::<std::collections::HashSet<_>>();
+	assert!(collapsed.len() < 100);
+
+	let points = grid();
+	let tree = KDTree::<3, f64, [f64; 3], Adapter, EuclideanDistanceSquared>::new(&points);
+
+	for (index, point) in points.iter().enumerate() {
+		let mut data = [Entry { distance: 0.0, index: 0 }; 2];
+		let count = tree.k_nearest(point, &mut data, 1.0);
+		assert_eq!(count, 2);
+		assert_eq!(data[0].index, index);
+		assert_eq!(data[0].distance, 0.0);
+		assert!((data[1].distance - 0.0001).abs() < 1e-9);
+
+		let max_distance = 0.015 * 0.015;
+		let mut expected = points
+			.iter()
+			.enumerate()
+			.filter(|&(_, other)| EuclideanDistanceSquared::distance(other, point) < max_distance)
+			.map(|(index, _)| index)
+			.collect::<Vec<_>>();
+		let mut found = tree
+			.nearest(point, max_distance)
+			.iter()
+			.map(|entry| entry.index)
+			.collect::<Vec<_>>();
+		expected.sort();
+		found.sort();
+		assert_eq!(expected, found);
+	}
+}
@@ -5,7 +5,12 @@ pub struct DepthTexture {
 impl DepthTexture {
 	pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
-	pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+	pub fn new(
+		device: &wgpu::Device,
+		config: &wgpu::SurfaceConfiguration,
+		sample_count: u32,
+		label: &str,
+	) -> Self {
 		let size = wgpu::Extent3d {
 			width: config.width,
 			height: config.height,
@@ -15,7 +20,7 @@ impl DepthTexture {
 			label: Some(label),
 			size,
 			mip_level_count: 1,
-			sample_count: 1,
+			sample_count,
 			dimension: wgpu::TextureDimension::D2,
 			format: Self::DEPTH_FORMAT,
 			view_formats: &[],
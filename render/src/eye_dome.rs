@@ -30,6 +30,10 @@ pub struct EyeDome {
 
 	pub color: na::Point3<f32>,
 	pub strength: f32,
+	/// Distance in pixels to the sampled neighbors.
+	pub radius: f32,
+	/// Number of neighbors sampled on the circle with `radius`.
+	pub sample_count: u32,
 }
 
 #[repr(C)]
@@ -37,6 +41,9 @@ pub struct EyeDome {
 struct EyeDomeUniform {
 	color: [f32; 3],
 	strength: f32,
+	radius: f32,
+	sample_count: u32,
+	_padding: [u32; 2],
 }
 
 impl EyeDome {
@@ -45,6 +52,8 @@ impl EyeDome {
 		config: &wgpu::SurfaceConfiguration,
 		depth: &DepthTexture,
 		strength: f32,
+		radius: f32,
+		sample_count: u32,
 	) -> Self {
 		let depth_layout =
 			state
@@ -57,7 +66,7 @@ impl EyeDome {
 							count: None,
 							ty: wgpu::BindingType::Texture {
 								sample_type: wgpu::TextureSampleType::Float { filterable: false },
-								multisampled: false,
+								multisampled: state.sample_count > 1,
 								view_dimension: wgpu::TextureViewDimension::D2,
 							},
 							visibility: wgpu::ShaderStages::FRAGMENT,
@@ -92,8 +101,14 @@ impl EyeDome {
 
 		let color = [0.0, 0.0, 0.0].into();
 
-		let settings_bind_group =
-			Self::get_settings_bindgroup(state, &settings_layout, color, strength);
+		let settings_bind_group = Self::get_settings_bindgroup(
+			state,
+			&settings_layout,
+			color,
+			strength,
+			radius,
+			sample_count,
+		);
 
 		let vertex_buffer = state
 			.device
@@ -116,7 +131,18 @@ impl EyeDome {
 			.device
 			.create_shader_module(wgpu::ShaderModuleDescriptor {
 				label: Some("eye dome Display Shader"),
-				source: wgpu::ShaderSource::Wgsl(include_str!("eye_dome.wgsl").into()),
+				source: wgpu::ShaderSource::Wgsl(
+					[
+						include_str!("eye_dome.wgsl"),
+						if state.sample_count > 1 {
+							include_str!("eye_dome_multisampled.wgsl")
+						} else {
+							include_str!("eye_dome_single.wgsl")
+						},
+					]
+					.concat()
+					.into(),
+				),
 			});
 
 		let render_pipeline =
@@ -172,6 +198,8 @@ impl EyeDome {
 
 			color,
 			strength,
+			radius,
+			sample_count,
 		}
 	}
 
@@ -213,8 +241,14 @@ impl EyeDome {
 	}
 
 	pub fn update_settings(&mut self, state: &State) {
-		self.settings_bind_group =
-			Self::get_settings_bindgroup(state, &self.settings_layout, self.color, self.strength);
+		self.settings_bind_group = Self::get_settings_bindgroup(
+			state,
+			&self.settings_layout,
+			self.color,
+			self.strength,
+			self.radius,
+			self.sample_count,
+		);
 	}
 
 	fn get_settings_bindgroup(
@@ -222,11 +256,16 @@ impl EyeDome {
 		layout: &wgpu::BindGroupLayout,
 		color: na::Point3<f32>,
 		strength: f32,
+		radius: f32,
+		sample_count: u32,
 	) -> wgpu::BindGroup {
 		let strength = 1.0 - strength;
 		let uniform = EyeDomeUniform {
 			color: color.coords.data.0[0],
 			strength: if strength < 0.1 { 0.1 } else { strength }.powi(6),
+			radius: radius.max(1.0),
+			sample_count: sample_count.max(1),
+			_padding: [0, 0],
 		};
 		let buffer = state
 			.device
@@ -23,10 +23,12 @@ fn vs_main(
 struct Settings {
     color: vec3<f32>,
     strength: f32,
+    radius: f32,
+    sample_count: u32,
+    _padding: vec2<u32>,
 };
 
-@group(0) @binding(0)
-var depths: texture_2d<f32>;
+// `depths` is declared in `eye_dome_single.wgsl` or `eye_dome_multisampled.wgsl`
 @group(0) @binding(1)
 var depth_sampler: sampler;
 
@@ -45,11 +47,14 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     if depth == 1.0 {
         return vec4<f32>(0.0, 0.0, 0.0, 0.0);
     }
+    // sample neighbors on a circle around the pixel
+    let step = 6.283185307 / f32(settings.sample_count);
     var m = depth;
-    m = max(m, get_depth(coord + vec2<f32>(-delta.x, 0)));
-    m = max(m, get_depth(coord + vec2<f32>(delta.x, 0)));
-    m = max(m, get_depth(coord + vec2<f32>(0, -delta.y)));
-    m = max(m, get_depth(coord + vec2<f32>(0, delta.y)));
+    for (var i = 0u; i < settings.sample_count; i++) {
+        let angle = f32(i) * step;
+        let offset = vec2<f32>(cos(angle), sin(angle)) * settings.radius * delta;
+        m = max(m, get_depth(coord + offset));
+    }
     m = min(m, depth + settings.strength);
     return vec4<f32>(settings.color, (m - depth) / settings.strength);
 }
@@ -59,7 +64,10 @@ fn get_depth(uv: vec2<f32>) -> f32 {
     let near = 0.1;
     let far = 10000.0;
 
-    let depth = textureSample(depths, depth_sampler, uv).x;
+    // load instead of sample, works for single and multisampled depth
+    let size = vec2<i32>(textureDimensions(depths));
+    let coord = clamp(vec2<i32>(uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    let depth = textureLoad(depths, coord, 0).x;
     if depth >= 1.0{
         return 1.0;
     }
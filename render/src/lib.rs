@@ -4,6 +4,7 @@ mod eye_dome;
 mod lines;
 mod lookup;
 mod mesh;
+mod offscreen;
 mod point;
 mod point_cloud;
 mod state;
@@ -17,6 +18,7 @@ pub use eye_dome::*;
 pub use lines::*;
 pub use lookup::*;
 pub use mesh::*;
+pub use offscreen::*;
 pub use point::*;
 pub use point_cloud::*;
 pub use state::*;
@@ -9,7 +9,23 @@ pub struct Lookup {
 
 impl Lookup {
 	pub fn new_png(state: &State, data: &[u8], range: u32) -> Self {
-		let texture = Texture::new_1d(state, data, state.surface_format);
+		Self::new_png_dimmed(state, data, range, 1.0)
+	}
+
+	/// Lookup with every color multiplied by `brightness`.
+	pub fn new_png_dimmed(state: &State, data: &[u8], range: u32, brightness: f32) -> Self {
+		let texture = Texture::new_row(state, data, state.surface_format);
+		Self::from_texture(state, texture, range, brightness)
+	}
+
+	/// Lookup with the colors evenly spaced over the range.
+	/// The number of colors must be a power of two.
+	pub fn new_colors(state: &State, colors: &[[u8; 4]], range: u32) -> Self {
+		let texture = Texture::new_row_rgba(state, colors, state.surface_format);
+		Self::from_texture(state, texture, range, 1.0)
+	}
+
+	fn from_texture(state: &State, texture: Texture, range: u32, brightness: f32) -> Self {
 		assert!(texture.size.x.is_power_of_two());
 		assert_eq!(texture.size.y, 1);
 
@@ -24,7 +40,7 @@ impl Lookup {
 			.device
 			.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 				label: Some("Camera Buffer"),
-				contents: bytemuck::cast_slice(&[mult, shift, 0, 0]),
+				contents: bytemuck::cast_slice(&[mult, shift, brightness.to_bits(), 0]),
 				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
 			});
 
@@ -60,7 +76,7 @@ impl Lookup {
 						visibility: wgpu::ShaderStages::FRAGMENT,
 						ty: wgpu::BindingType::Texture {
 							multisampled: false,
-							view_dimension: wgpu::TextureViewDimension::D1,
+							view_dimension: wgpu::TextureViewDimension::D2,
 							sample_type: wgpu::TextureSampleType::Float { filterable: true },
 						},
 						count: None,
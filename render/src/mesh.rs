@@ -66,7 +66,7 @@ impl MeshState {
 					bias: wgpu::DepthBiasState::default(),
 				}),
 				multisample: wgpu::MultisampleState {
-					count: 1,
+					count: state.sample_count,
 					mask: !0,
 					alpha_to_coverage_enabled: false,
 				},
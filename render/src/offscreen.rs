@@ -0,0 +1,147 @@
+use super::*;
+
+/// Color format for offscreen rendering, matches the pixel layout of `image::RgbaImage`.
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Render target without a window.
+///
+/// The depth texture can be used for the eye dome like the depth texture of a window.
+pub struct Offscreen {
+	config: wgpu::SurfaceConfiguration,
+	texture: wgpu::Texture,
+	depth_texture: DepthTexture,
+	multisampled: Option<wgpu::TextureView>,
+}
+
+impl Offscreen {
+	pub fn new(state: &State, width: u32, height: u32) -> Self {
+		let config = wgpu::SurfaceConfiguration {
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+			format: state.surface_format,
+			width,
+			height,
+			present_mode: wgpu::PresentMode::Fifo,
+			alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+			desired_maximum_frame_latency: 2,
+			view_formats: Vec::new(),
+		};
+		let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("offscreen color"),
+			size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: state.surface_format,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+			view_formats: &[],
+		});
+		let depth_texture = DepthTexture::new(
+			&state.device,
+			&config,
+			state.sample_count,
+			"offscreen depth",
+		);
+		let multisampled = Window::create_multisampled(state, &config);
+		Self {
+			config,
+			texture,
+			depth_texture,
+			multisampled,
+		}
+	}
+
+	pub fn config(&self) -> &wgpu::SurfaceConfiguration {
+		&self.config
+	}
+
+	pub fn depth_texture(&self) -> &DepthTexture {
+		&self.depth_texture
+	}
+
+	/// Render a frame and return the pixels.
+	///
+	/// The pipelines must be created with the same `state`, the background is set by the render pass.
+	/// Blocks until the GPU is finished.
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn render(
+		&self,
+		state: &State,
+		render: impl for<'b> FnOnce(&'b mut RenderContext),
+	) -> image::RgbaImage {
+		let (width, height) = (self.config.width, self.config.height);
+		let encoder = state
+			.device
+			.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Offscreen Encoder"),
+			});
+		let mut context = RenderContext {
+			encoder,
+			view: self.texture.create_view(&Default::default()),
+			multisampled: self.multisampled.as_ref(),
+			depth_texture: &self.depth_texture.view,
+		};
+		render(&mut context);
+
+		// rows in the buffer must be aligned
+		let bytes_per_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+			* wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+		let buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("offscreen readback"),
+			size: (bytes_per_row * height) as u64,
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+			mapped_at_creation: false,
+		});
+		context.encoder.copy_texture_to_buffer(
+			self.texture.as_image_copy(),
+			wgpu::ImageCopyBuffer {
+				buffer: &buffer,
+				layout: wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: Some(bytes_per_row),
+					rows_per_image: Some(height),
+				},
+			},
+			wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+		);
+		state.queue.submit(Some(context.encoder.finish()));
+
+		let slice = buffer.slice(..);
+		slice.map_async(wgpu::MapMode::Read, |res| {
+			if let Err(err) = res {
+				log::error!("Failed to read offscreen image: {}", err);
+			}
+		});
+		state.device.poll(wgpu::Maintain::Wait);
+
+		let data = slice.get_mapped_range();
+		let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+		for row in data.chunks_exact(bytes_per_row as usize) {
+			pixels.extend_from_slice(&row[..(width * 4) as usize]);
+		}
+		drop(data);
+		buffer.unmap();
+
+		if matches!(
+			state.surface_format,
+			wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+		) {
+			for pixel in pixels.chunks_exact_mut(4) {
+				pixel.swap(0, 2);
+			}
+		}
+		image::RgbaImage::from_raw(width, height, pixels).unwrap()
+	}
+}
+
+/// Render a frame without a window and return the pixels.
+///
+/// See `Offscreen::render`, use `Offscreen` directly for post processing with the depth texture.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_to_image(
+	state: &State,
+	width: u32,
+	height: u32,
+	render: impl for<'b> FnOnce(&'b mut RenderContext),
+) -> image::RgbaImage {
+	Offscreen::new(state, width, height).render(state, render)
+}
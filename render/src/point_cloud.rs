@@ -1,4 +1,8 @@
 use nalgebra as na;
+use std::{
+	collections::HashMap,
+	ops::{Not, Range},
+};
 use wgpu::util::DeviceExt;
 
 use crate::{
@@ -26,6 +30,12 @@ impl PointCloudState {
 		let shader = state
 			.device
 			.create_shader_module(wgpu::include_wgsl!("point_cloud.wgsl"));
+		// smooth point edges, only valid with multiple samples
+		let alpha_to_coverage = state.sample_count > 1;
+		let constants = HashMap::from([(
+			String::from("alpha_to_coverage"),
+			f64::from(u8::from(alpha_to_coverage)),
+		)]);
 		let render_pipeline_layout =
 			state
 				.device
@@ -62,7 +72,10 @@ impl PointCloudState {
 						blend: Some(wgpu::BlendState::REPLACE),
 						write_mask: wgpu::ColorWrites::ALL,
 					})],
-					compilation_options: Default::default(),
+					compilation_options: wgpu::PipelineCompilationOptions {
+						constants: &constants,
+						..Default::default()
+					},
 				}),
 				primitive: wgpu::PrimitiveState {
 					topology: wgpu::PrimitiveTopology::TriangleList,
@@ -81,9 +94,9 @@ impl PointCloudState {
 					bias: wgpu::DepthBiasState::default(),
 				}),
 				multisample: wgpu::MultisampleState {
-					count: 1,
+					count: state.sample_count,
 					mask: !0,
-					alpha_to_coverage_enabled: false,
+					alpha_to_coverage_enabled: alpha_to_coverage,
 				},
 				multiview: None,
 				cache: None,
@@ -139,20 +152,61 @@ impl PointCloud {
 			.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 				label: Some("point cloud buffer"),
 				contents: bytemuck::cast_slice(vertices),
-				usage: wgpu::BufferUsages::VERTEX,
+				usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
 			});
 
 		Self { buffer, instances: vertices.len() as u32 }
 	}
 
+	/// Replace the points, the buffer is reused if the points fit.
+	pub fn update(&mut self, state: &State, vertices: &[na::Point3<f32>]) {
+		self.update_range(state, vertices, 0..vertices.len());
+	}
+
+	/// Replace the points, only the `changed` points are written if the buffer is reused.
+	pub fn update_range(
+		&mut self,
+		state: &State,
+		vertices: &[na::Point3<f32>],
+		changed: Range<usize>,
+	) {
+		let size = std::mem::size_of::<na::Point3<f32>>();
+		let contents = bytemuck::cast_slice(vertices);
+		let changed = (changed.start * size)..(changed.end * size);
+		if write_buffer(
+			state,
+			&mut self.buffer,
+			contents,
+			changed,
+			"point cloud buffer",
+		)
+		.not()
+		{
+			self.buffer = Self::new(state, vertices).buffer;
+		}
+		self.instances = vertices.len() as u32;
+	}
+
+	/// Maximal number of points without a new buffer.
+	pub fn capacity(&self) -> u32 {
+		(self.buffer.size() / std::mem::size_of::<na::Point3<f32>>() as u64) as u32
+	}
+
 	pub fn render<'a>(
 		&'a self,
 		point_cloud_pass: &mut PointCloudPass<'a>,
 		property: &'a PointCloudProperty,
 	) {
-		point_cloud_pass
-			.0
-			.set_vertex_buffer(1, self.buffer.slice(..));
+		if self.instances == 0 {
+			return;
+		}
+		point_cloud_pass.0.set_vertex_buffer(
+			1,
+			self.buffer.slice(
+				0..(self.instances as usize * std::mem::size_of::<na::Point3<f32>>())
+					as wgpu::BufferAddress,
+			),
+		);
 		point_cloud_pass.0.set_vertex_buffer(
 			2,
 			property.buffer.slice(
@@ -186,23 +240,94 @@ impl PointCloudProperty {
 			.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 				label: Some("point cloud property buffer"),
 				contents: bytemuck::cast_slice(data),
-				usage: wgpu::BufferUsages::VERTEX,
+				usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
 			});
 
 		Self { buffer, length: data.len() as u32 }
 	}
 
+	/// Replace the values, the buffer is reused if the values fit.
+	pub fn update(&mut self, state: &State, data: &[u32]) {
+		self.update_range(state, data, 0..data.len());
+	}
+
+	/// Replace the values, only the `changed` values are written if the buffer is reused.
+	pub fn update_range(&mut self, state: &State, data: &[u32], changed: Range<usize>) {
+		let size = std::mem::size_of::<u32>();
+		let contents = bytemuck::cast_slice(data);
+		let changed = (changed.start * size)..(changed.end * size);
+		if write_buffer(
+			state,
+			&mut self.buffer,
+			contents,
+			changed,
+			"point cloud property buffer",
+		)
+		.not()
+		{
+			self.buffer = Self::new(state, data).buffer;
+		}
+		self.length = data.len() as u32;
+	}
+
+	/// Maximal number of values without a new buffer.
+	pub fn capacity(&self) -> u32 {
+		(self.buffer.size() / std::mem::size_of::<u32>() as u64) as u32
+	}
+
 	pub fn new_empty(state: &State, size: usize) -> Self {
 		let buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
 			label: Some("point cloud property buffer"),
 			size: (size * std::mem::size_of::<u32>()) as u64,
-			usage: wgpu::BufferUsages::VERTEX,
+			usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
 			mapped_at_creation: false,
 		});
 		Self { buffer, length: 0 }
 	}
 }
 
+/// Write `contents` into `buffer` if it fits, or into a new buffer with some spare capacity.
+/// Only the `changed` bytes are written into a reused buffer.
+/// Returns `false` if a buffer with the exact size should be created instead.
+///
+/// Buffers much larger than the contents are not reused to release the memory.
+fn write_buffer(
+	state: &State,
+	buffer: &mut wgpu::Buffer,
+	contents: &[u8],
+	changed: Range<usize>,
+	label: &str,
+) -> bool {
+	let size = contents.len() as u64;
+	if size == 0 {
+		return false;
+	}
+	if size <= buffer.size() && size >= buffer.size() / 4 {
+		let end = changed.end.min(contents.len());
+		let start = changed.start.min(end);
+		if start < end {
+			state
+				.queue
+				.write_buffer(buffer, start as u64, &contents[start..end]);
+		}
+		return true;
+	}
+	if size > buffer.size() && buffer.size() > 0 {
+		// growing buffer, reserve space for the next growth
+		let capacity = (size + size / 2).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+		let new = state.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some(label),
+			size: capacity,
+			usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+		state.queue.write_buffer(&new, 0, contents);
+		*buffer = new;
+		return true;
+	}
+	false
+}
+
 pub struct PointCloudEnvironment {
 	bind_group: wgpu::BindGroup,
 	pub min: u32,
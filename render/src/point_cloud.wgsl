@@ -61,20 +61,31 @@ fn vs_main(
 struct LookupUniform {
     mult: u32,
     shift: u32,
-    _padding: vec2<u32>,
+    brightness: f32,
+    _padding: u32,
 };
 
 @group(2) @binding(0)
-var lookup: texture_1d<f32>;
+var lookup: texture_2d<f32>;
 @group(2) @binding(1)
 var<uniform> lookup_uniform: LookupUniform;
 
+// use the alpha for the coverage of the point, set if multisampled
+override alpha_to_coverage: bool = false;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    if in.pos.x * in.pos.x + in.pos.y * in.pos.y >= 1.0 {
+    let distance = length(in.pos);
+    if distance >= 1.0 {
         discard;
     }
     // return vec4<f32>(1.0, 1.0, 1.0, 1.0);
     let idx = (in.value * lookup_uniform.mult) >> lookup_uniform.shift;
-    return textureLoad(lookup, idx, 0);
+    let color = textureLoad(lookup, vec2<u32>(idx, 0u), 0);
+    var coverage = 1.0;
+    if alpha_to_coverage {
+        // fraction of the pixel inside the point
+        coverage = clamp((1.0 - distance) / max(fwidth(distance), 0.0001), 0.0, 1.0);
+    }
+    return vec4<f32>(color.rgb * lookup_uniform.brightness, color.a * coverage);
 }
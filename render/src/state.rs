@@ -1,12 +1,27 @@
-use std::sync::Arc;
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc,
+};
 
 use super::*;
 
+/// Backends for the window and the default headless state.
+#[cfg(not(feature = "webgl"))]
+const BACKENDS: wgpu::Backends = wgpu::Backends::PRIMARY;
+#[cfg(feature = "webgl")]
+const BACKENDS: wgpu::Backends = wgpu::Backends::GL;
+
 #[derive(Debug)]
 pub struct State {
 	pub device: wgpu::Device,
 	pub queue: wgpu::Queue,
 	pub surface_format: wgpu::TextureFormat,
+	/// Samples per pixel for the 3D render pass, pipelines must be recreated after a change.
+	pub sample_count: u32,
+	/// Sample counts supported for the surface and depth format, only `1` for the GL backend.
+	pub supported_sample_counts: Vec<u32>,
+	/// Set by the device lost callback.
+	lost: Arc<AtomicBool>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -22,19 +37,40 @@ pub enum RenderError {
 
 	#[error("Failed to get WebGPU device")]
 	RequestDeviceError,
+
+	#[error("WebGPU device lost")]
+	DeviceLost,
+
+	#[error("Out of GPU memory")]
+	OutOfMemory,
 }
 
 impl State {
 	pub async fn new(window: Arc<winit::window::Window>) -> Result<(Self, Window), RenderError> {
-		let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-			#[cfg(not(feature = "webgl"))]
-			backends: wgpu::Backends::PRIMARY,
-			#[cfg(feature = "webgl")]
-			backends: wgpu::Backends::GL,
-			dx12_shader_compiler: wgpu::Dx12Compiler::default(),
-			flags: wgpu::InstanceFlags::default(),
-			gles_minor_version: wgpu::Gles3MinorVersion::default(),
-		});
+		let (state, surface, config) = Self::for_window(window.clone()).await?;
+		let window = Window::new(window, config, surface, &state);
+
+		Ok((state, window))
+	}
+
+	/// Create a new device and surface for the window after the device was lost.
+	///
+	/// The sample count is kept if supported, all other GPU resources must be created again.
+	pub async fn recover(&mut self, window: &mut Window) -> Result<(), RenderError> {
+		window.release_surface();
+		let (mut state, surface, config) = Self::for_window(window.handle()).await?;
+		if state.supported_sample_counts.contains(&self.sample_count) {
+			state.sample_count = self.sample_count;
+		}
+		*self = state;
+		window.replace_surface(surface, config, self);
+		Ok(())
+	}
+
+	async fn for_window(
+		window: Arc<winit::window::Window>,
+	) -> Result<(Self, wgpu::Surface<'static>, wgpu::SurfaceConfiguration), RenderError> {
+		let instance = Self::instance(BACKENDS);
 
 		let surface = instance.create_surface(window.clone())?;
 
@@ -47,22 +83,6 @@ impl State {
 			.await
 			.ok_or(RenderError::NoWebGPUSupport)?;
 
-		let (device, queue) = adapter
-			.request_device(
-				&wgpu::DeviceDescriptor {
-					required_features: wgpu::Features::empty(),
-					#[cfg(not(feature = "webgl"))]
-					required_limits: wgpu::Limits::default(),
-					#[cfg(feature = "webgl")]
-					required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
-					label: None,
-					memory_hints: wgpu::MemoryHints::Performance,
-				},
-				None,
-			)
-			.await
-			.map_err(|_| RenderError::RequestDeviceError)?;
-
 		let size = window.inner_size();
 		let surface_caps = surface.get_capabilities(&adapter);
 
@@ -82,13 +102,92 @@ impl State {
 			desired_maximum_frame_latency: 2,
 			view_formats: Vec::new(),
 		};
-		surface.configure(&device, &config);
 
-		let depth_texture = DepthTexture::new(&device, &config, "depth");
+		let state = Self::from_adapter(&adapter, surface_format).await?;
+		surface.configure(&state.device, &config);
+
+		Ok((state, surface, config))
+	}
+
+	/// State without a window to render offscreen with `Offscreen`.
+	///
+	/// `fallback` selects a software adapter from any backend, so the same image is rendered without a GPU.
+	pub async fn new_headless(fallback: bool) -> Result<Self, RenderError> {
+		let backends = if fallback {
+			wgpu::Backends::all()
+		} else {
+			BACKENDS
+		};
+		let adapter = Self::instance(backends)
+			.request_adapter(&wgpu::RequestAdapterOptions {
+				power_preference: wgpu::PowerPreference::HighPerformance,
+				compatible_surface: None,
+				force_fallback_adapter: fallback,
+			})
+			.await
+			.ok_or(RenderError::NoWebGPUSupport)?;
+		Self::from_adapter(&adapter, OFFSCREEN_FORMAT).await
+	}
+
+	fn instance(backends: wgpu::Backends) -> wgpu::Instance {
+		wgpu::Instance::new(wgpu::InstanceDescriptor {
+			backends,
+			dx12_shader_compiler: wgpu::Dx12Compiler::default(),
+			flags: wgpu::InstanceFlags::default(),
+			gles_minor_version: wgpu::Gles3MinorVersion::default(),
+		})
+	}
+
+	async fn from_adapter(
+		adapter: &wgpu::Adapter,
+		surface_format: wgpu::TextureFormat,
+	) -> Result<Self, RenderError> {
+		let (device, queue) = adapter
+			.request_device(
+				&wgpu::DeviceDescriptor {
+					required_features: wgpu::Features::empty(),
+					#[cfg(not(feature = "webgl"))]
+					required_limits: wgpu::Limits::default(),
+					#[cfg(feature = "webgl")]
+					required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
+					label: None,
+					memory_hints: wgpu::MemoryHints::Performance,
+				},
+				None,
+			)
+			.await
+			.map_err(|_| RenderError::RequestDeviceError)?;
+
+		let lost = Arc::new(AtomicBool::new(false));
+		let flag = lost.clone();
+		device.set_device_lost_callback(move |reason, message| {
+			log::error!("Device lost ({:?}): {}", reason, message);
+			flag.store(true, Ordering::Relaxed);
+		});
 
-		let window = Window::new(window, config, surface, depth_texture);
+		let color_features = adapter.get_texture_format_features(surface_format).flags;
+		let depth_features = adapter
+			.get_texture_format_features(DepthTexture::DEPTH_FORMAT)
+			.flags;
+		// the GL backend can not sample the multisampled depth texture for the eye dome
+		let supported_sample_counts = if adapter.get_info().backend == wgpu::Backend::Gl {
+			vec![1]
+		} else {
+			color_features
+				.supported_sample_counts()
+				.into_iter()
+				.filter(|&count| depth_features.sample_count_supported(count))
+				.collect()
+		};
 
-		Ok((Self { device, queue, surface_format }, window))
+		Ok(Self {
+			device,
+			queue,
+			surface_format,
+			sample_count: 1,
+			supported_sample_counts,
+			lost,
+		})
 	}
 
 	pub fn device(&self) -> &wgpu::Device {
@@ -102,4 +201,9 @@ impl State {
 	pub fn surface_format(&self) -> wgpu::TextureFormat {
 		self.surface_format
 	}
+
+	/// `true` if the device was lost and all GPU resources are invalid.
+	pub fn is_lost(&self) -> bool {
+		self.lost.load(Ordering::Relaxed)
+	}
 }
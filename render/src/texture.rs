@@ -1,4 +1,3 @@
-use image::GenericImageView;
 use nalgebra as na;
 
 use crate::State;
@@ -15,8 +14,17 @@ impl Texture {
 		Self::new_xd(state, data, wgpu::TextureDimension::D2, format)
 	}
 
-	pub fn new_1d(state: &State, data: &[u8], format: wgpu::TextureFormat) -> Self {
-		Self::new_xd(state, data, wgpu::TextureDimension::D1, format)
+	/// Texture with a single row.
+	///
+	/// Stored as 2D texture, 1D textures read as zero with the desktop OpenGL backend.
+	pub fn new_row(state: &State, data: &[u8], format: wgpu::TextureFormat) -> Self {
+		Self::new_xd(state, data, wgpu::TextureDimension::D2, format)
+	}
+
+	/// Texture with a single row from RGBA colors.
+	pub fn new_row_rgba(state: &State, colors: &[[u8; 4]], format: wgpu::TextureFormat) -> Self {
+		let img = image::RgbaImage::from_raw(colors.len() as u32, 1, colors.concat()).unwrap();
+		Self::from_image(state, img, wgpu::TextureDimension::D2, format)
 	}
 
 	fn new_xd(
@@ -26,7 +34,16 @@ impl Texture {
 		format: wgpu::TextureFormat,
 	) -> Self {
 		let img = image::load_from_memory(data).unwrap();
-		let dimensions = img.dimensions();
+		Self::from_image(state, img.to_rgba8(), dimension, format)
+	}
+
+	fn from_image(
+		state: &State,
+		mut data: image::RgbaImage,
+		dimension: TextureDimension,
+		format: wgpu::TextureFormat,
+	) -> Self {
+		let dimensions = data.dimensions();
 
 		let texture_size = wgpu::Extent3d {
 			width: dimensions.0,
@@ -45,7 +62,6 @@ impl Texture {
 		});
 
 		// swap for brga formats
-		let mut data = img.to_rgba8();
 		match format {
 			wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
 				for p in data.pixels_mut() {
@@ -1,4 +1,7 @@
-use std::{ops::Deref, sync::Arc};
+use std::{
+	ops::{Deref, Not},
+	sync::Arc,
+};
 
 use nalgebra as na;
 
@@ -9,8 +12,11 @@ pub type WindowId = winit::window::WindowId;
 pub struct Window {
 	window: Arc<winit::window::Window>,
 	config: wgpu::SurfaceConfiguration,
-	surface: wgpu::Surface<'static>,
+	/// `None` while the device is recreated.
+	surface: Option<wgpu::Surface<'static>>,
 	depth_texture: DepthTexture,
+	/// Color target resolved into the surface, `None` without multisampling.
+	multisampled: Option<wgpu::TextureView>,
 }
 
 impl Deref for Window {
@@ -26,9 +32,39 @@ impl Window {
 		window: Arc<winit::window::Window>,
 		config: wgpu::SurfaceConfiguration,
 		surface: wgpu::Surface<'static>,
-		depth_texture: DepthTexture,
+		state: &State,
 	) -> Self {
-		Self { window, config, surface, depth_texture }
+		Self {
+			depth_texture: DepthTexture::new(&state.device, &config, state.sample_count, "depth"),
+			multisampled: Self::create_multisampled(state, &config),
+			window,
+			config,
+			surface: Some(surface),
+		}
+	}
+
+	pub(crate) fn create_multisampled(
+		state: &State,
+		config: &wgpu::SurfaceConfiguration,
+	) -> Option<wgpu::TextureView> {
+		if state.sample_count <= 1 {
+			return None;
+		}
+		let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("multisampled color"),
+			size: wgpu::Extent3d {
+				width: config.width,
+				height: config.height,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: state.sample_count,
+			dimension: wgpu::TextureDimension::D2,
+			format: config.format,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+			view_formats: &[],
+		});
+		Some(texture.create_view(&Default::default()))
 	}
 
 	pub fn get_aspect(&self) -> f32 {
@@ -82,13 +118,51 @@ impl Window {
 		let size = self.window.inner_size();
 		self.config.width = size.width;
 		self.config.height = size.height;
-		self.surface.configure(&state.device, &self.config);
-		self.depth_texture = DepthTexture::new(&state.device, &self.config, "depth");
+		if let Some(surface) = &self.surface {
+			surface.configure(&state.device, &self.config);
+		}
+		self.depth_texture =
+			DepthTexture::new(&state.device, &self.config, state.sample_count, "depth");
+		self.multisampled = Self::create_multisampled(state, &self.config);
+	}
+
+	/// Drop the surface of the lost device, the window allows only one surface on some platforms.
+	pub(crate) fn release_surface(&mut self) {
+		self.surface = None;
 	}
 
-	pub fn render(&self, state: &State, render: impl for<'b> FnOnce(&'b mut RenderContext)) {
-		let Some(output) = self.surface.get_current_texture().ok() else {
-			return;
+	/// Use the surface of a new device, the render targets are created again.
+	pub(crate) fn replace_surface(
+		&mut self,
+		surface: wgpu::Surface<'static>,
+		config: wgpu::SurfaceConfiguration,
+		state: &State,
+	) {
+		self.surface = Some(surface);
+		self.config = config;
+		self.resized(state);
+	}
+
+	/// Render a frame.
+	///
+	/// A lost or outdated surface is reconfigured and the frame is skipped.
+	pub fn render(
+		&self,
+		state: &State,
+		render: impl for<'b> FnOnce(&'b mut RenderContext),
+	) -> Result<(), RenderError> {
+		let Some(surface) = self.surface.as_ref().filter(|_| state.is_lost().not()) else {
+			return Err(RenderError::DeviceLost);
+		};
+		let output = match surface.get_current_texture() {
+			Ok(output) => output,
+			Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+				surface.configure(&state.device, &self.config);
+				self.window.request_redraw();
+				return Ok(());
+			},
+			Err(wgpu::SurfaceError::Timeout) => return Ok(()),
+			Err(wgpu::SurfaceError::OutOfMemory) => return Err(RenderError::OutOfMemory),
 		};
 		let view = output.texture.create_view(&Default::default());
 
@@ -101,6 +175,7 @@ impl Window {
 		let mut context = RenderContext {
 			encoder,
 			view,
+			multisampled: self.multisampled.as_ref(),
 			depth_texture: &self.depth_texture.view,
 		};
 
@@ -108,11 +183,16 @@ impl Window {
 
 		state.queue.submit(Some(context.encoder.finish()));
 		output.present();
+		Ok(())
 	}
 
 	pub fn inner(&self) -> &winit::window::Window {
 		&self.window
 	}
+
+	pub(crate) fn handle(&self) -> Arc<winit::window::Window> {
+		self.window.clone()
+	}
 }
 
 // Window stayed open, but unresponsive. Just hide it.
@@ -123,9 +203,10 @@ impl Drop for Window {
 }
 
 pub struct RenderContext<'a> {
-	encoder: wgpu::CommandEncoder,
-	view: wgpu::TextureView,
-	depth_texture: &'a wgpu::TextureView,
+	pub(crate) encoder: wgpu::CommandEncoder,
+	pub(crate) view: wgpu::TextureView,
+	pub(crate) multisampled: Option<&'a wgpu::TextureView>,
+	pub(crate) depth_texture: &'a wgpu::TextureView,
 }
 
 impl<'a> RenderContext<'a> {
@@ -138,8 +219,8 @@ impl<'a> RenderContext<'a> {
 			RenderPass::new(self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
 				label: Some("Render Pass"),
 				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-					view: &self.view,
-					resolve_target: None,
+					view: self.multisampled.unwrap_or(&self.view),
+					resolve_target: self.multisampled.map(|_| &self.view),
 					ops: wgpu::Operations {
 						load: wgpu::LoadOp::Clear(wgpu::Color {
 							r: background.x as f64,
@@ -0,0 +1,272 @@
+//! Render small scenes on a software adapter and compare them with the reference images in `tests/golden`.
+//!
+//! Run with `UPDATE_GOLDEN=1` to replace the reference images after an intended change.
+//! The tests need an adapter and are ignored by default, run them with `cargo test -p render -- --ignored`.
+//! The multisampled test needs an adapter with multisampling, the GL backend does not support it.
+
+use nalgebra as na;
+use std::{path::PathBuf, sync::Mutex};
+
+const SIZE: u32 = 96;
+
+/// Largest difference per channel for equal pixels.
+const CHANNEL_TOLERANCE: u8 = 16;
+
+/// Largest fraction of different pixels.
+const PIXEL_TOLERANCE: f32 = 0.01;
+
+const BACKGROUND: na::Point3<f32> = na::Point3::new(1.0, 1.0, 1.0);
+
+/// The software adapter is not shared between threads.
+static ADAPTER: Mutex<()> = Mutex::new(());
+
+/// Lookups of the viewer.
+const TURBO: &[u8] = include_bytes!("../../treee/assets/grad_turbo.png");
+const WARM: &[u8] = include_bytes!("../../treee/assets/grad_warm.png");
+const WHITE: &[u8] = include_bytes!("../../treee/assets/white.png");
+
+struct Scene {
+	state: render::State,
+	offscreen: render::Offscreen,
+	camera: render::Camera3DGPU,
+	environment: render::PointCloudEnvironment,
+	cloud: render::PointCloud,
+	property: render::PointCloudProperty,
+}
+
+impl Scene {
+	fn new(sample_count: u32) -> Self {
+		let mut state = pollster::block_on(render::State::new_headless(true))
+			.unwrap_or_else(|err| panic!("no software adapter: {}", err));
+		assert!(
+			state.supported_sample_counts.contains(&sample_count),
+			"{} samples not supported by the adapter",
+			sample_count,
+		);
+		state.sample_count = sample_count;
+		let offscreen = render::Offscreen::new(&state, SIZE, SIZE);
+
+		let camera = render::Camera3D {
+			aspect: 1.0,
+			fovy: 60.0f32.to_radians(),
+			near: 0.1,
+			far: 100.0,
+		};
+		let view = na::Isometry3::look_at_rh(
+			&na::point![2.5, 2.0, 3.5],
+			&na::Point3::origin(),
+			&na::Vector3::y(),
+		);
+		let transform = na::Affine3::from_matrix_unchecked(view.inverse().to_homogeneous());
+		let camera = render::Camera3DGPU::new(&state, &camera, &transform);
+
+		// cube of points, colored by height
+		let mut points = Vec::new();
+		let mut values = Vec::new();
+		for x in 0..8 {
+			for y in 0..8 {
+				for z in 0..8 {
+					let p =
+						na::point![x as f32, y as f32, z as f32] / 3.5 - na::vector![1.0, 1.0, 1.0];
+					points.push(p);
+					values.push((y as f64 / 7.0 * u32::MAX as f64) as u32);
+				}
+			}
+		}
+
+		Self {
+			environment: render::PointCloudEnvironment::new(&state, 0, u32::MAX, 0.1),
+			cloud: render::PointCloud::new(&state, &points),
+			property: render::PointCloudProperty::new(&state, &values),
+			camera,
+			offscreen,
+			state,
+		}
+	}
+
+	/// Render the points with `lookup` and the eye dome if `eye_dome` is set.
+	fn points(&self, lookup: &[u8], eye_dome: bool) -> image::RgbaImage {
+		let lookup = render::Lookup::new_png(&self.state, lookup, u32::MAX);
+		let point_cloud_state = render::PointCloudState::new(&self.state);
+		let eye_dome = eye_dome.then(|| {
+			render::EyeDome::new(
+				&self.state,
+				self.offscreen.config(),
+				self.offscreen.depth_texture(),
+				0.7,
+				1.0,
+				4,
+			)
+		});
+		self.offscreen.render(&self.state, |context| {
+			let mut render_pass = context.render_pass(BACKGROUND);
+			let pass = point_cloud_state.render(
+				&mut render_pass,
+				&self.camera,
+				&lookup,
+				&self.environment,
+			);
+			self.cloud.render(pass, &self.property);
+			drop(render_pass);
+
+			if let Some(eye_dome) = &eye_dome {
+				let mut render_pass = context.post_process_pass();
+				eye_dome.render(&mut render_pass);
+			}
+		})
+	}
+}
+
+fn reference_path(name: &str) -> PathBuf {
+	PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/golden")
+		.join(format!("{}.png", name))
+}
+
+fn reference(name: &str) -> image::RgbaImage {
+	let path = reference_path(name);
+	image::open(&path)
+		.unwrap_or_else(|err| panic!("missing reference {}: {}", path.display(), err))
+		.into_rgba8()
+}
+
+/// Fraction of the pixels with a larger difference than `tolerance` in any channel.
+fn different(reference: &image::RgbaImage, image: &image::RgbaImage, tolerance: u8) -> f32 {
+	assert_eq!(reference.dimensions(), image.dimensions());
+	let different = reference
+		.pixels()
+		.zip(image.pixels())
+		.filter(|(a, b)| {
+			a.0.iter()
+				.zip(b.0.iter())
+				.any(|(&a, &b)| a.abs_diff(b) > tolerance)
+		})
+		.count();
+	different as f32 / (reference.width() * reference.height()) as f32
+}
+
+/// Fail if too many pixels are different and keep the rendered `image`.
+fn assert_similar(name: &str, image: &image::RgbaImage, fraction: f32) {
+	if fraction > PIXEL_TOLERANCE {
+		let actual = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+		image.save(&actual).unwrap();
+		panic!(
+			"{} differs in {:.1}% of the pixels, rendered image at {}",
+			name,
+			fraction * 100.0,
+			actual.display(),
+		);
+	}
+}
+
+/// Compare `image` with the reference image `name`.
+fn check(name: &str, image: &image::RgbaImage) {
+	if std::env::var_os("UPDATE_GOLDEN").is_some() {
+		image.save(reference_path(name)).unwrap();
+		return;
+	}
+	let fraction = different(&reference(name), image, CHANNEL_TOLERANCE);
+	assert_similar(name, image, fraction);
+}
+
+/// Compare `image` with the reference image `name` at half the resolution, where smoothed edges
+/// are averaged out.
+fn check_smoothed(name: &str, image: &image::RgbaImage) {
+	let half = |image: &image::RgbaImage| {
+		image::imageops::resize(
+			image,
+			SIZE / 2,
+			SIZE / 2,
+			image::imageops::FilterType::Triangle,
+		)
+	};
+	let fraction = different(&half(&reference(name)), &half(image), 2 * CHANNEL_TOLERANCE);
+	assert_similar(name, image, fraction);
+}
+
+#[test]
+#[ignore = "needs a software adapter"]
+fn points_turbo() {
+	let _lock = ADAPTER.lock().unwrap_or_else(|err| err.into_inner());
+	let scene = Scene::new(1);
+	check("points_turbo", &scene.points(TURBO, false));
+}
+
+#[test]
+#[ignore = "needs a software adapter"]
+fn points_warm() {
+	let _lock = ADAPTER.lock().unwrap_or_else(|err| err.into_inner());
+	let scene = Scene::new(1);
+	check("points_warm", &scene.points(WARM, false));
+}
+
+#[test]
+#[ignore = "needs a software adapter"]
+fn points_white() {
+	let _lock = ADAPTER.lock().unwrap_or_else(|err| err.into_inner());
+	let scene = Scene::new(1);
+	check("points_white", &scene.points(WHITE, false));
+}
+
+#[test]
+#[ignore = "needs a software adapter"]
+fn points_eye_dome() {
+	let _lock = ADAPTER.lock().unwrap_or_else(|err| err.into_inner());
+	let scene = Scene::new(1);
+	check("points_eye_dome", &scene.points(TURBO, true));
+}
+
+/// Multisampling only smooths the edges of the points, compared with the single sample reference.
+/// The multisampled eye dome is only validated, its output depends on the samples.
+#[test]
+#[ignore = "needs an adapter with multisampling"]
+fn points_multisampled() {
+	let _lock = ADAPTER.lock().unwrap_or_else(|err| err.into_inner());
+	let scene = Scene::new(4);
+	render::EyeDome::new(
+		&scene.state,
+		scene.offscreen.config(),
+		scene.offscreen.depth_texture(),
+		0.7,
+		1.0,
+		4,
+	);
+	check_smoothed("points_turbo", &scene.points(TURBO, false));
+}
+
+#[test]
+#[ignore = "needs a software adapter"]
+fn lines() {
+	let _lock = ADAPTER.lock().unwrap_or_else(|err| err.into_inner());
+	let scene = Scene::new(1);
+	let state = &scene.state;
+
+	// edges of the cube around the points
+	let corners = (0..8)
+		.map(|i| {
+			na::point![
+				if i & 1 == 0 { -1.0 } else { 1.0 },
+				if i & 2 == 0 { -1.0 } else { 1.0 },
+				if i & 4 == 0 { -1.0 } else { 1.0 },
+			]
+		})
+		.collect::<Vec<_>>();
+	let mut indices = Vec::new();
+	for a in 0..8u32 {
+		for bit in [1, 2, 4] {
+			if a & bit == 0 {
+				indices.extend([a, a | bit]);
+			}
+		}
+	}
+	let corners = render::PointCloud::new(state, &corners);
+	let lines = render::Lines::new(state, &indices);
+	let lines_state = render::LinesState::new(state);
+
+	let image = scene.offscreen.render(state, |context| {
+		let mut render_pass = context.render_pass(BACKGROUND);
+		let lines_pass = lines_state.render(&mut render_pass, &scene.camera);
+		lines.render(&corners, lines_pass);
+	});
+	check("lines", &image);
+}
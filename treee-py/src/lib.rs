@@ -0,0 +1,37 @@
+// the `pyfunction` macro converts the errors of `PyResult` functions
+#![allow(clippy::useless_conversion)]
+
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+use std::path::PathBuf;
+
+fn runtime_error(err: impl std::fmt::Display) -> PyErr {
+	PyRuntimeError::new_err(err.to_string())
+}
+
+/// Calculate the traits for all segments in a saved session.
+///
+/// Returns a list with a dictionary for every segment, sorted by id.
+#[pyfunction]
+fn segment_traits(py: Python<'_>, session: PathBuf) -> PyResult<PyObject> {
+	let traits = py
+		.allow_threads(|| treee::session_traits(&session))
+		.map_err(runtime_error)?;
+	let json = serde_json::to_string(&traits).map_err(runtime_error)?;
+	let traits = py.import_bound("json")?.call_method1("loads", (json,))?;
+	Ok(traits.unbind())
+}
+
+/// Calculate the traits for all segments in a saved session and write them as CSV or JSON.
+#[pyfunction]
+fn extract_traits(py: Python<'_>, session: PathBuf, output: PathBuf) -> PyResult<()> {
+	py.allow_threads(|| treee::extract_traits(&session, &output))
+		.map_err(runtime_error)
+}
+
+/// Python bindings for the trait calculation without a window.
+#[pymodule]
+fn treee_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+	module.add_function(wrap_pyfunction!(segment_traits, module)?)?;
+	module.add_function(wrap_pyfunction!(extract_traits, module)?)?;
+	Ok(())
+}
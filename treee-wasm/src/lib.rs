@@ -6,7 +6,7 @@ pub use wasm_bindgen_rayon::init_thread_pool;
 pub async fn treee() {
 	std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 	console_log::init_with_level(log::Level::Warn).expect("Couldn't initialize logger");
-	treee::try_main(|err| {
+	treee::try_main(treee::Settings::default(), |err| {
 		let message = format!("{}", err);
 		web_sys::window()
 			.unwrap()
@@ -1,26 +1,112 @@
 use core::f32;
 use std::{
 	collections::HashMap,
+	ops::Not,
 	sync::{
 		atomic::{AtomicUsize, Ordering},
-		Arc,
+		Arc, Mutex,
 	},
 };
 
 use crossbeam::channel::TrySendError;
 use nalgebra as na;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
-use crate::{program::Event, segmenting::Tree};
+use crate::{
+	id,
+	interactive::{
+		hits::HitCache,
+		trunk_axis::{StemForm, TrunkAxisSave},
+	},
+	program::Event,
+	progress::Progress,
+	segmenting::{SegmentationSettings, Tree},
+};
 
 /// Slice width for calculations.
 const SLICE_WIDTH: f32 = 0.1;
 
+/// Checked points per neighbor for the approximate neighbors search.
+const APPROXIMATE_CHECKS_PER_NEIGHBOR: usize = 4;
+
+/// Thread pool with a limited number of threads, shared by restarts and later calculations.
+static LIMITED_POOL: Mutex<Option<Arc<rayon::ThreadPool>>> = Mutex::new(None);
+
+/// Get the pool with `threads` threads, only created again if the number of threads changed.
+fn limited_pool(threads: usize) -> Result<Arc<rayon::ThreadPool>, rayon::ThreadPoolBuildError> {
+	let mut pool = LIMITED_POOL.lock().unwrap();
+	if let Some(pool) = pool.as_ref() {
+		if pool.current_num_threads() == threads {
+			return Ok(pool.clone());
+		}
+	}
+	let new = Arc::new(
+		rayon::ThreadPoolBuilder::new()
+			.num_threads(threads)
+			.thread_name(|idx| format!("calculation-{}", idx))
+			.build()?,
+	);
+	*pool = Some(new.clone());
+	Ok(new)
+}
+
+/// Adjustable parameters for the calculations of a single segment.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CalculationSettings {
+	/// Height above the ground to measure the trunk diameter.
+	pub trunk_diameter_height: f32,
+	/// Slice width for the expansion.
+	pub slice_width: f32,
+	/// Neighbors used to calculate the curvature.
+	pub neighbors_count: usize,
+	/// Search the neighbors approximately, faster but may miss some of the nearest points.
+	pub approximate_neighbors: bool,
+	/// Method to measure the trunk diameter.
+	pub trunk_diameter_method: TrunkDiameterMethod,
+}
+
+impl Default for CalculationSettings {
+	fn default() -> Self {
+		Self {
+			trunk_diameter_height: 1.3,
+			slice_width: SLICE_WIDTH,
+			neighbors_count: 31,
+			approximate_neighbors: false,
+			trunk_diameter_method: TrunkDiameterMethod::CircleFit,
+		}
+	}
+}
+
+/// Method to measure the trunk diameter.
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	PartialEq,
+	Eq,
+	serde::Serialize,
+	serde::Deserialize
+)]
+pub enum TrunkDiameterMethod {
+	/// Diameter of a circle with the same area as the convex hull.
+	ConvexArea,
+	/// Least squares circle through the trunk points.
+	CircleFit,
+}
+
 /// State for the Calculations phase.
 pub struct Calculations {
 	pub shared: Arc<Shared>,
 	pub total: usize,
 	pub world_offset: na::Point3<f64>,
+	/// Parameters of the segmentation which created the segments.
+	pub segmentation: SegmentationSettings,
+	/// Rotation applied to level the ground.
+	pub leveling: Option<na::UnitQuaternion<f32>>,
+	pub restart: crossbeam::channel::Sender<usize>,
+	/// Maximum segments calculated at the same time.
+	pub threads: usize,
+	progress: Progress,
 }
 
 /// Shared state for the workers.
@@ -40,9 +126,14 @@ pub struct SegmentData {
 	pub min: na::Point3<f32>,
 	pub max: na::Point3<f32>,
 	pub coords: Option<(f64, f64)>,
+	/// Acceleration for ray hits, calculated when needed.
+	#[serde(skip)]
+	pub hits: HitCache,
 }
 
 /// Classification for a point.
+///
+/// Saves contain the index of the variant, new variants must be added at the end.
 #[derive(
 	Debug,
 	Clone,
@@ -56,6 +147,8 @@ pub enum Classification {
 	Ground,
 	Trunk,
 	Crown,
+	/// Outlier kept in the segment, excluded from the traits.
+	Noise,
 }
 
 /// Calculated information to save for one segment.
@@ -70,6 +163,11 @@ pub struct SegmentSave {
 	pub latitude: Option<f64>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub longitude: Option<f64>,
+	#[serde(flatten)]
+	pub stem: StemForm,
+	/// Trunk axis from the segment view.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub trunk_axis: Option<TrunkAxisSave>,
 }
 
 /// Limit event queue size.
@@ -77,9 +175,12 @@ const SENDER_CAPACITY: usize = 128;
 
 impl Calculations {
 	/// Create phase from segments.
+	/// At most `threads` segments are calculated and held in memory at the same time.
 	pub fn new(
 		segments: HashMap<u32, Vec<na::Point3<f32>>>,
 		world_offset: na::Point3<f64>,
+		segmentation: SegmentationSettings,
+		threads: usize,
 	) -> (Self, crossbeam::channel::Receiver<Event>) {
 		let shared = Shared {
 			segments: std::sync::Mutex::new(HashMap::new()),
@@ -89,47 +190,128 @@ impl Calculations {
 		let total = segments.len();
 
 		let (sender, reciever) = crossbeam::channel::bounded(SENDER_CAPACITY);
-		_ = sender.send(Event::ClearPointClouds);
+		let (restart_sender, restart_reciever) = crossbeam::channel::unbounded();
+		let threads = threads.clamp(1, rayon::current_num_threads());
+		restart_sender.send(threads).unwrap();
 
 		{
 			let shared = shared.clone();
 			rayon::spawn(move || {
-				segments.into_par_iter().for_each(|(idx, points)| {
-					let seg = SegmentData::new(points);
-
-					while sender.len() > SENDER_CAPACITY - 16 {
-						std::hint::spin_loop();
-					}
-					let mut event = Event::PointCloud {
-						idx: Some(idx),
-						data: seg.points.clone(),
-						segment: vec![idx; seg.points.len()],
-					};
-					loop {
-						match sender.try_send(event) {
-							Ok(_) => break,
-							Err(TrySendError::Disconnected(_)) => break,
-							Err(TrySendError::Full(v)) => event = v,
+				while let Ok(threads) = restart_reciever.recv() {
+					let calculate = || calculations(&segments, &shared, &sender, &restart_reciever);
+					let finished = if threads < rayon::current_num_threads() {
+						match limited_pool(threads) {
+							Ok(pool) => pool.install(calculate),
+							Err(err) => {
+								log::warn!("Failed to limit threads: {}", err);
+								calculate()
+							},
 						}
+					} else {
+						calculate()
+					};
+					if finished {
+						// release shared state before the phase is finished
+						drop(shared);
+						_ = sender.send(Event::Done);
+						return;
 					}
-
-					shared.segments.lock().unwrap().insert(idx, seg);
-					shared.progress.fetch_add(1, Ordering::Relaxed);
-				});
-				_ = sender.send(Event::Done);
+				}
 			});
 		}
 
-		(Self { shared, total, world_offset }, reciever)
+		(
+			Self {
+				shared,
+				total,
+				world_offset,
+				segmentation,
+				leveling: None,
+				restart: restart_sender,
+				threads,
+				progress: Progress::new("segments"),
+			},
+			reciever,
+		)
 	}
 
 	/// Draw UI.
 	pub fn ui(&mut self, ui: &mut egui::Ui) {
-		let progress = self.shared.progress.load(Ordering::Relaxed) as f32 / self.total as f32;
-		ui.add(egui::ProgressBar::new(progress).rounding(egui::Rounding::ZERO));
+		if ui
+			.add_sized([ui.available_width(), 0.0], egui::Button::new("Restart"))
+			.clicked()
+		{
+			_ = self.restart.send(self.threads);
+		}
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			ui.label("Threads");
+			if ui
+				.add(egui::Slider::new(
+					&mut self.threads,
+					1..=rayon::current_num_threads(),
+				))
+				.changed()
+			{
+				_ = self.restart.send(self.threads);
+			}
+			ui.end_row();
+		});
+		ui.separator();
+		let progress = self.shared.progress.load(Ordering::Relaxed);
+		self.progress.ui(ui, progress, self.total);
 	}
 }
 
+/// Calculate the data for all segments.
+/// Returns `false` if canceled by a restart or if the phase was closed.
+fn calculations(
+	segments: &HashMap<u32, Vec<na::Point3<f32>>>,
+	shared: &Shared,
+	sender: &crossbeam::channel::Sender<Event>,
+	restart: &crossbeam::channel::Receiver<usize>,
+) -> bool {
+	shared.segments.lock().unwrap().clear();
+	shared.progress.store(0, Ordering::Relaxed);
+	_ = sender.send(Event::ClearPointClouds);
+
+	let result = segments.par_iter().try_for_each(|(&idx, points)| {
+		// recieved restart, break
+		if restart.is_empty().not() {
+			return Err(());
+		}
+
+		let seg = SegmentData::new(points.clone());
+
+		while sender.len() > SENDER_CAPACITY - 16 {
+			std::hint::spin_loop();
+		}
+		let mut event = Event::PointCloud {
+			idx: Some(idx),
+			data: seg.points.clone(),
+			segment: vec![idx; seg.points.len()],
+		};
+		loop {
+			match sender.try_send(event) {
+				Ok(_) => break,
+				// phase closed, break
+				Err(TrySendError::Disconnected(_)) => return Err(()),
+				Err(TrySendError::Full(v)) => event = v,
+			}
+		}
+
+		shared.segments.lock().unwrap().insert(idx, seg);
+		shared.progress.fetch_add(1, Ordering::Relaxed);
+		Ok(())
+	});
+
+	if result.is_err() {
+		// discard partial results
+		shared.segments.lock().unwrap().clear();
+		return false;
+	}
+	true
+}
+
 impl SegmentData {
 	/// Create segment from points.
 	pub fn new(points: Vec<na::Point3<f32>>) -> Self {
@@ -169,6 +351,7 @@ impl SegmentData {
 			min,
 			max,
 			coords: None,
+			hits: HitCache::default(),
 		}
 	}
 
@@ -215,7 +398,7 @@ impl SegmentInformation {
 			}
 		}
 
-		let areas = get_size_areas(min, height, data, |_| true);
+		let areas = get_size_areas(min, height, data, SLICE_WIDTH, |_| true);
 
 		let min_area = areas
 			.iter()
@@ -273,35 +456,56 @@ impl SegmentInformation {
 		classifications: &[Classification],
 		min: f32,
 		max: f32,
-		calc_curve: bool,
+		neighbors: Option<&NeighborsTree>,
+		settings: &CalculationSettings,
 	) -> CalculationProperties {
-		let neighbors_count = 31;
-		let neighbors_max_distance = f32::MAX;
+		let slice_width = settings.slice_width;
 
 		let height = max - min;
 
-		let slices = ((height / SLICE_WIDTH) as usize) + 1;
+		let slices = ((height / slice_width) as usize) + 1;
 		let mut sets = vec![<Option<Tree>>::None; slices];
 		for pos in data
 			.iter()
 			.zip(classifications)
 			.filter_map(|(&p, &c)| (c == Classification::Crown).then_some(p))
 		{
-			let idx = ((pos.y - min) / SLICE_WIDTH) as usize;
+			let idx = ((pos.y - min) / slice_width) as usize;
 			match &mut sets[idx] {
 				Some(tree) => tree.insert(na::vector![pos.x, pos.z].into()),
 				x @ None => *x = Some(Tree::new(na::vector![pos.x, pos.z].into())),
 			}
 		}
 
-		let areas = get_size_areas(min, height, data, |idx| {
+		let areas = get_size_areas(min, height, data, slice_width, |idx| {
 			classifications[idx] == Classification::Crown
 		});
 
+		let trunk_diameter = match settings.trunk_diameter_method {
+			TrunkDiameterMethod::ConvexArea => {
+				let areas = get_size_areas(min, height, data, slice_width, |idx| {
+					classifications[idx] == Classification::Trunk
+				});
+				let slice = (self.ground_sep + settings.trunk_diameter_height - min) / slice_width;
+				approximate_diameter(areas.get(slice as usize).copied().unwrap_or(0.0))
+			},
+			TrunkDiameterMethod::CircleFit => {
+				let center = self.ground_sep + settings.trunk_diameter_height;
+				let range = (center - slice_width / 2.0)..(center + slice_width / 2.0);
+				let points = data
+					.iter()
+					.zip(classifications)
+					.filter(|&(p, &c)| c == Classification::Trunk && range.contains(&p.y))
+					.map(|(p, _)| na::point![p.x, p.z])
+					.collect::<Vec<_>>();
+				fit_circle(&points).map_or(0.0, |(_, radius)| 2.0 * radius)
+			},
+		};
+
 		let crown_area = areas
 			.iter()
 			.copied()
-			.skip(((self.crown_sep - min) / SLICE_WIDTH) as usize)
+			.skip(((self.crown_sep - min) / slice_width) as usize)
 			.max_by(|a, b| a.total_cmp(b))
 			.unwrap_or(0.0);
 
@@ -315,7 +519,7 @@ impl SegmentInformation {
 			.iter()
 			.copied()
 			.map(|p| {
-				let idx = ((p.y - min) / SLICE_WIDTH) as usize;
+				let idx = ((p.y - min) / slice_width) as usize;
 				slices[idx]
 			})
 			.collect();
@@ -325,55 +529,108 @@ impl SegmentInformation {
 			.map(|p| (p.y - min) / height)
 			.collect::<Vec<_>>();
 
-		let curve = if calc_curve {
-			let neighbors_tree = NeighborsTree::new(data);
+		let curve = match neighbors {
+			Some(neighbors) => neighbor_covariances(data, neighbors, settings, |variance| {
+				let eigen_values = fast_eigenvalues(variance);
+				(3.0 * eigen_values.z) / (eigen_values.x + eigen_values.y + eigen_values.z)
+			}),
+			None => vec![0.0; data.len()],
+		};
 
-			let mut neighbors_location = bytemuck::zeroed_vec(neighbors_count);
-			data.iter()
-				.enumerate()
-				.map(|(i, _)| {
-					let neighbors = neighbors_tree.get(
-						i,
-						data,
-						&mut neighbors_location,
-						neighbors_max_distance,
-					);
-
-					let mean = {
-						let mut mean = na::Point3::new(0.0, 0.0, 0.0);
-						for entry in neighbors {
-							mean += data[entry.index].coords;
-						}
-						mean / neighbors.len() as f32
-					};
-					let variance = {
-						let mut variance = na::Matrix3::default();
-						for entry in neighbors {
-							let difference = data[entry.index] - mean;
-							for x in 0..3 {
-								for y in 0..3 {
-									variance[(x, y)] += difference[x] * difference[y];
-								}
-							}
-						}
-						for x in 0..3 {
-							for y in 0..3 {
-								variance[(x, y)] /= neighbors.len() as f32;
-							}
+		let stem = StemForm::new(data, classifications, slice_width);
+
+		CalculationProperties {
+			expansion,
+			curve,
+			height,
+			trunk_diameter,
+			stem,
+		}
+	}
+}
+
+/// Apply `f` to the covariance matrix of the neighbors for every point.
+///
+/// `neighbors_tree` must contain the points of `data` with their index.
+fn neighbor_covariances<T>(
+	data: &[na::Point3<f32>],
+	neighbors_tree: &NeighborsTree,
+	settings: &CalculationSettings,
+	mut f: impl FnMut(na::Matrix3<f32>) -> T,
+) -> Vec<T> {
+	let neighbors_count = settings.neighbors_count;
+	let neighbors_max_distance = f32::MAX;
+
+	let mut neighbors_location = bytemuck::zeroed_vec(neighbors_count);
+	data.iter()
+		.enumerate()
+		.map(|(i, _)| {
+			let neighbors = if settings.approximate_neighbors {
+				neighbors_tree.get_approximate(
+					i,
+					data,
+					&mut neighbors_location,
+					neighbors_max_distance,
+					neighbors_count * APPROXIMATE_CHECKS_PER_NEIGHBOR,
+				)
+			} else {
+				neighbors_tree.get(i, data, &mut neighbors_location, neighbors_max_distance)
+			};
+
+			let mean = {
+				let mut mean = na::Point3::new(0.0, 0.0, 0.0);
+				for entry in neighbors {
+					mean += data[entry.index].coords;
+				}
+				mean / neighbors.len() as f32
+			};
+			let variance = {
+				let mut variance = na::Matrix3::default();
+				for entry in neighbors {
+					let difference = data[entry.index] - mean;
+					for x in 0..3 {
+						for y in 0..3 {
+							variance[(x, y)] += difference[x] * difference[y];
 						}
-						variance
-					};
+					}
+				}
+				for x in 0..3 {
+					for y in 0..3 {
+						variance[(x, y)] /= neighbors.len() as f32;
+					}
+				}
+				variance
+			};
 
-					let eigen_values = fast_eigenvalues(variance);
-					(3.0 * eigen_values.z) / (eigen_values.x + eigen_values.y + eigen_values.z)
-				})
-				.collect()
-		} else {
-			vec![0.0; data.len()]
-		};
+			f(variance)
+		})
+		.collect()
+}
 
-		CalculationProperties { expansion, curve, height }
+/// Normal for every point from the neighbors, oriented away from the vertical center line.
+pub fn point_normals(
+	data: &[na::Point3<f32>],
+	settings: &CalculationSettings,
+) -> Vec<na::Vector3<f32>> {
+	if data.is_empty() {
+		return Vec::new();
 	}
+	let center = data.iter().map(|p| p.coords).sum::<na::Vector3<f32>>() / data.len() as f32;
+	let normals = neighbor_covariances(data, &NeighborsTree::new(data), settings, |variance| {
+		eigenvector(variance, fast_eigenvalues(variance).z)
+	});
+	normals
+		.into_iter()
+		.zip(data)
+		.map(|(normal, p)| {
+			let outwards = na::vector![p.x - center.x, 0.0, p.z - center.z];
+			if normal.dot(&outwards) < 0.0 {
+				-normal
+			} else {
+				normal
+			}
+		})
+		.collect()
 }
 
 /// Adapter to use generic KD-Tree.
@@ -393,6 +650,14 @@ pub struct NeighborsTree {
 	tree: k_nearest::KDTree<3, f32, na::Point3<f32>, Adapter, k_nearest::EuclideanDistanceSquared>,
 }
 
+impl std::fmt::Debug for NeighborsTree {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("NeighborsTree")
+			.field("len", &self.tree.len())
+			.finish()
+	}
+}
+
 impl NeighborsTree {
 	pub fn new(points: &[na::Point3<f32>]) -> Self {
 		let tree = <k_nearest::KDTree<
@@ -406,6 +671,22 @@ impl NeighborsTree {
 		Self { tree }
 	}
 
+	/// Replace the points from `old` with the points from `new`, only changed indices are updated.
+	pub fn update(&mut self, old: &[na::Point3<f32>], new: &[na::Point3<f32>]) {
+		for (index, point) in new.iter().enumerate() {
+			if old.get(index) == Some(point) {
+				continue;
+			}
+			if index < old.len() {
+				self.tree.remove(index);
+			}
+			self.tree.insert(point, index);
+		}
+		for index in new.len()..old.len() {
+			self.tree.remove(index);
+		}
+	}
+
 	pub fn get<'a>(
 		&self,
 		index: usize,
@@ -416,6 +697,175 @@ impl NeighborsTree {
 		let l = self.tree.k_nearest(&data[index], location, max_distance);
 		&location[0..l]
 	}
+
+	/// Nearest point to `point` within the squared `max_distance`.
+	pub fn nearest(
+		&self,
+		point: &na::Point3<f32>,
+		max_distance: f32,
+	) -> Option<k_nearest::Entry<f32>> {
+		let mut location = [bytemuck::Zeroable::zeroed()];
+		let l = self.tree.k_nearest(point, &mut location, max_distance);
+		(l > 0).then_some(location[0])
+	}
+
+	/// Same as `get`, but only checks up to `max_checks` points.
+	pub fn get_approximate<'a>(
+		&self,
+		index: usize,
+		data: &[na::Point3<f32>],
+		location: &'a mut [k_nearest::Entry<f32>],
+		max_distance: f32,
+		max_checks: usize,
+	) -> &'a [k_nearest::Entry<f32>] {
+		let l = self
+			.tree
+			.k_nearest_approximate(&data[index], location, max_distance, max_checks);
+		&location[0..l]
+	}
+}
+
+/// Parameters for the statistical outlier removal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlierSettings {
+	/// Neighbors used for the mean distance.
+	pub neighbors_count: usize,
+	/// Allowed standard deviations above the mean distance.
+	pub std_factor: f32,
+}
+
+impl Default for OutlierSettings {
+	fn default() -> Self {
+		Self { neighbors_count: 8, std_factor: 2.0 }
+	}
+}
+
+impl OutlierSettings {
+	/// Draw UI, returns `true` if the settings changed.
+	pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+		let mut changed = false;
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			ui.label("Neighbors");
+			changed |= ui
+				.add(egui::Slider::new(&mut self.neighbors_count, 2..=64))
+				.changed();
+			ui.end_row();
+
+			ui.label("Deviation");
+			changed |= ui
+				.add(egui::Slider::new(&mut self.std_factor, 0.5..=5.0))
+				.changed();
+			ui.end_row();
+		});
+		changed
+	}
+
+	/// Mark statistical outliers.
+	///
+	/// A point is an outlier if the mean distance to its neighbors is larger than
+	/// the mean over all points plus `std_factor` times the standard deviation.
+	pub fn outliers(&self, points: &[na::Point3<f32>]) -> Vec<bool> {
+		if points.len() <= self.neighbors_count {
+			return vec![false; points.len()];
+		}
+		let tree = NeighborsTree::new(points);
+		let distances = (0..points.len())
+			.into_par_iter()
+			.map_init(
+				// the point itself is the first neighbor
+				|| bytemuck::zeroed_vec(self.neighbors_count + 1),
+				|location, idx| {
+					let neighbors = tree.get(idx, points, location, f32::MAX);
+					neighbors
+						.iter()
+						.skip(1)
+						.map(|entry| entry.distance.sqrt())
+						.sum::<f32>() / (neighbors.len() - 1).max(1) as f32
+				},
+			)
+			.collect::<Vec<_>>();
+
+		let mean = distances.iter().sum::<f32>() / distances.len() as f32;
+		let variance =
+			distances.iter().map(|d| (d - mean).powi(2)).sum::<f32>() / distances.len() as f32;
+		let max = mean + self.std_factor * variance.sqrt();
+
+		distances.into_iter().map(|d| d > max).collect()
+	}
+}
+
+/// Horizontal size of the cells for the lowest points.
+const GROUND_CELL_SIZE: f32 = 1.0;
+
+/// Maximum distance to the plane for ground points.
+const GROUND_DISTANCE: f32 = 0.1;
+
+/// Random planes tested for the ground.
+const GROUND_ITERATIONS: usize = 500;
+
+/// Rotation to level the dominant ground plane, `None` if no plane is found.
+///
+/// The plane is found with RANSAC over the lowest point in every cell and refined with the
+/// covariance of the ground points.
+pub fn ground_leveling(
+	points: impl Iterator<Item = na::Point3<f32>>,
+	rng: &mut impl rand::Rng,
+) -> Option<na::UnitQuaternion<f32>> {
+	let mut lowest = HashMap::<(isize, isize), na::Point3<f32>>::new();
+	for p in points {
+		let cell = (
+			(p.x / GROUND_CELL_SIZE).floor() as isize,
+			(p.z / GROUND_CELL_SIZE).floor() as isize,
+		);
+		let entry = lowest.entry(cell).or_insert(p);
+		if p.y < entry.y {
+			*entry = p;
+		}
+	}
+	let lowest = lowest.into_values().collect::<Vec<_>>();
+	if lowest.len() < 3 {
+		return None;
+	}
+
+	let mut best = (0, na::Vector3::y(), na::Point3::origin());
+	for _ in 0..GROUND_ITERATIONS {
+		let [a, b, c] = [(); 3].map(|_| lowest[rng.gen_range(0..lowest.len())]);
+		let Some(normal) = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON) else {
+			continue;
+		};
+		// only planes which could be the ground
+		if normal.y.abs() < 0.5 {
+			continue;
+		}
+		let count = lowest
+			.iter()
+			.filter(|p| (*p - a).dot(&normal).abs() < GROUND_DISTANCE)
+			.count();
+		if count > best.0 {
+			best = (count, normal, a);
+		}
+	}
+	let (count, normal, origin) = best;
+	if count < 3 {
+		return None;
+	}
+
+	let ground = lowest
+		.iter()
+		.filter(|p| (*p - origin).dot(&normal).abs() < GROUND_DISTANCE)
+		.collect::<Vec<_>>();
+	let center = ground.iter().map(|p| p.coords).sum::<na::Vector3<f32>>() / ground.len() as f32;
+	let mut covariance = na::Matrix3::zeros();
+	for p in ground {
+		let d = p.coords - center;
+		covariance += d * d.transpose();
+	}
+	covariance /= count as f32;
+	let mut normal = eigenvector(covariance, fast_eigenvalues(covariance).z);
+	if normal.y < 0.0 {
+		normal = -normal;
+	}
+	na::UnitQuaternion::rotation_between(&normal, &na::Vector3::y())
 }
 
 /// Calculated properties for one segment.
@@ -424,6 +874,8 @@ pub struct CalculationProperties {
 	pub expansion: Vec<f32>,
 	pub curve: Vec<f32>,
 	pub height: Vec<f32>,
+	pub trunk_diameter: f32,
+	pub stem: StemForm,
 }
 
 /// Seperate points into slices and calculate convex areas.
@@ -431,16 +883,17 @@ pub fn get_size_areas(
 	min: f32,
 	height: f32,
 	data: &[na::Point3<f32>],
+	slice_width: f32,
 	valid: impl Fn(usize) -> bool,
 ) -> Vec<f32> {
-	let slices = ((height / SLICE_WIDTH) as usize) + 1;
+	let slices = ((height / slice_width) as usize) + 1;
 	let mut sets = vec![<Option<Tree>>::None; slices];
 	for pos in data
 		.iter()
 		.enumerate()
 		.filter_map(|(idx, &p)| valid(idx).then_some(p))
 	{
-		let idx = ((pos.y - min) / SLICE_WIDTH) as usize;
+		let idx = ((pos.y - min) / slice_width) as usize;
 		match &mut sets[idx] {
 			Some(tree) => tree.insert(na::vector![pos.x, pos.z].into()),
 			x @ None => *x = Some(Tree::new(na::vector![pos.x, pos.z].into())),
@@ -451,6 +904,33 @@ pub fn get_size_areas(
 		.collect::<Vec<_>>()
 }
 
+/// Least squares circle fit, returns center and radius.
+/// Returns `None` for less than three points or if the points are collinear.
+///
+/// Source: <https://doi.org/10.1109/TIM.1976.6312298>
+pub fn fit_circle(points: &[na::Point2<f32>]) -> Option<(na::Point2<f32>, f32)> {
+	if points.len() < 3 {
+		return None;
+	}
+	// relative to the mean for numerical stability
+	let mean = points.iter().map(|p| p.coords).sum::<na::Vector2<f32>>() / points.len() as f32;
+
+	// solve x^2 + y^2 + d * x + e * y + f = 0
+	let mut lhs = na::Matrix3::<f32>::zeros();
+	let mut rhs = na::Vector3::<f32>::zeros();
+	for p in points {
+		let p = p.coords - mean;
+		let row = na::vector![p.x, p.y, 1.0];
+		lhs += row * row.transpose();
+		rhs -= row * p.norm_squared();
+	}
+	let solution = lhs.try_inverse()? * rhs;
+
+	let center = na::point![-solution.x / 2.0, -solution.y / 2.0];
+	let radius = (center.coords.norm_squared() - solution.z).sqrt();
+	radius.is_finite().then_some((center + mean, radius))
+}
+
 /// Convert value in range [0.0, 1.0] to [0, u32::MAX]
 pub fn map_to_u32(value: f32) -> u32 {
 	(value * u32::MAX as f32) as u32
@@ -496,3 +976,92 @@ pub fn fast_eigenvalues(mat: na::Matrix3<f32>) -> na::Point3<f32> {
 	let eig_2 = 3.0 * q - eig_1 - eig_3;
 	[eig_1, eig_2, eig_3].into()
 }
+
+/// Normalized eigenvector for an eigenvalue of a real and symmetric 3x3 matrix.
+///
+/// The eigenvector is orthogonal to the rows of `mat - value * I`, the largest cross product
+/// of two rows is the most stable. Returns up for degenerate matrices.
+pub fn eigenvector(mat: na::Matrix3<f32>, value: f32) -> na::Vector3<f32> {
+	let shifted = mat - na::Matrix3::identity() * value;
+	let rows = [0, 1, 2].map(|i| shifted.row(i).transpose());
+	[
+		rows[0].cross(&rows[1]),
+		rows[0].cross(&rows[2]),
+		rows[1].cross(&rows[2]),
+	]
+	.into_iter()
+	.max_by(|a, b| a.norm_squared().total_cmp(&b.norm_squared()))
+	.and_then(|v| v.try_normalize(f32::EPSILON))
+	.unwrap_or(na::vector![0.0, 1.0, 0.0])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fit_circle_exact() {
+		let (center, radius) = (na::point![3.0, -2.0], 0.4);
+		let points = (0..16)
+			.map(|i| {
+				let angle = i as f32 / 16.0 * std::f32::consts::TAU;
+				center + na::vector![angle.cos(), angle.sin()] * radius
+			})
+			.collect::<Vec<_>>();
+		let (fitted, fitted_radius) = fit_circle(&points).unwrap();
+		assert!((fitted - center).norm() < 1e-4);
+		assert!((fitted_radius - radius).abs() < 1e-4);
+	}
+
+	#[test]
+	fn fit_circle_partial_noisy() {
+		// half of a trunk is scanned, far away from the origin
+		let (center, radius) = (na::point![512.0, 1024.0], 0.25);
+		let points = (0..50)
+			.map(|i| {
+				let angle = i as f32 / 50.0 * std::f32::consts::PI;
+				let noise = if i % 2 == 0 { 0.005 } else { -0.005 };
+				center + na::vector![angle.cos(), angle.sin()] * (radius + noise)
+			})
+			.collect::<Vec<_>>();
+		let (fitted, fitted_radius) = fit_circle(&points).unwrap();
+		assert!((fitted - center).norm() < 0.01);
+		assert!((fitted_radius - radius).abs() < 0.01);
+	}
+
+	#[test]
+	fn fit_circle_degenerate() {
+		assert!(fit_circle(&[na::point![0.0, 0.0], na::point![1.0, 1.0]]).is_none());
+		let line = (0..10)
+			.map(|i| na::point![i as f32, 2.0 * i as f32])
+			.collect::<Vec<_>>();
+		// collinear points are an infinite circle
+		if let Some((_, radius)) = fit_circle(&line) {
+			assert!(radius > 1e3);
+		}
+	}
+
+	#[test]
+	fn neighbors_tree_update() {
+		let grid = |offset: f32| {
+			(0..400)
+				.map(|i| na::point![(i % 20) as f32, (i / 20) as f32 + offset, 0.0])
+				.collect::<Vec<_>>()
+		};
+		let old = grid(0.0);
+		let mut new = grid(0.0);
+		new.truncate(350);
+		new[10] = na::point![100.0, 0.0, 0.0];
+		new.push(na::point![-5.0, -5.0, 0.0]);
+
+		let mut tree = NeighborsTree::new(&old);
+		tree.update(&old, &new);
+		let fresh = NeighborsTree::new(&new);
+		for point in new.iter().chain(&grid(0.5)) {
+			let updated = tree.nearest(point, f32::MAX).unwrap();
+			let expected = fresh.nearest(point, f32::MAX).unwrap();
+			assert_eq!(updated.distance, expected.distance);
+			assert!(updated.index < new.len());
+		}
+	}
+}
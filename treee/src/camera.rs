@@ -1,4 +1,5 @@
 use nalgebra as na;
+use std::ops::Not;
 
 const BASE_ROTATE_SPEED: f32 = 0.002;
 const VERTICAL_SPEED: f32 = 0.02;
@@ -6,12 +7,35 @@ const VERTICAL_SPEED: f32 = 0.02;
 /// 45 degrees
 const FIELD_OF_VIEW: f32 = 45.0 * std::f32::consts::TAU / 360.0;
 
+/// Default duration for camera transitions in seconds.
+pub const DEFAULT_ANIMATION_DURATION: f32 = 0.5;
+
 /// Camera controller
 pub struct Camera {
 	gpu: render::Camera3DGPU,
 	cam: render::Camera3D,
 	transform: na::Affine3<f32>,
 	controller: Controller,
+	animation: Option<Animation>,
+	/// Animate transitions, jump instantly if `false`.
+	pub animated: bool,
+	/// Duration for transitions in seconds.
+	pub animation_duration: f32,
+}
+
+/// Camera position saved with a session.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct CameraSave {
+	transform: na::Matrix4<f32>,
+	controller: Controller,
+}
+
+/// Running transition between two camera positions.
+struct Animation {
+	movement: na::Vector3<f32>,
+	offset: f32,
+	/// Progress in [0.0, 1.0].
+	t: f32,
 }
 
 #[allow(dead_code)]
@@ -32,6 +56,9 @@ impl Camera {
 			transform,
 			cam: camera,
 			controller,
+			animation: None,
+			animated: true,
+			animation_duration: DEFAULT_ANIMATION_DURATION,
 		}
 	}
 
@@ -126,12 +153,93 @@ impl Camera {
 		(self.transform * na::vector![position.x, -position.y, -dist]).normalize()
 	}
 
+	/// Move the camera to show a sphere with `radius` around `center`.
+	/// The view direction is kept.
+	pub fn focus(&mut self, center: na::Point3<f32>, radius: f32, state: &render::State) {
+		let distance = radius / (FIELD_OF_VIEW / 2.0).sin();
+		let back = (self.transform * na::Vector3::z()).normalize();
+		let movement = center + back * distance - self.position();
+		let offset = match self.controller {
+			Controller::Orbital { offset } => distance - offset,
+			Controller::FirstPerson { .. } => 0.0,
+		};
+		self.animation = Some(Animation { movement, offset, t: 0.0 });
+		if self.animated.not() {
+			self.animate(f32::INFINITY, state);
+		}
+	}
+
+	/// Advance the running transition, returns `true` if the camera moved.
+	pub fn animate(&mut self, delta: f32, state: &render::State) -> bool {
+		let Some(animation) = &mut self.animation else {
+			return false;
+		};
+		let t = if self.animation_duration > 0.0 {
+			(animation.t + delta / self.animation_duration).min(1.0)
+		} else {
+			1.0
+		};
+		let step = ease(t) - ease(animation.t);
+		animation.t = t;
+
+		self.transform = na::Translation3 { vector: animation.movement * step } * self.transform;
+		if let Controller::Orbital { offset } = &mut self.controller {
+			*offset += animation.offset * step;
+		}
+		if t >= 1.0 {
+			self.animation = None;
+		}
+		self.update_gpu(state);
+		true
+	}
+
+	/// Projection from world positions to the screen, `None` if the position is behind the camera.
+	///
+	/// The transform is inverted once for all positions.
+	pub fn world_to_screen(
+		&self,
+		window_size: na::Point2<f32>,
+	) -> impl Fn(na::Point3<f32>) -> Option<na::Point2<f32>> {
+		let inverse = self.transform.inverse();
+		let dist = (window_size.y / 2.0) / (FIELD_OF_VIEW / 2.0).tan();
+		move |position| {
+			let local = inverse * position;
+			if local.z >= 0.0 {
+				return None;
+			}
+			let scale = dist / -local.z;
+			Some(na::point![
+				local.x * scale + window_size.x / 2.0,
+				-local.y * scale + window_size.y / 2.0
+			])
+		}
+	}
+
+	pub fn save(&self) -> CameraSave {
+		CameraSave {
+			transform: self.transform.to_homogeneous(),
+			controller: self.controller,
+		}
+	}
+
+	pub fn restore(&mut self, save: CameraSave, state: &render::State) {
+		self.transform = na::Affine3::from_matrix_unchecked(save.transform);
+		self.controller = save.controller;
+		self.animation = None;
+		self.update_gpu(state);
+	}
+
 	pub fn gpu(&self) -> &render::Camera3DGPU {
 		&self.gpu
 	}
 }
 
-#[derive(Clone, Copy, PartialEq)]
+/// Smooth start and end for `t` in [0.0, 1.0].
+fn ease(t: f32) -> f32 {
+	t * t * (3.0 - 2.0 * t)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
 pub enum Controller {
 	#[allow(dead_code)]
 	FirstPerson {
@@ -6,6 +6,9 @@ use wasm_bindgen::prelude::*;
 /// Empty phase
 pub struct Empty {
 	sender: crossbeam::channel::Sender<Event>,
+	/// Autosave from a session with edits which were not saved.
+	#[cfg(not(target_arch = "wasm32"))]
+	autosave: bool,
 	#[cfg(target_arch = "wasm32")]
 	progress: std::sync::Arc<std::sync::atomic::AtomicUsize>,
 }
@@ -17,6 +20,8 @@ impl Empty {
 		(
 			Self {
 				sender,
+				#[cfg(not(target_arch = "wasm32"))]
+				autosave: environment::autosave_source().is_some(),
 				#[cfg(target_arch = "wasm32")]
 				progress: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(1000)),
 			},
@@ -24,7 +29,19 @@ impl Empty {
 		)
 	}
 
-	pub fn ui(&self, ui: &mut egui::Ui) {
+	/// Open a source without the file dialog.
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn open(&self, source: environment::Source) {
+		_ = self.sender.send(Event::Load(source));
+	}
+
+	/// Load points from a network stream.
+	#[cfg(feature = "stream")]
+	pub fn stream(&self, address: String) {
+		_ = self.sender.send(Event::Stream(address));
+	}
+
+	pub fn ui(&mut self, ui: &mut egui::Ui) {
 		if ui
 			.add_sized([ui.available_width(), 0.0], egui::Button::new("Load"))
 			.clicked()
@@ -32,6 +49,41 @@ impl Empty {
 			environment::Source::start(&self.sender);
 		}
 
+		#[cfg(not(target_arch = "wasm32"))]
+		if self.autosave {
+			ui.separator();
+			ui.add_sized(
+				[ui.available_width(), 0.0],
+				egui::Label::new("The last session has unsaved edits."),
+			);
+			let (restore, discard) = ui.columns(2, |columns| {
+				(
+					columns[0]
+						.add_sized(
+							[columns[0].available_width(), 0.0],
+							egui::Button::new("Restore"),
+						)
+						.clicked(),
+					columns[1]
+						.add_sized(
+							[columns[1].available_width(), 0.0],
+							egui::Button::new("Discard"),
+						)
+						.on_hover_text("Delete the autosave")
+						.clicked(),
+				)
+			});
+			if restore {
+				if let Some(source) = environment::autosave_source() {
+					self.open(source);
+				}
+			}
+			if discard {
+				environment::remove_autosave();
+				self.autosave = false;
+			}
+		}
+
 		#[cfg(target_arch = "wasm32")]
 		{
 			ui.separator();
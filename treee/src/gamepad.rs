@@ -0,0 +1,69 @@
+use gilrs::{Axis, Button};
+use nalgebra as na;
+
+use crate::camera::Camera;
+
+/// Stick values below are ignored.
+const DEAD_ZONE: f32 = 0.15;
+/// Rotation speed for the right stick in pixels per second.
+const LOOK_SPEED: f32 = 800.0;
+/// Zoom speed for the shoulder buttons.
+const ZOOM_SPEED: f32 = 5.0;
+
+/// Camera control with a connected gamepad.
+pub struct Gamepad {
+	gilrs: gilrs::Gilrs,
+}
+
+impl Gamepad {
+	/// `None` if the gamepad backend is not available.
+	pub fn new() -> Option<Self> {
+		match gilrs::Gilrs::new() {
+			Ok(gilrs) => Some(Self { gilrs }),
+			Err(err) => {
+				log::warn!("Gamepad support not available: {}", err);
+				None
+			},
+		}
+	}
+
+	/// Move the camera, returns `true` if the camera changed.
+	pub fn update(&mut self, delta: f32, camera: &mut Camera, state: &render::State) -> bool {
+		while self.gilrs.next_event().is_some() {}
+
+		let mut changed = false;
+		for (_, gamepad) in self.gilrs.gamepads() {
+			let stick = |x, y| {
+				let value = na::vector![gamepad.value(x), -gamepad.value(y)];
+				if value.norm() < DEAD_ZONE {
+					na::Vector2::zeros()
+				} else {
+					value
+				}
+			};
+			let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+
+			let movement = stick(Axis::LeftStickX, Axis::LeftStickY);
+			if movement != na::Vector2::zeros() {
+				camera.movement(movement * delta, state);
+				changed = true;
+			}
+			let look = stick(Axis::RightStickX, Axis::RightStickY);
+			if look != na::Vector2::zeros() {
+				camera.rotate(look * delta * LOOK_SPEED, state);
+				changed = true;
+			}
+			let vertical = trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2);
+			if vertical != 0.0 {
+				camera.vertical_movement(vertical * delta * 10.0, state);
+				changed = true;
+			}
+			let zoom = trigger(Button::LeftTrigger) - trigger(Button::RightTrigger);
+			if zoom != 0.0 {
+				camera.scroll(zoom * delta * ZOOM_SPEED, state);
+				changed = true;
+			}
+		}
+		changed
+	}
+}
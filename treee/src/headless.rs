@@ -0,0 +1,50 @@
+use std::{path::Path, sync::atomic::AtomicUsize};
+
+use crate::{
+	calculations::CalculationSettings,
+	interactive::{
+		batch::{segment_traits, write_traits, SegmentTraits},
+		hull::SplitRadialSettings,
+		npy::write_npy,
+		InteractiveSave,
+	},
+	Error,
+};
+
+/// Calculate the traits for all segments in a saved interactive session, sorted by id.
+pub fn session_traits(input: &Path) -> Result<Vec<SegmentTraits>, Error> {
+	let reader = std::io::BufReader::new(std::fs::File::open(input)?);
+	let (save, _) = InteractiveSave::read(reader)?;
+	Ok(segment_traits(
+		save.segments,
+		save.world_offset,
+		&save.source_location,
+		&CalculationSettings::default(),
+		&SplitRadialSettings::default(),
+		&AtomicUsize::new(0),
+	))
+}
+
+/// Calculate the traits for all segments in a saved interactive session without a window.
+///
+/// The output format is selected by the extension of `output`, `.csv` or JSON otherwise.
+pub fn extract_traits(input: &Path, output: &Path) -> Result<(), Error> {
+	let segments = session_traits(input)?;
+
+	let writer = std::io::BufWriter::new(std::fs::File::create(output)?);
+	let csv = output
+		.extension()
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+	write_traits(writer, &segments, csv)?;
+	Ok(())
+}
+
+/// Write all points in a saved interactive session as a NumPy `.npy` file.
+pub fn export_npy(input: &Path, output: &Path) -> Result<(), Error> {
+	let reader = std::io::BufReader::new(std::fs::File::open(input)?);
+	let (save, _) = InteractiveSave::read(reader)?;
+
+	let writer = std::io::BufWriter::new(std::fs::File::create(output)?);
+	write_npy(writer, &save.segments)?;
+	Ok(())
+}
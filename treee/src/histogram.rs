@@ -0,0 +1,96 @@
+use crate::id;
+
+/// Height of the histogram in points.
+const HEIGHT: f32 = 80.0;
+
+/// Histogram for values in the range [0.0, 1.0].
+///
+/// The counts are cached for a key, the values are only counted again if the key or the bins change.
+#[derive(Debug)]
+pub struct Histogram<K> {
+	bins: usize,
+	cache: Option<(K, Vec<usize>)>,
+}
+
+impl<K: PartialEq> Histogram<K> {
+	pub fn new() -> Self {
+		Self { bins: 32, cache: None }
+	}
+
+	/// Count the values again at the next draw.
+	pub fn invalidate(&mut self) {
+		self.cache = None;
+	}
+
+	/// Count the values for every bin.
+	fn counts(&self, values: impl Iterator<Item = f32>) -> Vec<usize> {
+		let mut counts = vec![0; self.bins];
+		for value in values {
+			let idx = (value.clamp(0.0, 1.0) * self.bins as f32) as usize;
+			counts[idx.min(self.bins - 1)] += 1;
+		}
+		counts
+	}
+
+	/// Draw settings and bars, `values` are only used if the counts for `key` are not cached.
+	pub fn ui<I: Iterator<Item = f32>>(
+		&mut self,
+		ui: &mut egui::Ui,
+		key: K,
+		values: impl FnOnce() -> I,
+	) {
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			ui.label("Bins");
+			if ui.add(egui::Slider::new(&mut self.bins, 4..=128)).changed() {
+				self.cache = None;
+			}
+			ui.end_row();
+		});
+
+		if self
+			.cache
+			.as_ref()
+			.map_or(true, |(cached, _)| *cached != key)
+		{
+			self.cache = Some((key, self.counts(values())));
+		}
+		let Some((_, counts)) = &self.cache else {
+			return;
+		};
+		let max = counts.iter().copied().max().unwrap_or(0).max(1);
+
+		let (rect, response) = ui.allocate_exact_size(
+			egui::vec2(ui.available_width(), HEIGHT),
+			egui::Sense::hover(),
+		);
+		let painter = ui.painter_at(rect);
+		painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+		let width = rect.width() / self.bins as f32;
+		let color = ui.visuals().selection.bg_fill;
+		for (idx, &count) in counts.iter().enumerate() {
+			if count == 0 {
+				continue;
+			}
+			let height = count as f32 / max as f32 * rect.height();
+			let left = rect.left() + idx as f32 * width;
+			painter.rect_filled(
+				egui::Rect::from_min_max(
+					egui::pos2(left, rect.bottom() - height),
+					egui::pos2(left + width, rect.bottom()),
+				),
+				0.0,
+				color,
+			);
+		}
+
+		if let Some(position) = response.hover_pos() {
+			let idx = (((position.x - rect.left()) / width) as usize).min(self.bins - 1);
+			response.on_hover_text(format!(
+				"{:.2} - {:.2}: {}",
+				idx as f32 / self.bins as f32,
+				(idx + 1) as f32 / self.bins as f32,
+				counts[idx],
+			));
+		}
+	}
+}
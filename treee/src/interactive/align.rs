@@ -0,0 +1,203 @@
+use nalgebra as na;
+use rayon::prelude::*;
+
+use crate::{calculations::NeighborsTree, id, units::Units};
+
+/// Maximum points of the segment used for the automatic alignment.
+const ALIGN_SAMPLES: usize = 5_000;
+
+/// Maximum iterations for the automatic alignment.
+const ALIGN_ITERATIONS: usize = 50;
+
+/// Action requested by the alignment UI.
+pub enum AlignAction {
+	/// Move the segment with the transform.
+	Move(na::Isometry3<f32>),
+	/// Align the segment with the points of the other segments.
+	Auto,
+}
+
+/// Rigid transform to align a segment, for example a follow-up scan with a baseline.
+#[derive(Debug)]
+pub struct Align {
+	translation: na::Vector3<f32>,
+	/// Rotation around the vertical axis through the segment center in degrees.
+	rotation: f32,
+	/// Maximum distance between corresponding points for the automatic alignment.
+	pub max_distance: f32,
+	/// Result of the last automatic alignment.
+	pub message: Option<String>,
+}
+
+impl Default for Align {
+	fn default() -> Self {
+		Self {
+			translation: na::Vector3::zeros(),
+			rotation: 0.0,
+			max_distance: 1.0,
+			message: None,
+		}
+	}
+}
+
+impl Align {
+	/// `total` is the transform applied to the segment so far.
+	pub fn ui(
+		&mut self,
+		ui: &mut egui::Ui,
+		center: na::Point3<f32>,
+		total: Option<&na::Isometry3<f32>>,
+		units: Units,
+	) -> Option<AlignAction> {
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			if let Some(total) = total {
+				ui.label("Applied");
+				ui.label(format!(
+					"{}, {:.2}°",
+					units.length(total.translation.vector.norm()),
+					total.rotation.angle().to_degrees(),
+				));
+				ui.end_row();
+			}
+
+			for (name, value) in ["X", "Y", "Z"].into_iter().zip(self.translation.iter_mut()) {
+				ui.label(name);
+				ui.add(egui::DragValue::new(value).speed(0.01));
+				ui.end_row();
+			}
+
+			ui.label("Rotation");
+			ui.add(
+				egui::DragValue::new(&mut self.rotation)
+					.speed(0.1)
+					.suffix("°"),
+			);
+			ui.end_row();
+
+			ui.label("Distance");
+			ui.add(egui::Slider::new(&mut self.max_distance, 0.1..=5.0))
+				.on_hover_text("Maximum distance between corresponding points for Auto");
+			ui.end_row();
+		});
+
+		let (apply, auto, reset) = ui.columns(3, |columns| {
+			(
+				columns[0]
+					.add_sized(
+						[columns[0].available_width(), 0.0],
+						egui::Button::new("Apply"),
+					)
+					.clicked(),
+				columns[1]
+					.add_sized(
+						[columns[1].available_width(), 0.0],
+						egui::Button::new("Auto"),
+					)
+					.on_hover_text("Align with the other segments")
+					.clicked(),
+				columns[2]
+					.add_enabled(total.is_some(), egui::Button::new("Reset"))
+					.on_hover_text("Undo the applied transform")
+					.clicked(),
+			)
+		});
+		if let Some(message) = &self.message {
+			ui.add_sized([ui.available_width(), 0.0], egui::Label::new(message));
+		}
+
+		if apply {
+			let rotation = na::UnitQuaternion::from_axis_angle(
+				&na::Vector3::y_axis(),
+				self.rotation.to_radians(),
+			);
+			let transform =
+				na::Isometry3::translation(
+					center.x + self.translation.x,
+					center.y + self.translation.y,
+					center.z + self.translation.z,
+				) * rotation * na::Isometry3::translation(-center.x, -center.y, -center.z);
+			self.translation = na::Vector3::zeros();
+			self.rotation = 0.0;
+			return Some(AlignAction::Move(transform));
+		}
+		if let (true, Some(total)) = (reset, total) {
+			return Some(AlignAction::Move(total.inverse()));
+		}
+		auto.then_some(AlignAction::Auto)
+	}
+}
+
+/// Rigid transform which moves `source` onto `target` with iterative closest points.
+///
+/// Returns the transform and the mean distance between the corresponding points,
+/// `None` if the point clouds do not overlap.
+pub fn icp(
+	source: &[na::Point3<f32>],
+	target: &[na::Point3<f32>],
+	max_distance: f32,
+) -> Option<(na::Isometry3<f32>, f32)> {
+	if target.is_empty() {
+		return None;
+	}
+	let tree = NeighborsTree::new(target);
+	let step = source.len().div_ceil(ALIGN_SAMPLES).max(1);
+	let source = source.iter().step_by(step).copied().collect::<Vec<_>>();
+
+	let mut transform = na::Isometry3::identity();
+	let mut mean = f32::MAX;
+	for _ in 0..ALIGN_ITERATIONS {
+		let pairs = source
+			.par_iter()
+			.filter_map(|p| {
+				let p = transform * p;
+				let entry = tree.nearest(&p, max_distance * max_distance)?;
+				Some((p, target[entry.index], entry.distance.sqrt()))
+			})
+			.collect::<Vec<_>>();
+		if pairs.len() < 3 {
+			break;
+		}
+		mean = pairs.iter().map(|&(_, _, d)| d).sum::<f32>() / pairs.len() as f32;
+
+		let step = best_fit(&pairs);
+		transform = step * transform;
+		if step.translation.vector.norm() < 1e-4 && step.rotation.angle() < 1e-5 {
+			break;
+		}
+	}
+	(mean < f32::MAX).then_some((transform, mean))
+}
+
+/// Rigid transform with the least squared distance between the pairs.
+///
+/// Source: <https://en.wikipedia.org/wiki/Kabsch_algorithm>
+fn best_fit(pairs: &[(na::Point3<f32>, na::Point3<f32>, f32)]) -> na::Isometry3<f32> {
+	let count = pairs.len() as f32;
+	let source = pairs
+		.iter()
+		.map(|(p, _, _)| p.coords)
+		.sum::<na::Vector3<f32>>()
+		/ count;
+	let target = pairs
+		.iter()
+		.map(|(_, q, _)| q.coords)
+		.sum::<na::Vector3<f32>>()
+		/ count;
+	let mut covariance = na::Matrix3::zeros();
+	for (p, q, _) in pairs {
+		covariance += (p.coords - source) * (q.coords - target).transpose();
+	}
+
+	let svd = covariance.svd(true, true);
+	let (Some(u), Some(v_t)) = (svd.u, svd.v_t) else {
+		return na::Isometry3::identity();
+	};
+	let v = v_t.transpose();
+	// reflections are not rigid
+	let d = (v * u.transpose()).determinant().signum();
+	let rotation = v * na::Matrix3::from_diagonal(&na::vector![1.0, 1.0, d]) * u.transpose();
+	let rotation =
+		na::UnitQuaternion::from_rotation_matrix(&na::Rotation3::from_matrix_unchecked(rotation));
+	let translation = target - rotation * source;
+	na::Isometry3::from_parts(translation.into(), rotation)
+}
@@ -0,0 +1,182 @@
+use nalgebra as na;
+use rayon::prelude::*;
+use std::{
+	collections::HashMap,
+	io::Write,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+};
+
+use crate::{
+	calculations::{CalculationSettings, SegmentData, SegmentInformation},
+	environment,
+	progress::Progress,
+};
+
+use super::{
+	hull::{SplitRadialSettings, Traits},
+	trunk_axis::StemForm,
+};
+
+/// Information and traits for a single segment.
+#[derive(Debug, serde::Serialize)]
+pub struct SegmentTraits {
+	id: u32,
+	#[serde(flatten)]
+	info: SegmentInformation,
+	#[serde(flatten)]
+	traits: Traits,
+	#[serde(flatten)]
+	stem: StemForm,
+	longitude: Option<f64>,
+	latitude: Option<f64>,
+}
+
+/// Calculate the traits for all segments with the split radial bounding volume from `split`, sorted by id.
+///
+/// `done` is incremented for every finished segment.
+pub fn segment_traits(
+	segments: HashMap<u32, Arc<SegmentData>>,
+	world_offset: na::Point3<f64>,
+	source_location: &str,
+	settings: &CalculationSettings,
+	split: &SplitRadialSettings,
+	done: &AtomicUsize,
+) -> Vec<SegmentTraits> {
+	let proj = match proj4rs::Proj::from_proj_string(source_location) {
+		Ok(proj) => Some(proj),
+		Err(err) => {
+			log::warn!("Invalid location: {}", err);
+			None
+		},
+	};
+
+	let mut segments = segments
+		.into_par_iter()
+		.map(|(id, segment)| {
+			let coords = match &proj {
+				Some(proj) => Some(segment.location(world_offset, proj)),
+				None => segment.coords,
+			};
+			let traits = SegmentTraits {
+				id,
+				info: segment.info,
+				traits: split.traits(
+					&segment.points,
+					&segment.classifications,
+					settings.trunk_diameter_height,
+				),
+				stem: StemForm::new(
+					&segment.points,
+					&segment.classifications,
+					settings.slice_width,
+				),
+				longitude: coords.map(|c| c.0.to_degrees()),
+				latitude: coords.map(|c| c.1.to_degrees()),
+			};
+			done.fetch_add(1, Ordering::Relaxed);
+			traits
+		})
+		.collect::<Vec<_>>();
+	segments.sort_by_key(|segment| segment.id);
+	segments
+}
+
+/// Write the traits as CSV or JSON.
+pub fn write_traits(
+	mut writer: impl Write,
+	segments: &[SegmentTraits],
+	csv: bool,
+) -> Result<(), std::io::Error> {
+	if csv {
+		writeln!(
+			writer,
+			"id,trunk_height,crown_height,ground_sep,crown_sep,diameter_breast_height,trunk_cross_area,crown_diameter,crown_projected_area,crown_volume,crown_surface,stem_volume,wood_volume,lean,sweep,longitude,latitude",
+		)?;
+		for segment in segments {
+			let info = &segment.info;
+			let traits = &segment.traits;
+			let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+			writeln!(
+				writer,
+				"{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+				segment.id,
+				info.trunk_height,
+				info.crown_height,
+				info.ground_sep,
+				info.crown_sep,
+				traits.diameter_breast_height,
+				traits.trunk_cross_area,
+				traits.crown_diameter,
+				traits.crown_projected_area,
+				traits.crown_volume,
+				traits.crown_surface,
+				traits.stem_volume,
+				traits.wood_volume,
+				segment.stem.lean,
+				segment.stem.sweep,
+				optional(segment.longitude),
+				optional(segment.latitude),
+			)?;
+		}
+	} else {
+		serde_json::to_writer_pretty(&mut writer, segments)?;
+	}
+	writer.flush()
+}
+
+/// Traits calculation for all segments in the background.
+#[derive(Debug)]
+pub struct Batch {
+	done: Arc<AtomicUsize>,
+	total: usize,
+	progress: Progress,
+}
+
+impl Batch {
+	/// Ask for the output file and calculate the traits for all `segments`.
+	pub fn start(
+		segments: HashMap<u32, Arc<SegmentData>>,
+		world_offset: na::Point3<f64>,
+		source_location: String,
+		settings: CalculationSettings,
+		split: SplitRadialSettings,
+		csv: bool,
+	) -> Self {
+		let done = Arc::new(AtomicUsize::new(0));
+		let total = segments.len();
+		let counter = done.clone();
+		let extension = if csv { "csv" } else { "json" };
+		environment::Saver::start("traits", extension, move |mut saver| {
+			let traits = segment_traits(
+				segments,
+				world_offset,
+				&source_location,
+				&settings,
+				&split,
+				&counter,
+			);
+			write_traits(saver.inner(), &traits, csv).unwrap();
+			saver.save();
+		});
+		Self {
+			done,
+			total,
+			progress: Progress::new("segments"),
+		}
+	}
+
+	/// Draw the progress, returns `false` if the calculation finished or was cancelled.
+	pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+		// the background task holds the other reference until it is done
+		if Arc::strong_count(&self.done) == 1 {
+			return false;
+		}
+		let done = self.done.load(Ordering::Relaxed);
+		self.progress.ui(ui, done, self.total.max(1));
+		ui.ctx().request_repaint();
+		true
+	}
+}
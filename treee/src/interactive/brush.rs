@@ -0,0 +1,42 @@
+use nalgebra as na;
+
+/// Line segments for every circle of the sphere.
+const RING_SEGMENTS: u32 = 32;
+
+/// Outline of the brush used to edit points.
+#[derive(Debug)]
+pub struct Brush {
+	cloud: render::PointCloud,
+	lines: render::Lines,
+}
+
+impl Brush {
+	/// Wireframe sphere with `radius` around `center`.
+	pub fn sphere(center: na::Point3<f32>, radius: f32, state: &render::State) -> Self {
+		let mut points = Vec::new();
+		let mut indices = Vec::new();
+		for (u, v) in [
+			(na::Vector3::x(), na::Vector3::y()),
+			(na::Vector3::y(), na::Vector3::z()),
+			(na::Vector3::z(), na::Vector3::x()),
+		] {
+			let offset = points.len() as u32;
+			points.extend((0..RING_SEGMENTS).map(|i| {
+				let angle = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+				center + (u * angle.cos() + v * angle.sin()) * radius
+			}));
+			indices.extend(
+				(0..RING_SEGMENTS).flat_map(|i| [offset + i, offset + (i + 1) % RING_SEGMENTS]),
+			);
+		}
+
+		Self {
+			cloud: render::PointCloud::new(state, &points),
+			lines: render::Lines::new(state, &indices),
+		}
+	}
+
+	pub fn render<'a>(&'a self, lines_pass: &mut render::LinesPass<'a>) {
+		self.lines.render(&self.cloud, lines_pass);
+	}
+}
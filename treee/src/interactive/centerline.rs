@@ -0,0 +1,250 @@
+use nalgebra as na;
+use std::ops::Not;
+
+use crate::{
+	calculations::{fit_circle, Classification, SegmentData},
+	environment::Saver,
+	id,
+	interactive::Georeference,
+	units::Units,
+};
+
+/// Minimum trunk points in a slice for a circle fit.
+const MIN_SLICE_POINTS: usize = 10;
+
+/// Largest mean distance of the points to the fitted circle relative to the radius.
+const MAX_RELATIVE_RESIDUAL: f32 = 0.25;
+
+/// Format of the saved centerline.
+#[derive(Debug, Clone, Copy)]
+pub enum CenterlineFormat {
+	Csv,
+	Ply,
+}
+
+/// Center of the trunk in a single slice.
+#[derive(Debug, Clone, Copy)]
+pub struct CenterPoint {
+	pub position: na::Point3<f32>,
+	pub diameter: f32,
+	/// `false` if the center is interpolated from the neighboring slices.
+	pub fitted: bool,
+}
+
+/// Polyline through the trunk centers of every slice.
+#[derive(Debug, Default)]
+pub struct Centerline {
+	pub show: bool,
+	points: Vec<CenterPoint>,
+	render: Option<(render::PointCloud, render::Lines)>,
+}
+
+impl Centerline {
+	pub fn update(&mut self, segment: &SegmentData, slice_width: f32, state: &render::State) {
+		if self.show.not() {
+			self.points = Vec::new();
+			self.render = None;
+			return;
+		}
+		self.points = centerline(&segment.points, &segment.classifications, slice_width);
+		self.render = (self.points.len() >= 2).then(|| {
+			let vertices = self.points.iter().map(|p| p.position).collect::<Vec<_>>();
+			let indices = (1..vertices.len() as u32)
+				.flat_map(|idx| [idx - 1, idx])
+				.collect::<Vec<_>>();
+			(
+				render::PointCloud::new(state, &vertices),
+				render::Lines::new(state, &indices),
+			)
+		});
+	}
+
+	pub fn ui(
+		&mut self,
+		ui: &mut egui::Ui,
+		segment: &SegmentData,
+		slice_width: f32,
+		units: Units,
+		state: &render::State,
+	) -> Option<CenterlineFormat> {
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			ui.label("Show");
+			if ui.checkbox(&mut self.show, "").changed() {
+				self.update(segment, slice_width, state);
+			}
+			ui.end_row();
+
+			if self.show {
+				let fitted = self.points.iter().filter(|p| p.fitted).count();
+				ui.label("Fitted Slices");
+				ui.label(format!("{} / {}", fitted, self.points.len()))
+					.on_hover_text("Other slices are interpolated");
+				ui.end_row();
+
+				ui.label("Slice Width");
+				ui.label(units.length(slice_width));
+				ui.end_row();
+			}
+		});
+
+		ui.columns(2, |columns| {
+			for (column, name, format) in [
+				(0, "CSV", CenterlineFormat::Csv),
+				(1, "PLY", CenterlineFormat::Ply),
+			] {
+				if columns[column]
+					.add_sized(
+						[columns[column].available_width(), 0.0],
+						egui::Button::new(name),
+					)
+					.on_hover_text("One point per slice from bottom to top")
+					.clicked()
+				{
+					return Some(format);
+				}
+			}
+			None
+		})
+	}
+
+	pub fn render<'a>(&'a self, lines_pass: &mut render::LinesPass<'a>) {
+		if let Some((cloud, lines)) = &self.render {
+			lines.render(cloud, lines_pass);
+		}
+	}
+}
+
+/// Fit a circle to the trunk points of every slice with `slice_width`.
+///
+/// Slices without enough points or with a poor fit are interpolated from the nearest fitted slices.
+/// Returns an empty list if no slice could be fitted.
+pub fn centerline(
+	points: &[na::Point3<f32>],
+	classifications: &[Classification],
+	slice_width: f32,
+) -> Vec<CenterPoint> {
+	let trunk = points
+		.iter()
+		.zip(classifications)
+		.filter_map(|(&p, &c)| (c == Classification::Trunk).then_some(p))
+		.collect::<Vec<_>>();
+	if trunk.is_empty() {
+		return Vec::new();
+	}
+	let (min, max) = trunk.iter().fold((f32::MAX, f32::MIN), |(min, max), p| {
+		(min.min(p.y), max.max(p.y))
+	});
+
+	let slices = ((max - min) / slice_width) as usize + 1;
+	let mut sets = vec![Vec::new(); slices];
+	for p in trunk {
+		let idx = ((p.y - min) / slice_width) as usize;
+		sets[idx].push(na::point![p.x, p.z]);
+	}
+
+	let fits = sets.iter().map(|set| fit_slice(set)).collect::<Vec<_>>();
+	if fits.iter().all(Option::is_none) {
+		return Vec::new();
+	}
+
+	(0..slices)
+		.map(|idx| {
+			let height = min + (idx as f32 + 0.5) * slice_width;
+			let (center, diameter, fitted) = match fits[idx] {
+				Some((center, diameter)) => (center, diameter, true),
+				None => {
+					let below = (0..idx).rev().find_map(|i| fits[i].map(|fit| (i, fit)));
+					let above = (idx + 1..slices).find_map(|i| fits[i].map(|fit| (i, fit)));
+					let (center, diameter) = match (below, above) {
+						(
+							Some((low, (low_center, low_diameter))),
+							Some((high, (high_center, high_diameter))),
+						) => {
+							let t = (idx - low) as f32 / (high - low) as f32;
+							(
+								low_center + (high_center - low_center) * t,
+								low_diameter + (high_diameter - low_diameter) * t,
+							)
+						},
+						(Some((_, fit)), None) | (None, Some((_, fit))) => fit,
+						(None, None) => unreachable!(),
+					};
+					(center, diameter, false)
+				},
+			};
+			CenterPoint {
+				position: na::point![center.x, height, center.y],
+				diameter,
+				fitted,
+			}
+		})
+		.collect()
+}
+
+/// Center and diameter of the slice, `None` if the fit is not confident.
+///
+/// The fit must match the points and the circle can not be much larger than the points.
+fn fit_slice(points: &[na::Point2<f32>]) -> Option<(na::Point2<f32>, f32)> {
+	if points.len() < MIN_SLICE_POINTS {
+		return None;
+	}
+	let (center, radius) = fit_circle(points)?;
+	let residual = points
+		.iter()
+		.map(|p| ((p - center).norm() - radius).abs())
+		.sum::<f32>()
+		/ points.len() as f32;
+	let (min, max) = points.iter().fold(
+		(
+			na::Point2::from([f32::MAX; 2]),
+			na::Point2::from([f32::MIN; 2]),
+		),
+		|(min, max), p| (min.inf(p), max.sup(p)),
+	);
+	let extent = (max - min).max();
+	(residual <= MAX_RELATIVE_RESIDUAL * radius && radius <= extent)
+		.then_some((center, 2.0 * radius))
+}
+
+/// Save the centerline as `.csv` or as `.ply` with edges between the slices.
+///
+/// Every point has the `diameter` of the fitted circle and if it was `fitted` (0 interpolated, 1 fitted).
+pub fn save_centerline(
+	saver: &mut Saver,
+	points: &[CenterPoint],
+	format: CenterlineFormat,
+	georeference: &Georeference,
+) -> Result<(), std::io::Error> {
+	use std::io::Write;
+
+	let mut writer = saver.inner();
+	match format {
+		CenterlineFormat::Csv => {
+			writeln!(writer, "x,y,z,diameter,fitted")?;
+			for point in points {
+				georeference.write_position_separated(&mut writer, point.position, ",")?;
+				writeln!(writer, ",{},{}", point.diameter, u8::from(point.fitted))?;
+			}
+		},
+		CenterlineFormat::Ply => {
+			writeln!(writer, "ply")?;
+			writeln!(writer, "format ascii 1.0")?;
+			writeln!(writer, "element vertex {}", points.len())?;
+			georeference.write_header(&mut writer)?;
+			writeln!(writer, "property float diameter")?;
+			writeln!(writer, "property uchar fitted")?;
+			writeln!(writer, "element edge {}", points.len().saturating_sub(1))?;
+			writeln!(writer, "property int vertex1")?;
+			writeln!(writer, "property int vertex2")?;
+			writeln!(writer, "end_header")?;
+			for point in points {
+				georeference.write_position(&mut writer, point.position)?;
+				writeln!(writer, " {} {}", point.diameter, u8::from(point.fitted))?;
+			}
+			for idx in 1..points.len() {
+				writeln!(writer, "{} {}", idx - 1, idx)?;
+			}
+		},
+	}
+	Ok(())
+}
@@ -0,0 +1,236 @@
+use nalgebra as na;
+use rayon::prelude::*;
+use std::collections::HashSet;
+
+use crate::{
+	calculations::{map_to_u32, NeighborsTree},
+	environment::Saver,
+	id,
+	interactive::Georeference,
+	units::Units,
+};
+
+/// Distance for the brightest color as multiple of the threshold.
+pub const COLOR_RANGE: f32 = 4.0;
+
+/// Action requested by the comparison UI.
+pub enum CompareAction {
+	/// Compare the segment with the points of the other segments.
+	Compare,
+	/// Save the difference cloud.
+	Export,
+}
+
+/// Change detection between the segment and the surrounding points, for example an older scan.
+#[derive(Debug)]
+pub struct Compare {
+	/// Minimum distance for changed points.
+	pub threshold: f32,
+	pub result: Option<Comparison>,
+}
+
+impl Default for Compare {
+	fn default() -> Self {
+		Self { threshold: 0.1, result: None }
+	}
+}
+
+/// Changed points of a difference.
+#[derive(Debug, Clone)]
+pub struct Difference {
+	/// Distance to the nearest reference point for every point in the segment.
+	pub distances: Vec<f32>,
+	/// Reference points inside the segment bounds without a segment point nearby.
+	pub removed: Vec<na::Point3<f32>>,
+	pub threshold: f32,
+}
+
+/// Result of a difference.
+#[derive(Debug)]
+pub struct Comparison {
+	pub difference: Difference,
+	pub property: render::PointCloudProperty,
+	added_points: usize,
+	mean: f32,
+	added_volume: f32,
+	removed_volume: f32,
+}
+
+impl Compare {
+	pub fn ui(&mut self, ui: &mut egui::Ui, units: Units) -> Option<CompareAction> {
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			ui.label("Threshold");
+			ui.add(egui::Slider::new(&mut self.threshold, 0.01..=1.0));
+			ui.end_row();
+
+			if let Some(result) = &self.result {
+				ui.label("Mean Distance");
+				ui.label(units.length(result.mean));
+				ui.end_row();
+
+				ui.label("Added Points");
+				ui.label(format!("{}", result.added_points));
+				ui.end_row();
+
+				ui.label("Removed Points");
+				ui.label(format!("{}", result.difference.removed.len()));
+				ui.end_row();
+
+				ui.label("Added Volume");
+				ui.label(format!("{:.3}m³", result.added_volume));
+				ui.end_row();
+
+				ui.label("Removed Volume");
+				ui.label(format!("{:.3}m³", result.removed_volume));
+				ui.end_row();
+			}
+		});
+
+		ui.columns(2, |columns| {
+			if columns[0]
+				.add_sized(
+					[columns[0].available_width(), 0.0],
+					egui::Button::new("Compare"),
+				)
+				.on_hover_text("Distance to the points of the other segments")
+				.clicked()
+			{
+				return Some(CompareAction::Compare);
+			}
+			let export = columns[1]
+				.add_enabled_ui(self.result.is_some(), |ui| {
+					ui.add_sized([ui.available_width(), 0.0], egui::Button::new("Save"))
+						.on_hover_text("Difference cloud as .ply")
+				})
+				.inner
+				.clicked();
+			export.then_some(CompareAction::Export)
+		})
+	}
+}
+
+impl Comparison {
+	/// Compare the `points` of a segment with the `reference` points around it.
+	///
+	/// The volumes are estimated with voxels of the threshold size.
+	pub fn new(
+		points: &[na::Point3<f32>],
+		reference: &[na::Point3<f32>],
+		threshold: f32,
+		state: &render::State,
+	) -> Self {
+		let max_distance = threshold * COLOR_RANGE;
+		let distances = nearest_distances(points, reference, max_distance);
+
+		let (min, max) = points.iter().fold(
+			(
+				na::Point3::from([f32::MAX; 3]),
+				na::Point3::from([f32::MIN; 3]),
+			),
+			|(min, max), p| (min.inf(p), max.sup(p)),
+		);
+		let inside = reference
+			.iter()
+			.copied()
+			.filter(|p| (0..3).all(|dim| (min[dim]..=max[dim]).contains(&p[dim])))
+			.collect::<Vec<_>>();
+		let removed = nearest_distances(&inside, points, threshold)
+			.into_iter()
+			.zip(inside)
+			.filter_map(|(d, p)| (d >= threshold).then_some(p))
+			.collect::<Vec<_>>();
+
+		let added = points
+			.iter()
+			.zip(&distances)
+			.filter_map(|(&p, &d)| (d >= threshold).then_some(p));
+		let added_points = added.clone().count();
+		let added_volume = voxel_volume(added, threshold);
+		let removed_volume = voxel_volume(removed.iter().copied(), threshold);
+		let mean = distances.iter().sum::<f32>() / distances.len().max(1) as f32;
+
+		let values = distances
+			.iter()
+			.map(|&d| map_to_u32(d / max_distance))
+			.collect::<Vec<_>>();
+		Self {
+			property: render::PointCloudProperty::new(state, &values),
+			difference: Difference { distances, removed, threshold },
+			added_points,
+			mean,
+			added_volume,
+			removed_volume,
+		}
+	}
+
+	/// Normalized distances for the histogram.
+	pub fn normalized(&self) -> impl Iterator<Item = f32> + '_ {
+		let max_distance = self.difference.threshold * COLOR_RANGE;
+		self.difference
+			.distances
+			.iter()
+			.map(move |d| d / max_distance)
+	}
+}
+
+/// Distance to the nearest reference point, at most `max_distance`.
+fn nearest_distances(
+	points: &[na::Point3<f32>],
+	reference: &[na::Point3<f32>],
+	max_distance: f32,
+) -> Vec<f32> {
+	if reference.is_empty() {
+		return vec![max_distance; points.len()];
+	}
+	let tree = NeighborsTree::new(reference);
+	points
+		.par_iter()
+		.map(|p| {
+			tree.nearest(p, max_distance * max_distance)
+				.map_or(max_distance, |entry| entry.distance.sqrt())
+		})
+		.collect()
+}
+
+/// Volume of the voxels with the edge length `size` which contain a point.
+fn voxel_volume(points: impl Iterator<Item = na::Point3<f32>>, size: f32) -> f32 {
+	let voxels = points
+		.map(|p| p.coords.map(|v| (v / size).floor() as isize))
+		.collect::<HashSet<_>>();
+	voxels.len() as f32 * size.powi(3)
+}
+
+/// Save the segment and removed points as `.ply`.
+///
+/// Every point has the `distance` to the other scan and the `change`
+/// (0 unchanged, 1 added, 2 removed).
+pub fn save_difference(
+	saver: &mut Saver,
+	points: &[na::Point3<f32>],
+	difference: &Difference,
+	georeference: &Georeference,
+) -> Result<(), std::io::Error> {
+	use std::io::Write;
+
+	let mut writer = saver.inner();
+	writeln!(writer, "ply")?;
+	writeln!(writer, "format ascii 1.0")?;
+	writeln!(
+		writer,
+		"element vertex {}",
+		points.len() + difference.removed.len()
+	)?;
+	georeference.write_header(&mut writer)?;
+	writeln!(writer, "property float distance")?;
+	writeln!(writer, "property uchar change")?;
+	writeln!(writer, "end_header")?;
+	for (&p, &d) in points.iter().zip(&difference.distances) {
+		georeference.write_position(&mut writer, p)?;
+		writeln!(writer, " {} {}", d, u8::from(d >= difference.threshold))?;
+	}
+	for &p in difference.removed.iter() {
+		georeference.write_position(&mut writer, p)?;
+		writeln!(writer, " {} 2", difference.threshold)?;
+	}
+	Ok(())
+}
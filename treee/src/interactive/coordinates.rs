@@ -0,0 +1,131 @@
+use std::ops::Not;
+
+use crate::id;
+
+/// Latitude bands from 80°S to 84°N, the last band is 12° high.
+const LATITUDE_BANDS: &[u8; 21] = b"CDEFGHJKLMNPQRSTUVWXX";
+
+/// Column letters for the 100km squares, repeating every three zones.
+const MGRS_COLUMNS: [&[u8; 8]; 3] = [b"ABCDEFGH", b"JKLMNPQR", b"STUVWXYZ"];
+
+/// Row letters for the 100km squares, shifted by five for even zones.
+const MGRS_ROWS: &[u8; 20] = b"ABCDEFGHJKLMNPQRSTUV";
+
+/// Format for the position of a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinateFormat {
+	Degrees,
+	Utm,
+	Mgrs,
+	/// Coordinates in the source location.
+	Raw,
+}
+
+impl CoordinateFormat {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Degrees => "Degrees",
+			Self::Utm => "UTM",
+			Self::Mgrs => "MGRS",
+			Self::Raw => "Raw",
+		}
+	}
+
+	pub fn ui(&mut self, ui: &mut egui::Ui) {
+		egui::ComboBox::from_id_salt(id!())
+			.selected_text(self.name())
+			.width(ui.available_width())
+			.show_ui(ui, |ui| {
+				for v in [Self::Degrees, Self::Utm, Self::Mgrs, Self::Raw] {
+					ui.selectable_value(self, v, v.name());
+				}
+			});
+	}
+
+	/// Labels and values for the position.
+	///
+	/// `coords` are longitude and latitude in radians, `raw` is the position in the source location.
+	/// Falls back to the raw position if the coordinates are unknown or outside the UTM zones.
+	pub fn rows(self, coords: Option<(f64, f64)>, raw: (f64, f64)) -> Vec<(&'static str, String)> {
+		let utm = coords.and_then(|(long, lat)| Utm::new(long, lat));
+		match (self, coords, utm) {
+			(Self::Degrees, Some((long, lat)), _) => {
+				vec![("Lat", format_degrees(lat)), ("Long", format_degrees(long))]
+			},
+			(Self::Utm, _, Some(utm)) => vec![
+				("Zone", format!("{}{}", utm.zone, utm.band as char)),
+				("Easting", format!("{:.1}m", utm.easting)),
+				("Northing", format!("{:.1}m", utm.northing)),
+			],
+			(Self::Mgrs, _, Some(utm)) => vec![("MGRS", utm.mgrs())],
+			_ => vec![
+				("X", format!("{:.2}", raw.0)),
+				("Y", format!("{:.2}", raw.1)),
+			],
+		}
+	}
+}
+
+/// Position in the Universal Transverse Mercator system.
+#[derive(Debug, Clone, Copy)]
+struct Utm {
+	zone: u8,
+	band: u8,
+	easting: f64,
+	northing: f64,
+}
+
+impl Utm {
+	/// Convert longitude and latitude in radians.
+	/// Returns `None` outside of the UTM latitudes.
+	fn new(long: f64, lat: f64) -> Option<Self> {
+		let (long_deg, lat_deg) = (long.to_degrees(), lat.to_degrees());
+		if (-80.0..=84.0).contains(&lat_deg).not() {
+			return None;
+		}
+		let zone = (((long_deg + 180.0) / 6.0).floor() as i32).rem_euclid(60) as u8 + 1;
+		let band =
+			LATITUDE_BANDS[(((lat_deg + 80.0) / 8.0) as usize).min(LATITUDE_BANDS.len() - 1)];
+
+		let south = if lat < 0.0 { " +south" } else { "" };
+		let to = proj4rs::Proj::from_proj_string(&format!(
+			"+proj=utm +zone={} +ellps=WGS84{}",
+			zone, south
+		))
+		.ok()?;
+		let from = proj4rs::Proj::from_proj_string("+proj=latlong +ellps=WGS84").ok()?;
+		let mut point = (long, lat);
+		proj4rs::transform::transform(&from, &to, &mut point).ok()?;
+		Some(Self {
+			zone,
+			band,
+			easting: point.0,
+			northing: point.1,
+		})
+	}
+
+	/// Military Grid Reference System with 1m precision.
+	fn mgrs(&self) -> String {
+		let columns = MGRS_COLUMNS[(self.zone as usize - 1) % 3];
+		let column = ((self.easting / 100_000.0).floor() as usize).clamp(1, columns.len()) - 1;
+		let shift = if self.zone % 2 == 0 { 5 } else { 0 };
+		let row = ((self.northing / 100_000.0).floor() as usize + shift) % MGRS_ROWS.len();
+		format!(
+			"{}{} {}{} {:05} {:05}",
+			self.zone,
+			self.band as char,
+			columns[column] as char,
+			MGRS_ROWS[row] as char,
+			(self.easting.rem_euclid(100_000.0)) as u32,
+			(self.northing.rem_euclid(100_000.0)) as u32,
+		)
+	}
+}
+
+fn format_degrees(val: f64) -> String {
+	let deg = val.to_degrees();
+	let min = deg.fract() * if deg >= 0.0 { 60.0 } else { -60.0 };
+	let deg = deg.trunc() as isize;
+	let (min, sec) = (min.trunc() as isize, min.fract() * 60.0);
+	format!("{:0>2}°{:0>2}'{:0>4.1}\"", deg, min, sec)
+}
@@ -0,0 +1,179 @@
+use nalgebra as na;
+use std::sync::{Arc, Mutex};
+
+/// Maximal number of points in a block.
+const BLOCK_SIZE: usize = 64;
+
+/// First and second intersection of the ray with the box.
+/// Returns `None` if the ray does not hit the box.
+///
+/// Source: <https://tavianator.com/2011/ray_box.html>
+pub fn ray_box(
+	start: na::Point3<f32>,
+	direction: na::Vector3<f32>,
+	min: na::Point3<f32>,
+	max: na::Point3<f32>,
+) -> Option<(f32, f32)> {
+	let mut t_min = f32::NEG_INFINITY;
+	let mut t_max = f32::INFINITY;
+
+	for dir in 0..3 {
+		if direction[dir] != 0.0 {
+			let tx_1 = (min[dir] - start[dir]) / direction[dir];
+			let tx_2 = (max[dir] - start[dir]) / direction[dir];
+
+			t_min = t_min.max(tx_1.min(tx_2));
+			t_max = t_max.min(tx_1.max(tx_2));
+		} else if start[dir] < min[dir] || start[dir] > max[dir] {
+			return None;
+		}
+	}
+
+	(t_max >= t_min && t_max >= 0.0).then_some((t_min, t_max))
+}
+
+/// Small groups of nearby points with their bounding boxes.
+///
+/// Rays only test the points in blocks they pass, in the order the blocks are hit.
+#[derive(Debug)]
+pub struct HitIndex {
+	/// Point indices, every block is a continuous range.
+	order: Vec<u32>,
+	blocks: Vec<Block>,
+}
+
+#[derive(Debug)]
+struct Block {
+	min: na::Point3<f32>,
+	max: na::Point3<f32>,
+	start: usize,
+	end: usize,
+}
+
+impl HitIndex {
+	pub fn new(points: &[na::Point3<f32>]) -> Self {
+		let mut order = (0..points.len() as u32).collect::<Vec<_>>();
+		let mut blocks = Vec::with_capacity(points.len() / BLOCK_SIZE + 1);
+		split(points, &mut order, 0, &mut blocks);
+		Self { order, blocks }
+	}
+
+	/// Index and distance of the first point within `radius` of the ray.
+	pub fn hit(
+		&self,
+		points: &[na::Point3<f32>],
+		start: na::Point3<f32>,
+		direction: na::Vector3<f32>,
+		radius: f32,
+	) -> Option<(usize, f32)> {
+		let expand = na::vector![radius, radius, radius];
+		let mut candidates = self
+			.blocks
+			.iter()
+			.filter_map(|block| {
+				let (t_min, _) = ray_box(start, direction, block.min - expand, block.max + expand)?;
+				Some((t_min, block))
+			})
+			.collect::<Vec<_>>();
+		candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+		let mut found = None;
+		let mut best_dist = f32::MAX;
+		for (t_min, block) in candidates {
+			// points in later blocks are further along the ray
+			if t_min > best_dist {
+				break;
+			}
+			for &idx in &self.order[block.start..block.end] {
+				let diff = points[idx as usize] - start;
+				let l = direction.dot(&diff);
+				if l < 0.0 || l >= best_dist {
+					continue;
+				}
+				let distance = (diff - direction * l).norm();
+				if distance > radius {
+					continue;
+				}
+				found = Some(idx as usize);
+				best_dist = l;
+			}
+		}
+		found.map(|idx| (idx, best_dist))
+	}
+}
+
+/// Split the points at the median of the longest side until the blocks are small enough.
+fn split(points: &[na::Point3<f32>], order: &mut [u32], offset: usize, blocks: &mut Vec<Block>) {
+	if order.is_empty() {
+		return;
+	}
+	let (mut min, mut max) = (points[order[0] as usize], points[order[0] as usize]);
+	for &idx in order.iter() {
+		let p = points[idx as usize];
+		for dim in 0..3 {
+			min[dim] = min[dim].min(p[dim]);
+			max[dim] = max[dim].max(p[dim]);
+		}
+	}
+	if order.len() <= BLOCK_SIZE {
+		blocks.push(Block {
+			min,
+			max,
+			start: offset,
+			end: offset + order.len(),
+		});
+		return;
+	}
+
+	let dim = (max - min).imax();
+	let mid = order.len() / 2;
+	order.select_nth_unstable_by(mid, |&a, &b| {
+		points[a as usize][dim].total_cmp(&points[b as usize][dim])
+	});
+	let (left, right) = order.split_at_mut(mid);
+	split(points, left, offset, blocks);
+	split(points, right, offset + mid, blocks);
+}
+
+/// Lazily built `HitIndex` and bounding box of all points for a segment.
+///
+/// Rebuilt if the number of points changed, `clear` must be called for edits which keep the number.
+#[derive(Debug, Default)]
+pub struct HitCache {
+	index: Mutex<Option<Arc<HitIndex>>>,
+	/// Includes the noise points, which are not part of the segment bounds.
+	bounds: Mutex<Option<(na::Point3<f32>, na::Point3<f32>)>>,
+}
+
+impl HitCache {
+	pub fn get(&self, points: &[na::Point3<f32>]) -> Arc<HitIndex> {
+		let mut cache = self.index.lock().unwrap();
+		match cache.as_ref() {
+			Some(index) if index.order.len() == points.len() => index.clone(),
+			_ => cache.insert(Arc::new(HitIndex::new(points))).clone(),
+		}
+	}
+
+	/// Bounding box of all points, `points` must not be empty.
+	pub fn bounds(&self, points: &[na::Point3<f32>]) -> (na::Point3<f32>, na::Point3<f32>) {
+		*self.bounds.lock().unwrap().get_or_insert_with(|| {
+			points.iter().fold((points[0], points[0]), |(min, max), p| {
+				(min.inf(p), max.sup(p))
+			})
+		})
+	}
+
+	pub fn clear(&mut self) {
+		*self.index.get_mut().unwrap() = None;
+		*self.bounds.get_mut().unwrap() = None;
+	}
+}
+
+impl Clone for HitCache {
+	fn clone(&self) -> Self {
+		Self {
+			index: Mutex::new(self.index.lock().unwrap().clone()),
+			bounds: Mutex::new(*self.bounds.lock().unwrap()),
+		}
+	}
+}
@@ -1,68 +1,98 @@
 use std::{
 	collections::{HashMap, HashSet},
-	hash::Hash,
 	ops::Not,
+	sync::{
+		atomic::{AtomicBool, AtomicUsize, Ordering},
+		Arc,
+	},
 };
 
 use crate::{
-	calculations::{Classification, SegmentData},
+	calculations::{CalculationSettings, Classification, SegmentData},
 	environment::{self, Saver},
 	id,
+	progress::Progress,
 };
 use nalgebra as na;
 
+use super::{
+	trunk_axis::{axis_transform, principal_axis},
+	Georeference,
+};
+
 /// Filter points based on Classification.
+///
+/// Any subset of ground, trunk and crown can be included.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum IncludeMode {
-	All,
-	Crown,
-	Trunk,
-	Ground,
-	Tree,
-	NoCrown,
-}
+pub struct IncludeMode(u8);
 
 impl IncludeMode {
-	pub fn valid(self, c: Classification) -> bool {
-		match self {
-			Self::All => true,
-			Self::Crown => c == Classification::Crown,
-			Self::Trunk => c == Classification::Trunk,
-			Self::Ground => c == Classification::Ground,
-			Self::Tree => matches!(c, Classification::Crown | Classification::Trunk),
-			Self::NoCrown => matches!(c, Classification::Trunk | Classification::Ground),
+	pub const GROUND: Self = Self(1 << 0);
+	pub const TRUNK: Self = Self(1 << 1);
+	pub const CROWN: Self = Self(1 << 2);
+	pub const TREE: Self = Self(Self::TRUNK.0 | Self::CROWN.0);
+	pub const NO_CROWN: Self = Self(Self::GROUND.0 | Self::TRUNK.0);
+	pub const ALL: Self = Self(Self::GROUND.0 | Self::TRUNK.0 | Self::CROWN.0);
+
+	/// Named shortcuts for common combinations.
+	const PRESETS: [(Self, &'static str); 6] = [
+		(Self::ALL, "All"),
+		(Self::CROWN, "Crown"),
+		(Self::TRUNK, "Trunk"),
+		(Self::GROUND, "Ground"),
+		(Self::TREE, "Tree"),
+		(Self::NO_CROWN, "No Crown"),
+	];
+
+	fn mask(c: Classification) -> Self {
+		match c {
+			Classification::Ground => Self::GROUND,
+			Classification::Trunk => Self::TRUNK,
+			Classification::Crown => Self::CROWN,
+			Classification::Noise => Self(0),
 		}
 	}
 
+	pub fn valid(self, c: Classification) -> bool {
+		self.0 & Self::mask(c).0 != 0
+	}
+
 	pub fn name(self) -> &'static str {
-		match self {
-			Self::All => "All",
-			Self::Crown => "Crown",
-			Self::Trunk => "Trunk",
-			Self::Ground => "Ground",
-			Self::Tree => "Tree",
-			Self::NoCrown => "No Crown",
-		}
+		Self::PRESETS
+			.iter()
+			.find(|(mode, _)| *mode == self)
+			.map_or("Custom", |(_, name)| name)
 	}
 
 	pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
-		let mut changed = false;
-		egui::ComboBox::from_id_salt(id!())
-			.selected_text(self.name())
-			.width(ui.available_width())
-			.show_ui(ui, |ui| {
-				for v in [
-					Self::All,
-					Self::Crown,
-					Self::Trunk,
-					Self::Ground,
-					Self::Tree,
-					Self::NoCrown,
+		let previous = *self;
+		ui.vertical(|ui| {
+			egui::ComboBox::from_id_salt(id!())
+				.selected_text(self.name())
+				.width(ui.available_width())
+				.show_ui(ui, |ui| {
+					for (mode, name) in Self::PRESETS {
+						ui.selectable_value(self, mode, name);
+					}
+				});
+			ui.horizontal(|ui| {
+				for c in [
+					Classification::Ground,
+					Classification::Trunk,
+					Classification::Crown,
 				] {
-					changed |= ui.selectable_value(self, v, v.name()).changed();
+					let mut included = self.valid(c);
+					if ui.checkbox(&mut included, format!("{:?}", c)).changed() {
+						let mode = Self(self.0 ^ Self::mask(c).0);
+						// at least one classification must be included
+						if mode.0 != 0 {
+							*self = mode;
+						}
+					}
 				}
 			});
-		changed
+		});
+		*self != previous
 	}
 }
 
@@ -102,6 +132,14 @@ impl Hull {
 		}
 	}
 
+	/// Settings of the split radial bounding volume, `None` for other hulls.
+	pub fn split_settings(&self) -> Option<SplitRadialSettings> {
+		match self {
+			Self::SplitRadialBoundingVolume(split) => Some(split.settings()),
+			_ => None,
+		}
+	}
+
 	pub fn render<'a>(
 		&'a self,
 		cloud: &'a render::PointCloud,
@@ -133,6 +171,8 @@ impl Hull {
 		ui: &mut egui::Ui,
 		segment: &SegmentData,
 		transform: Option<na::Affine3<f32>>,
+		settings: &CalculationSettings,
+		georeference: &Georeference,
 		state: &render::State,
 	) {
 		ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Hull"));
@@ -152,7 +192,7 @@ impl Hull {
 			*self = Hull::Convex(ConvexHull::new(
 				&segment.points,
 				&segment.classifications,
-				IncludeMode::Crown,
+				IncludeMode::CROWN,
 				state,
 			));
 		}
@@ -164,9 +204,10 @@ impl Hull {
 			.clicked()
 		{
 			*self = Hull::RadialBoundingVolume(RadialBoundingVolume::new(
-				IncludeMode::All,
+				IncludeMode::ALL,
 				RadialBoundingVolumeMethod::Max,
 				false,
+				false,
 				&segment.points,
 				&segment.classifications,
 				8,
@@ -184,24 +225,26 @@ impl Hull {
 		{
 			*self = Hull::SplitRadialBoundingVolume(SplitRadialBoundingVolume {
 				crown: RadialBoundingVolume::new(
-					IncludeMode::Crown,
+					IncludeMode::CROWN,
 					RadialBoundingVolumeMethod::Max,
 					false,
+					false,
 					&segment.points,
 					&segment.classifications,
-					26,
-					32,
+					SplitRadialBoundingVolume::CROWN_DIVISIONS.0,
+					SplitRadialBoundingVolume::CROWN_DIVISIONS.1,
 					state,
 					transform,
 				),
 				trunk: RadialBoundingVolume::new(
-					IncludeMode::Trunk,
+					IncludeMode::TRUNK,
 					RadialBoundingVolumeMethod::Max,
 					false,
+					false,
 					&segment.points,
 					&segment.classifications,
-					5,
-					32,
+					SplitRadialBoundingVolume::TRUNK_DIVISIONS.0,
+					SplitRadialBoundingVolume::TRUNK_DIVISIONS.1,
 					state,
 					transform,
 				),
@@ -217,6 +260,10 @@ impl Hull {
 					ui.label("Include");
 					changed |= convex.mode.ui(ui);
 					ui.end_row();
+
+					ui.label("Faces");
+					ui.label(format!("{}", convex.faces()));
+					ui.end_row();
 				});
 				if changed {
 					*convex = ConvexHull::new(
@@ -226,14 +273,26 @@ impl Hull {
 						state,
 					);
 				}
-				if ui
+				if convex.poll(state) {
+					ui.ctx().request_repaint();
+					let task = convex.task.as_mut().unwrap();
+					let done = task.progress.points.load(Ordering::Relaxed);
+					task.bar.ui(ui, done, task.total.max(1));
+					if ui
+						.add_sized([ui.available_width(), 0.0], egui::Button::new("Cancel"))
+						.clicked()
+					{
+						*self = Self::None;
+					}
+				} else if ui
 					.add_sized([ui.available_width(), 0.0], egui::Button::new("Save"))
 					.clicked()
 				{
 					let points = segment.points.clone();
 					let faces = convex.faces.clone();
+					let georeference = georeference.clone();
 					environment::Saver::start("convex_hull", "ply", move |mut saver| {
-						ConvexHull::save(&mut saver, &points, &faces).unwrap();
+						ConvexHull::save(&mut saver, &points, &faces, &georeference).unwrap();
 						saver.save();
 					})
 				}
@@ -252,36 +311,63 @@ impl Hull {
 
 					ui.label("Method");
 					egui::ComboBox::from_id_salt(id!())
-						.selected_text(format!("{:?}", rbv.method))
+						.selected_text(rbv.method.name())
 						.width(ui.available_width())
 						.show_ui(ui, |ui| {
 							for v in [
 								RadialBoundingVolumeMethod::Max,
 								RadialBoundingVolumeMethod::Mean,
+								RadialBoundingVolumeMethod::Percentile(0.95),
+								RadialBoundingVolumeMethod::TopN(5),
 							] {
-								changed |= ui
-									.selectable_value(&mut rbv.method, v, format!("{:?}", v))
-									.changed();
+								let selected = std::mem::discriminant(&rbv.method)
+									== std::mem::discriminant(&v);
+								if ui.selectable_label(selected, v.name()).clicked()
+									&& selected.not()
+								{
+									rbv.method = v;
+									changed = true;
+								}
 							}
 						});
 					ui.end_row();
 
+					match &mut rbv.method {
+						RadialBoundingVolumeMethod::Max | RadialBoundingVolumeMethod::Mean => {},
+						RadialBoundingVolumeMethod::Percentile(quantile) => {
+							ui.label("Percentile");
+							changed |= ui.add(egui::Slider::new(quantile, 0.5..=1.0)).changed();
+							ui.end_row();
+						},
+						RadialBoundingVolumeMethod::TopN(n) => {
+							ui.label("Points");
+							changed |= ui.add(egui::Slider::new(n, 1..=100)).changed();
+							ui.end_row();
+						},
+					}
+
 					ui.label("Slices");
-					changed |= ui.add(egui::Slider::new(&mut rbv.slices, 1..=32)).changed();
+					changed |= ui
+						.add(egui::Slider::new(&mut rbv.data.slices, 1..=32))
+						.changed();
 					ui.end_row();
 
 					ui.label("Sectors");
 					changed |= ui
-						.add(egui::Slider::new(&mut rbv.sectors, 3..=32))
+						.add(egui::Slider::new(&mut rbv.data.sectors, 3..=32))
 						.changed();
 					ui.end_row();
 
-					let enabled = rbv.sectors % 2 == 0;
+					let enabled = rbv.data.sectors % 2 == 0;
 					ui.add_enabled_ui(enabled, |ui| ui.label("Extra"));
 					ui.add_enabled_ui(enabled, |ui| {
 						changed |= ui.checkbox(&mut rbv.symmetric, "Symmetric").changed()
 					});
 					ui.end_row();
+
+					ui.label("Axis");
+					changed |= ui.checkbox(&mut rbv.align, "Align to trunk axis").changed();
+					ui.end_row();
 				});
 				if ui
 					.add_sized(
@@ -291,12 +377,12 @@ impl Hull {
 					.clicked()
 				{
 					let save = RadialBoundingVolumeDistances {
-						center_x: rbv.center.x,
-						center_y: rbv.center.y,
-						height_min: rbv.min,
-						distances: rbv.distances.clone(),
-						slices: rbv.slices,
-						sectors: rbv.sectors,
+						center_x: rbv.data.center.x,
+						center_y: rbv.data.center.y,
+						height_min: rbv.data.min,
+						distances: rbv.data.distances.clone(),
+						slices: rbv.data.slices,
+						sectors: rbv.data.sectors,
 					};
 
 					environment::Saver::start("radial_bounding_volume", "json", move |mut saver| {
@@ -330,6 +416,27 @@ impl Hull {
 					});
 				}
 
+				if ui
+					.add_sized(
+						[ui.available_width(), 0.0],
+						egui::Button::new("Save Skeleton"),
+					)
+					.clicked()
+				{
+					let landmarks = rbv.landmarks(0.0);
+					let sectors = rbv.data.sectors;
+
+					environment::Saver::start("skeleton", "swc", move |mut saver| {
+						write_swc(
+							&mut saver.inner(),
+							&[(SWC_UNDEFINED, sectors, &landmarks)],
+							None,
+						)
+						.unwrap();
+						saver.save();
+					});
+				}
+
 				if changed {
 					rbv.update(segment, transform, state);
 				}
@@ -344,17 +451,17 @@ impl Hull {
 				egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
 					ui.label("Crown Slices");
 					changed |= ui
-						.add(egui::Slider::new(&mut split.crown.slices, 1..=32))
+						.add(egui::Slider::new(&mut split.crown.data.slices, 1..=32))
 						.changed();
 					ui.end_row();
 
 					ui.label("Crown Sectors");
 					changed |= ui
-						.add(egui::Slider::new(&mut split.crown.sectors, 3..=32))
+						.add(egui::Slider::new(&mut split.crown.data.sectors, 3..=32))
 						.changed();
 					ui.end_row();
 
-					let enabled = split.crown.sectors % 2 == 0;
+					let enabled = split.crown.data.sectors % 2 == 0;
 					ui.add_enabled_ui(enabled, |ui| ui.label("Crown"));
 					ui.add_enabled_ui(enabled, |ui| {
 						changed |= ui
@@ -365,17 +472,17 @@ impl Hull {
 
 					ui.label("Trunk Slices");
 					changed |= ui
-						.add(egui::Slider::new(&mut split.trunk.slices, 1..=32))
+						.add(egui::Slider::new(&mut split.trunk.data.slices, 1..=32))
 						.changed();
 					ui.end_row();
 
 					ui.label("Trunk Sectors");
 					changed |= ui
-						.add(egui::Slider::new(&mut split.trunk.sectors, 3..=32))
+						.add(egui::Slider::new(&mut split.trunk.data.sectors, 3..=32))
 						.changed();
 					ui.end_row();
 
-					let enabled = split.trunk.sectors % 2 == 0;
+					let enabled = split.trunk.data.sectors % 2 == 0;
 					ui.add_enabled_ui(enabled, |ui| ui.label("Trunk"));
 					ui.add_enabled_ui(enabled, |ui| {
 						changed |= ui
@@ -396,9 +503,9 @@ impl Hull {
 					.clicked()
 				{
 					let mut landmarks = split.trunk.landmarks(0.0);
-					let base = split.crown.min - split.trunk.min;
+					let base = split.crown.data.min - split.trunk.data.min;
 					landmarks.extend_from_slice(&split.crown.landmarks(base));
-					let top = base + split.crown.slice_height * split.crown.slices as f32;
+					let top = base + split.crown.data.slice_height * split.crown.data.slices as f32;
 					landmarks.extend_from_slice(&[0.0, 0.0, top]);
 
 					environment::Saver::start("landmarks", "txt", move |mut saver| {
@@ -416,6 +523,33 @@ impl Hull {
 						saver.save();
 					});
 				}
+				if ui
+					.add_sized(
+						[ui.available_width(), 0.0],
+						egui::Button::new("Save Skeleton"),
+					)
+					.clicked()
+				{
+					let trunk = split.trunk.landmarks(0.0);
+					let base = split.crown.data.min - split.trunk.data.min;
+					let crown = split.crown.landmarks(base);
+					let top = base + split.crown.data.slice_height * split.crown.data.slices as f32;
+					let trunk_sectors = split.trunk.data.sectors;
+					let crown_sectors = split.crown.data.sectors;
+
+					environment::Saver::start("skeleton", "swc", move |mut saver| {
+						write_swc(
+							&mut saver.inner(),
+							&[
+								(SWC_TRUNK, trunk_sectors, &trunk),
+								(SWC_CROWN, crown_sectors, &crown),
+							],
+							Some(top),
+						)
+						.unwrap();
+						saver.save();
+					});
+				}
 				ui.end_row();
 
 				if ui
@@ -425,7 +559,7 @@ impl Hull {
 					)
 					.clicked()
 				{
-					let save = split.traits();
+					let save = split.traits(settings.trunk_diameter_height);
 					environment::Saver::start("traits", "json", move |mut saver| {
 						serde_json::to_writer_pretty(saver.inner(), &save).unwrap();
 						saver.save();
@@ -442,141 +576,112 @@ pub struct ConvexHull {
 	mode: IncludeMode,
 	faces: Vec<[u32; 3]>,
 	lines: render::Lines,
+	/// Calculation in the background, the hull is empty until it is done.
+	task: Option<HullTask>,
+}
+
+/// Progress of the convex hull calculation, shared with the background task.
+#[derive(Debug, Default)]
+struct HullProgress {
+	/// Points added to the hull.
+	points: AtomicUsize,
+	/// Faces of the current hull.
+	faces: AtomicUsize,
+	/// Set if the result is no longer needed.
+	cancelled: AtomicBool,
+}
+
+/// Running convex hull calculation, dropping the task stops the calculation.
+#[derive(Debug)]
+struct HullTask {
+	progress: Arc<HullProgress>,
+	receiver: crossbeam::channel::Receiver<Vec<[u32; 3]>>,
+	total: usize,
+	bar: Progress,
+}
+
+impl Drop for HullTask {
+	fn drop(&mut self) {
+		self.progress.cancelled.store(true, Ordering::Relaxed);
+	}
 }
 
 impl ConvexHull {
-	/// Calculate convex hull with the gift wrapping algorithm.
-	///
-	/// Source: https://tildesites.bowdoin.edu/~ltoma/teaching/cs3250-CompGeom/spring17/Lectures/cg-hull3d.pdf
+	/// Start the calculation of the convex hull in the background.
 	pub fn new(
 		points: &[na::Point3<f32>],
 		classifications: &[Classification],
 		mode: IncludeMode,
 		state: &render::State,
 	) -> Self {
-		#[derive(Debug, Clone, Copy)]
-		struct Point {
-			idx: usize,
-			pos: na::Point3<f32>,
-		}
-
-		impl PartialEq for Point {
-			fn eq(&self, other: &Self) -> bool {
-				self.idx == other.idx
-			}
-		}
-		impl Eq for Point {}
-		impl Hash for Point {
-			fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-				self.idx.hash(state)
-			}
-		}
-
 		let points = points
 			.iter()
-			.copied()
 			.enumerate()
 			.zip(classifications)
-			.filter_map(|((idx, pos), &c)| mode.valid(c).then_some(Point { idx, pos }))
+			.filter(|((_, pos), &c)| mode.valid(c) && pos.iter().all(|v| v.is_finite()))
+			.map(|((idx, pos), _)| (idx, pos.cast::<f64>()))
 			.collect::<Vec<_>>();
 
-		if points.len() < 10 {
-			return Self {
-				faces: Vec::new(),
-				lines: render::Lines::new(state, &[0, 0]),
-				mode,
-			};
+		let progress = Arc::new(HullProgress::default());
+		let (sender, receiver) = crossbeam::channel::bounded(1);
+		let total = points.len();
+		{
+			let progress = progress.clone();
+			rayon::spawn(move || {
+				let faces = convex_hull(&points, &progress);
+				if progress.cancelled.load(Ordering::Relaxed).not() {
+					_ = sender.send(faces);
+				}
+			});
 		}
 
-		let mut first = points[0];
-		for &p in points.iter() {
-			if p.pos.y < first.pos.y {
-				first = p;
-			}
+		Self {
+			faces: Vec::new(),
+			lines: render::Lines::new(state, &[0, 0]),
+			mode,
+			task: Some(HullTask {
+				progress,
+				receiver,
+				total,
+				bar: Progress::new("points"),
+			}),
 		}
+	}
 
-		let mut best_value = f32::MAX;
-		let mut second = None;
-		for &p in points.iter() {
-			if first.idx == p.idx {
-				continue;
-			}
-			let v = p.pos - first.pos;
-			let x = v.dot(&na::vector![1.0, 0.0, 0.0]);
-			let y = v.dot(&na::vector![0.0, 1.0, 0.0]);
-			let angle = y.atan2(x);
-			assert!(angle >= 0.0);
-			if angle < best_value {
-				best_value = angle;
-				second = Some(p);
-			}
+	/// Number of faces, while calculating the faces of the incomplete hull.
+	fn faces(&self) -> usize {
+		match &self.task {
+			Some(task) => task.progress.faces.load(Ordering::Relaxed),
+			None => self.faces.len(),
 		}
-		let second = second.unwrap();
+	}
 
-		let mut iter = points
-			.iter()
-			.copied()
-			.filter(|&p| p != first && p != second);
-		let mut third = iter.next().unwrap();
-		for p in iter {
-			let out = (second.pos - first.pos)
-				.normalize()
-				.cross(&(third.pos - first.pos).normalize());
-			if out.dot(&(p.pos - first.pos).normalize()) < 0.0 {
-				third = p;
-			}
-		}
+	/// Use the result of the calculation if available.
+	/// Returns `true` while the calculation is running.
+	fn poll(&mut self, state: &render::State) -> bool {
+		let Some(task) = &self.task else {
+			return false;
+		};
+		let Ok(faces) = task.receiver.try_recv() else {
+			return true;
+		};
+		self.task = None;
 
+		let mut edges = HashSet::new();
 		let mut indices = Vec::new();
-		let mut faces = Vec::new();
-		indices.extend_from_slice(&[
-			first.idx as u32,
-			second.idx as u32,
-			second.idx as u32,
-			third.idx as u32,
-			third.idx as u32,
-			first.idx as u32,
-		]);
-		faces.push([first.idx as u32, second.idx as u32, third.idx as u32]);
-
-		let mut edges = [(second, first), (third, second), (first, third)]
-			.into_iter()
-			.collect::<HashSet<_>>();
-
-		while let Some(&(first, second)) = edges.iter().next() {
-			edges.remove(&(first, second));
-
-			let mut iter = points
-				.iter()
-				.copied()
-				.filter(|&p| p != first && p != second);
-			let mut third = iter.next().unwrap();
-			for p in iter {
-				let out = (second.pos - first.pos)
-					.normalize()
-					.cross(&(third.pos - first.pos).normalize());
-				if out.dot(&(p.pos - first.pos).normalize()) < 0.0 {
-					third = p;
+		for face in faces.iter() {
+			for i in 0..3 {
+				let (a, b) = (face[i], face[(i + 1) % 3]);
+				if edges.insert((a.min(b), a.max(b))) {
+					indices.extend_from_slice(&[a, b]);
 				}
 			}
-
-			faces.push([first.idx as u32, second.idx as u32, third.idx as u32]);
-
-			if edges.remove(&(third, first)).not() {
-				edges.insert((first, third));
-				indices.extend_from_slice(&[first.idx as u32, third.idx as u32]);
-			}
-			if edges.remove(&(second, third)).not() {
-				edges.insert((third, second));
-				indices.extend_from_slice(&[third.idx as u32, second.idx as u32]);
-			}
 		}
-
-		Self {
-			lines: render::Lines::new(state, &indices),
-			faces,
-			mode,
+		if indices.is_empty().not() {
+			self.lines = render::Lines::new(state, &indices);
 		}
+		self.faces = faces;
+		false
 	}
 
 	/// Save the convex hull as `.ply`.
@@ -584,6 +689,7 @@ impl ConvexHull {
 		saver: &mut Saver,
 		points: &[na::Point3<f32>],
 		faces: &[[u32; 3]],
+		georeference: &Georeference,
 	) -> Result<(), std::io::Error> {
 		use std::io::Write;
 
@@ -603,15 +709,13 @@ impl ConvexHull {
 		writeln!(writer, "ply")?;
 		writeln!(writer, "format ascii 1.0")?;
 		writeln!(writer, "element vertex {}", used_points.len())?;
-		writeln!(writer, "property float x")?;
-		writeln!(writer, "property float y")?;
-		writeln!(writer, "property float z")?;
+		georeference.write_header(&mut writer)?;
 		writeln!(writer, "element face {}", faces.len())?;
 		writeln!(writer, "property list uchar uint vertex_indices")?;
 		writeln!(writer, "end_header")?;
 		for idx in used_points {
-			let p = points[idx as usize];
-			writeln!(writer, "{} {} {}", p.x, -p.z, p.y)?;
+			georeference.write_position(&mut writer, points[idx as usize])?;
+			writeln!(writer)?;
 		}
 		for face in faces {
 			writeln!(
@@ -624,6 +728,241 @@ impl ConvexHull {
 	}
 }
 
+/// Relative distance to treat points as equal or coplanar.
+const HULL_EPSILON: f64 = 1e-6;
+
+/// Plane of a hull face with the normal pointing outwards.
+#[derive(Debug, Clone, Copy)]
+struct HullFace {
+	vertices: [usize; 3],
+	normal: na::Vector3<f64>,
+	offset: f64,
+}
+
+impl HullFace {
+	/// Face through the vertices, the normal follows the right hand rule.
+	fn new(vertices: [usize; 3], points: &[na::Point3<f64>]) -> Self {
+		let [a, b, c] = vertices.map(|idx| points[idx]);
+		let normal = (b - a).cross(&(c - a)).normalize();
+		Self {
+			vertices,
+			normal,
+			offset: normal.dot(&a.coords),
+		}
+	}
+
+	fn distance(&self, p: na::Point3<f64>) -> f64 {
+		self.normal.dot(&p.coords) - self.offset
+	}
+
+	fn edges(&self) -> [(usize, usize); 3] {
+		let [a, b, c] = self.vertices;
+		[(a, b), (b, c), (c, a)]
+	}
+}
+
+/// Faces of the convex hull with the normals pointing inwards.
+///
+/// Duplicate points are removed and every point is added once, so the calculation always terminates.
+/// Coplanar points result in a two sided polygon, collinear points or less than 3 unique points in no faces.
+/// Stops with no faces if `progress` is cancelled.
+fn convex_hull(points: &[(usize, na::Point3<f64>)], progress: &HullProgress) -> Vec<[u32; 3]> {
+	let Some(&(_, first)) = points.first() else {
+		return Vec::new();
+	};
+	let (mut min, mut max) = (first, first);
+	for &(_, p) in points.iter() {
+		min = min.inf(&p);
+		max = max.sup(&p);
+	}
+	let eps = (max - min).norm() * HULL_EPSILON;
+	if eps <= 0.0 {
+		return Vec::new();
+	}
+
+	let mut unique = HashSet::new();
+	let (indices, points): (Vec<_>, Vec<_>) = points
+		.iter()
+		.copied()
+		.filter(|(_, p)| unique.insert(p.coords.map(|v| (v / eps).round() as i64)))
+		.unzip();
+
+	let farthest = |distance: &dyn Fn(na::Point3<f64>) -> f64| {
+		(0..points.len())
+			.map(|idx| (idx, distance(points[idx]).abs()))
+			.max_by(|a, b| a.1.total_cmp(&b.1))
+			.unwrap()
+	};
+
+	// initial simplex from extreme points
+	let a = (0..points.len())
+		.min_by(|&a, &b| points[a].x.total_cmp(&points[b].x))
+		.unwrap();
+	let (b, distance) = farthest(&|p| (p - points[a]).norm());
+	if distance <= eps {
+		return Vec::new();
+	}
+	let direction = (points[b] - points[a]).normalize();
+	let (c, distance) = farthest(&|p| (p - points[a]).cross(&direction).norm());
+	if distance <= eps {
+		return Vec::new();
+	}
+	let normal = (points[b] - points[a])
+		.cross(&(points[c] - points[a]))
+		.normalize();
+	let (d, distance) = farthest(&|p| normal.dot(&(p - points[a])));
+
+	let faces = if distance <= eps {
+		planar_hull(&points, [a, b, c], eps)
+	} else {
+		let (b, c) = if normal.dot(&(points[d] - points[a])) > 0.0 {
+			(c, b)
+		} else {
+			(b, c)
+		};
+		let mut faces = [[a, b, c], [a, d, b], [b, d, c], [c, d, a]]
+			.map(|vertices| Some(HullFace::new(vertices, &points)))
+			.to_vec();
+		let mut free = Vec::new();
+		// face for every directed edge
+		let mut edges = HashMap::new();
+		for (idx, face) in faces.iter().enumerate() {
+			for edge in face.unwrap().edges() {
+				edges.insert(edge, idx);
+			}
+		}
+
+		for (idx, &p) in points.iter().enumerate() {
+			if progress.cancelled.load(Ordering::Relaxed) {
+				return Vec::new();
+			}
+			progress.points.store(idx, Ordering::Relaxed);
+			progress
+				.faces
+				.store(faces.len() - free.len(), Ordering::Relaxed);
+
+			let start = faces
+				.iter()
+				.enumerate()
+				.filter_map(|(i, face)| face.map(|face| (i, face.distance(p))))
+				.filter(|&(_, distance)| distance > eps)
+				.max_by(|a, b| a.1.total_cmp(&b.1));
+			let Some((start, _)) = start else {
+				continue;
+			};
+
+			// connected faces visible from the point
+			let mut visible = HashSet::from([start]);
+			let mut stack = vec![start];
+			while let Some(face) = stack.pop() {
+				for (a, b) in faces[face].unwrap().edges() {
+					let neighbor = edges[&(b, a)];
+					if visible.contains(&neighbor).not()
+						&& faces[neighbor].unwrap().distance(p) > eps
+					{
+						visible.insert(neighbor);
+						stack.push(neighbor);
+					}
+				}
+			}
+
+			let horizon = visible
+				.iter()
+				.flat_map(|&face| faces[face].unwrap().edges())
+				.filter(|&(a, b)| visible.contains(&edges[&(b, a)]).not())
+				.collect::<HashMap<_, _>>();
+			// skip points where the horizon is not a single loop because of rounding errors
+			let (&begin, _) = horizon.iter().next().unwrap();
+			let mut current = begin;
+			for _ in 0..horizon.len() {
+				current = horizon.get(&current).copied().unwrap_or(usize::MAX);
+			}
+			if current != begin {
+				continue;
+			}
+
+			for face in visible {
+				for edge in faces[face].take().unwrap().edges() {
+					edges.remove(&edge);
+				}
+				free.push(face);
+			}
+			for (&a, &b) in horizon.iter() {
+				let face = HullFace::new([a, b, idx], &points);
+				let slot = match free.pop() {
+					Some(slot) => {
+						faces[slot] = Some(face);
+						slot
+					},
+					None => {
+						faces.push(Some(face));
+						faces.len() - 1
+					},
+				};
+				for edge in face.edges() {
+					edges.insert(edge, slot);
+				}
+			}
+		}
+		faces
+			.into_iter()
+			.flatten()
+			.map(|face| face.vertices)
+			.collect()
+	};
+
+	faces
+		.into_iter()
+		.map(|[a, b, c]| [indices[a] as u32, indices[c] as u32, indices[b] as u32])
+		.collect()
+}
+
+/// Two sided polygon for points in the plane through `a`, `b` and `c`.
+///
+/// Source: https://en.wikibooks.org/wiki/Algorithm_Implementation/Geometry/Convex_hull/Monotone_chain
+fn planar_hull(points: &[na::Point3<f64>], [a, b, c]: [usize; 3], eps: f64) -> Vec<[usize; 3]> {
+	let u = (points[b] - points[a]).normalize();
+	let normal = u.cross(&(points[c] - points[a])).normalize();
+	let v = normal.cross(&u);
+	let mut projected = points
+		.iter()
+		.enumerate()
+		.map(|(idx, &p)| {
+			(
+				idx,
+				na::point![u.dot(&(p - points[a])), v.dot(&(p - points[a]))],
+			)
+		})
+		.collect::<Vec<_>>();
+	projected.sort_by(|(_, a), (_, b)| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+
+	let cross = |o: na::Point2<f64>, a: na::Point2<f64>, b: na::Point2<f64>| {
+		(a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+	};
+	let mut hull: Vec<(usize, na::Point2<f64>)> = Vec::new();
+	for pass in [projected.clone(), projected.into_iter().rev().collect()] {
+		let start = hull.len();
+		for p in pass {
+			while hull.len() >= start + 2
+				&& cross(hull[hull.len() - 2].1, hull[hull.len() - 1].1, p.1) <= eps * eps
+			{
+				hull.pop();
+			}
+			hull.push(p);
+		}
+		hull.pop();
+	}
+	if hull.len() < 3 {
+		return Vec::new();
+	}
+
+	let first = hull[0].0;
+	hull.windows(2)
+		.skip(1)
+		.flat_map(|w| [[first, w[0].0, w[1].0], [first, w[1].0, w[0].0]])
+		.collect()
+}
+
 /// Approximate hull with cylinder seperated into slices and sectors.
 ///
 /// Source: https://scholar.google.com/scholar?hl=en&as_sdt=0%2C5&q=Learning+to+Reconstruct+Botanical+Trees+from+Single+Images&btnG=
@@ -632,28 +971,52 @@ pub struct RadialBoundingVolume {
 	mode: IncludeMode,
 	method: RadialBoundingVolumeMethod,
 	symmetric: bool,
+	/// Align the cylinder to the principal axis of the trunk.
+	align: bool,
 
-	center: na::Point2<f32>,
-	min: f32,
-	distances: Vec<f32>,
-	slices: usize,
-	sectors: usize,
-	slice_height: f32,
+	data: RadialDistances,
 
 	visual_points: render::PointCloud,
 	visual_lines: render::Lines,
 }
 
+/// Distances for a radial bounding volume, without render data.
+#[derive(Debug, Clone)]
+pub struct RadialDistances {
+	pub center: na::Point2<f32>,
+	pub min: f32,
+	pub distances: Vec<f32>,
+	pub slices: usize,
+	pub sectors: usize,
+	pub slice_height: f32,
+}
+
 /// Method used to calculate the distance to the center
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RadialBoundingVolumeMethod {
 	Max,
 	Mean,
-	// Percentile?,
-	// TopN?,
+	/// Quantile in [0.0, 1.0] of the distances in every sector.
+	Percentile(f32),
+	/// Mean of the N farthest points in every sector.
+	TopN(usize),
 }
 
-impl RadialBoundingVolume {
+impl RadialBoundingVolumeMethod {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Max => "Max",
+			Self::Mean => "Mean",
+			Self::Percentile(_) => "Percentile",
+			Self::TopN(_) => "Top N",
+		}
+	}
+}
+
+impl RadialDistances {
+	/// Calculate the distances for the points included by `mode`.
+	/// Returns `None` if no point is included.
+	#[allow(clippy::too_many_arguments)]
 	pub fn new(
 		mode: IncludeMode,
 		method: RadialBoundingVolumeMethod,
@@ -662,15 +1025,12 @@ impl RadialBoundingVolume {
 		classifications: &[Classification],
 		slices: usize,
 		sectors: usize,
-		state: &render::State,
-
 		transform: Option<na::Affine3<f32>>,
-	) -> Self {
-		let (transform, centered) = match transform {
-			Some(transform) => (transform, true),
+	) -> Option<Self> {
+		let (inv, centered) = match transform {
+			Some(transform) => (transform.inverse(), true),
 			None => (na::Affine3::identity(), false),
 		};
-		let inv = transform.inverse();
 
 		let points_iter = points
 			.iter()
@@ -679,23 +1039,7 @@ impl RadialBoundingVolume {
 			.map(|p| inv * p);
 
 		let mut points = points_iter.clone();
-		let Some(first) = points.next() else {
-			return Self {
-				mode,
-				method,
-
-				symmetric,
-
-				center: na::point![0.0, 0.0],
-				min: 0.0,
-				distances: Vec::new(),
-				slice_height: 1.0,
-				slices,
-				sectors,
-				visual_points: render::PointCloud::new(state, &[na::point![0.0, 0.0, 0.0]]),
-				visual_lines: render::Lines::new(state, &[0]),
-			};
-		};
+		let first = points.next()?;
 
 		let mut min = first.y;
 		let mut max = min;
@@ -761,6 +1105,30 @@ impl RadialBoundingVolume {
 					*distance /= count as f32;
 				}
 			},
+			RadialBoundingVolumeMethod::Percentile(_) | RadialBoundingVolumeMethod::TopN(_) => {
+				let mut sectors = vec![Vec::new(); distances.len()];
+				for p in points_iter.clone() {
+					let (idx, distance) = get_idx_and_distance(p);
+					sectors[idx].push(distance);
+				}
+				for (distance, mut values) in distances.iter_mut().zip(sectors) {
+					if values.is_empty() {
+						continue;
+					}
+					values.sort_by(|a, b| a.total_cmp(b));
+					*distance = match method {
+						RadialBoundingVolumeMethod::Percentile(quantile) => {
+							let idx = (quantile * (values.len() - 1) as f32).round() as usize;
+							values[idx.min(values.len() - 1)]
+						},
+						RadialBoundingVolumeMethod::TopN(n) => {
+							let top = &values[values.len().saturating_sub(n.max(1))..];
+							top.iter().sum::<f32>() / top.len() as f32
+						},
+						_ => unreachable!(),
+					};
+				}
+			},
 		}
 
 		// maybe make symmetric
@@ -776,6 +1144,73 @@ impl RadialBoundingVolume {
 			}
 		}
 
+		Some(Self {
+			center,
+			min,
+			distances,
+			slices,
+			sectors,
+			slice_height,
+		})
+	}
+
+	/// Distances without any included points.
+	pub fn empty(slices: usize, sectors: usize) -> Self {
+		Self {
+			center: na::point![0.0, 0.0],
+			min: 0.0,
+			distances: vec![0.0; slices * sectors],
+			slices,
+			sectors,
+			slice_height: 1.0,
+		}
+	}
+}
+
+impl RadialBoundingVolume {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		mode: IncludeMode,
+		method: RadialBoundingVolumeMethod,
+		symmetric: bool,
+		align: bool,
+		points: &[na::Point3<f32>],
+		classifications: &[Classification],
+		slices: usize,
+		sectors: usize,
+		state: &render::State,
+
+		transform: Option<na::Affine3<f32>>,
+	) -> Self {
+		let transform = aligned_transform(align, points, classifications, transform);
+		let Some(data) = RadialDistances::new(
+			mode,
+			method,
+			symmetric,
+			points,
+			classifications,
+			slices,
+			sectors,
+			transform,
+		) else {
+			return Self {
+				mode,
+				method,
+
+				symmetric,
+				align,
+
+				data: RadialDistances::empty(slices, sectors),
+				visual_points: render::PointCloud::new(state, &[na::point![0.0, 0.0, 0.0]]),
+				visual_lines: render::Lines::new(state, &[0]),
+			};
+		};
+		let transform = transform.unwrap_or(na::Affine3::identity());
+		let RadialDistances {
+			center, min, ref distances, slice_height, ..
+		} = data;
+		let sector_angle = std::f32::consts::TAU / sectors as f32;
+
 		// create render data
 		let mut points = Vec::new();
 		let mut indices = Vec::new();
@@ -844,14 +1279,10 @@ impl RadialBoundingVolume {
 			mode,
 			method,
 			symmetric,
+			align,
 
-			distances,
-			slice_height,
-			center,
-			slices,
-			min,
+			data,
 
-			sectors,
 			visual_points,
 			visual_lines,
 		}
@@ -867,43 +1298,111 @@ impl RadialBoundingVolume {
 			self.mode,
 			self.method,
 			self.symmetric,
+			self.align,
 			&segment.points,
 			&segment.classifications,
-			self.slices,
-			self.sectors,
+			self.data.slices,
+			self.data.sectors,
 			state,
 			transform,
 		)
 	}
 
+	/// Settings to calculate the same volume for other segments.
+	pub fn settings(&self) -> RadialSettings {
+		RadialSettings {
+			mode: self.mode,
+			method: self.method,
+			symmetric: self.symmetric,
+			align: self.align,
+			slices: self.data.slices,
+			sectors: self.data.sectors,
+		}
+	}
+
 	/// Calculate characteristic points.
 	fn landmarks(&self, base: f32) -> Vec<f32> {
-		let mut values = Vec::with_capacity(self.slices * self.sectors * 3);
+		let mut values = Vec::with_capacity(self.data.slices * self.data.sectors * 3);
 
-		let sector_angle = std::f32::consts::TAU / self.sectors as f32;
-		for slice in 0..self.slices {
-			for sector in 0..self.sectors {
-				let idx = slice * self.sectors + sector;
-				let distance = self.distances[idx];
+		let sector_angle = std::f32::consts::TAU / self.data.sectors as f32;
+		for slice in 0..self.data.slices {
+			for sector in 0..self.data.sectors {
+				let idx = slice * self.data.sectors + sector;
+				let distance = self.data.distances[idx];
 				let angle = (sector as f32 + 0.5) * sector_angle;
 				values.push(angle.cos() * distance);
 				values.push(angle.sin() * distance);
-				let offset = match self.mode {
+				let offset = if self.mode.valid(Classification::Ground)
+					&& self.mode.valid(Classification::Crown).not()
+				{
 					// interpolate from 0.0 at lowest to 1.0 at highest
-					IncludeMode::Ground | IncludeMode::NoCrown => {
-						slice as f32 / (self.slices - 1) as f32
-					},
+					slice as f32 / (self.data.slices - 1) as f32
+				} else {
 					// middle of the slice
-					_ => 0.5,
+					0.5
 				};
 
-				values.push(base + (slice as f32 + offset) * self.slice_height);
+				values.push(base + (slice as f32 + offset) * self.data.slice_height);
 			}
 		}
 		values
 	}
 }
 
+/// SWC node type for skeletons without classification.
+const SWC_UNDEFINED: u8 = 0;
+/// SWC node type for the trunk, custom types start at 5.
+const SWC_TRUNK: u8 = 5;
+/// SWC node type for the crown.
+const SWC_CROWN: u8 = 6;
+
+/// Write landmarks as SWC skeleton.
+///
+/// Every part is a list of landmarks with `sectors` points per slice, stacked from the bottom.
+/// Each slice gets a node on the axis with the mean distance as radius, connected to the node of the slice below.
+/// The landmarks of a slice are connected to the node of their slice.
+/// Source: http://www.neuronland.org/NLMorphologyConverter/MorphologyFormats/SWC/Spec.html
+fn write_swc(
+	writer: &mut impl std::io::Write,
+	parts: &[(u8, usize, &[f32])],
+	top: Option<f32>,
+) -> Result<(), std::io::Error> {
+	writeln!(writer, "# skeleton exported by treee")?;
+	writeln!(writer, "# id type x y z radius parent")?;
+
+	let mut id = 0;
+	let mut parent = -1;
+	for &(kind, sectors, landmarks) in parts {
+		for slice in landmarks.chunks_exact(sectors * 3) {
+			let radius = slice
+				.chunks_exact(3)
+				.map(|p| (p[0] * p[0] + p[1] * p[1]).sqrt())
+				.sum::<f32>()
+				/ sectors as f32;
+			id += 1;
+			writeln!(
+				writer,
+				"{} {} 0 0 {} {} {}",
+				id, kind, slice[2], radius, parent
+			)?;
+			let center = id;
+			for p in slice.chunks_exact(3) {
+				id += 1;
+				writeln!(
+					writer,
+					"{} {} {} {} {} 0 {}",
+					id, kind, p[0], p[1], p[2], center
+				)?;
+			}
+			parent = center;
+		}
+	}
+	if let Some(top) = top {
+		writeln!(writer, "{} {} 0 0 {} 0 {}", id + 1, SWC_CROWN, top, parent)?;
+	}
+	Ok(())
+}
+
 /// Information to save Radial Bounding Volume.
 #[derive(Debug, serde::Serialize)]
 pub struct RadialBoundingVolumeDistances {
@@ -923,65 +1422,182 @@ pub struct SplitRadialBoundingVolume {
 }
 
 impl SplitRadialBoundingVolume {
-	pub fn traits(&self) -> Traits {
-		let trunk_height = self.trunk.slice_height * self.trunk.slices as f32;
-		let height =
-			self.crown.min + self.crown.slice_height * self.crown.slices as f32 - self.trunk.min;
+	/// Slices and sectors used for the crown.
+	pub const CROWN_DIVISIONS: (usize, usize) = (26, 32);
+	/// Slices and sectors used for the trunk.
+	pub const TRUNK_DIVISIONS: (usize, usize) = (5, 32);
+
+	pub fn traits(&self, diameter_height: f32) -> Traits {
+		Traits::new(&self.crown.data, &self.trunk.data, diameter_height)
+	}
+
+	pub fn settings(&self) -> SplitRadialSettings {
+		SplitRadialSettings {
+			crown: self.crown.settings(),
+			trunk: self.trunk.settings(),
+		}
+	}
+}
+
+/// Settings for a radial bounding volume, without the distances and render data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RadialSettings {
+	pub mode: IncludeMode,
+	pub method: RadialBoundingVolumeMethod,
+	pub symmetric: bool,
+	pub align: bool,
+	pub slices: usize,
+	pub sectors: usize,
+}
+
+impl RadialSettings {
+	/// Distances of the radial bounding volume with these settings for the points.
+	pub fn distances(
+		&self,
+		points: &[na::Point3<f32>],
+		classifications: &[Classification],
+		transform: Option<na::Affine3<f32>>,
+	) -> RadialDistances {
+		RadialDistances::new(
+			self.mode,
+			self.method,
+			self.symmetric,
+			points,
+			classifications,
+			self.slices,
+			self.sectors,
+			aligned_transform(self.align, points, classifications, transform),
+		)
+		.unwrap_or_else(|| RadialDistances::empty(self.slices, self.sectors))
+	}
+}
+
+/// Settings for a split radial bounding volume, used to calculate the traits of all segments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplitRadialSettings {
+	pub crown: RadialSettings,
+	pub trunk: RadialSettings,
+}
+
+impl Default for SplitRadialSettings {
+	fn default() -> Self {
+		let settings = |mode, (slices, sectors)| RadialSettings {
+			mode,
+			method: RadialBoundingVolumeMethod::Max,
+			symmetric: false,
+			align: false,
+			slices,
+			sectors,
+		};
+		Self {
+			crown: settings(
+				IncludeMode::CROWN,
+				SplitRadialBoundingVolume::CROWN_DIVISIONS,
+			),
+			trunk: settings(
+				IncludeMode::TRUNK,
+				SplitRadialBoundingVolume::TRUNK_DIVISIONS,
+			),
+		}
+	}
+}
+
+impl SplitRadialSettings {
+	/// Traits of the split radial bounding volume with these settings for the points.
+	pub fn traits(
+		&self,
+		points: &[na::Point3<f32>],
+		classifications: &[Classification],
+		diameter_height: f32,
+	) -> Traits {
+		let crown = self.crown.distances(points, classifications, None);
+		let trunk = self.trunk.distances(points, classifications, None);
+		Traits::new(&crown, &trunk, diameter_height)
+	}
+}
+
+/// Transform along the principal axis of the trunk if `align` is set, `transform` otherwise or as fallback.
+fn aligned_transform(
+	align: bool,
+	points: &[na::Point3<f32>],
+	classifications: &[Classification],
+	transform: Option<na::Affine3<f32>>,
+) -> Option<na::Affine3<f32>> {
+	if align {
+		principal_axis(points, classifications)
+			.map(|(origin, direction, _)| axis_transform(origin, direction))
+			.or(transform)
+	} else {
+		transform
+	}
+}
+
+impl Traits {
+	/// Calculate the traits for a segment with the default bounding volumes.
+	pub fn from_points(
+		points: &[na::Point3<f32>],
+		classifications: &[Classification],
+		diameter_height: f32,
+	) -> Self {
+		SplitRadialSettings::default().traits(points, classifications, diameter_height)
+	}
+
+	/// Calculate the traits from the crown and trunk distances.
+	/// The trunk diameter is measured at `diameter_height` above the lowest trunk point.
+	pub fn new(crown: &RadialDistances, trunk: &RadialDistances, diameter_height: f32) -> Self {
+		let trunk_height = trunk.slice_height * trunk.slices as f32;
+		let height = crown.min + crown.slice_height * crown.slices as f32 - trunk.min;
 
 		let diameter_breast_height = {
-			let slice = (1.3 / self.trunk.slice_height).floor() as usize;
-			let slice = slice.min(self.trunk.slices - 1);
-			let range = (slice * self.trunk.sectors)..((slice + 1) * self.trunk.sectors);
-			self.trunk.distances[range].iter().sum::<f32>() * 2.0 / self.trunk.sectors as f32
+			let slice = (diameter_height / trunk.slice_height).floor() as usize;
+			let slice = slice.min(trunk.slices - 1);
+			let range = (slice * trunk.sectors)..((slice + 1) * trunk.sectors);
+			trunk.distances[range].iter().sum::<f32>() * 2.0 / trunk.sectors as f32
 		};
 
 		let trunk_cross_area = std::f32::consts::PI * (diameter_breast_height / 2.0).powi(2);
 
-		let mut sector_max = vec![0.0f32; self.crown.sectors];
-		for slice in 0..self.crown.slices {
-			for sector in 0..self.crown.sectors {
-				let idx = slice * self.crown.sectors + sector;
-				sector_max[sector] = sector_max[sector].max(self.crown.distances[idx]);
+		let mut sector_max = vec![0.0f32; crown.sectors];
+		for slice in 0..crown.slices {
+			for sector in 0..crown.sectors {
+				let idx = slice * crown.sectors + sector;
+				sector_max[sector] = sector_max[sector].max(crown.distances[idx]);
 			}
 		}
 
 		let mut crown_sectors_sum = 0.0;
-		for slice in 0..self.crown.slices {
-			for sector in 0..self.crown.sectors {
-				let idx = slice * self.crown.sectors + sector;
-				crown_sectors_sum += self.crown.distances[idx];
+		for slice in 0..crown.slices {
+			for sector in 0..crown.sectors {
+				let idx = slice * crown.sectors + sector;
+				crown_sectors_sum += crown.distances[idx];
 			}
 		}
 
-		let crown_diameter =
-			sector_max.iter().copied().sum::<f32>() * 2.0 / self.crown.sectors as f32;
+		let crown_diameter = sector_max.iter().copied().sum::<f32>() * 2.0 / crown.sectors as f32;
 
 		let crown_projected_area = sector_max.iter().copied().map(|x| x.powi(2)).sum::<f32>()
 			* std::f32::consts::PI
-			/ self.crown.sectors as f32;
+			/ crown.sectors as f32;
 
 		let crown_volume = {
 			let mut crown_volume = 0.0;
-			for slice in 0..self.crown.slices {
-				for sector in 0..self.crown.sectors {
-					let idx = slice * self.crown.sectors + sector;
-					crown_volume += self.crown.distances[idx].powi(2);
+			for slice in 0..crown.slices {
+				for sector in 0..crown.sectors {
+					let idx = slice * crown.sectors + sector;
+					crown_volume += crown.distances[idx].powi(2);
 				}
 			}
-			crown_volume * std::f32::consts::PI * self.crown.slice_height
-				/ self.crown.sectors as f32
+			crown_volume * std::f32::consts::PI * crown.slice_height / crown.sectors as f32
 		};
 
 		let crown_surface = 2.0 * crown_projected_area
-			+ std::f32::consts::TAU * self.crown.slice_height / self.crown.sectors as f32
-				* crown_sectors_sum;
+			+ std::f32::consts::TAU * crown.slice_height / crown.sectors as f32 * crown_sectors_sum;
 
 		let stem_volume = trunk_cross_area * (trunk_height + (height - trunk_height) / 3.0);
 		let wood_volume = stem_volume
-			+ trunk_cross_area / (self.crown.slices as f32 * self.crown.sectors as f32)
-				* crown_sectors_sum;
+			+ trunk_cross_area / (crown.slices as f32 * crown.sectors as f32) * crown_sectors_sum;
 
-		Traits {
+		Self {
 			diameter_breast_height,
 			trunk_cross_area,
 			crown_diameter,
@@ -997,12 +1613,134 @@ impl SplitRadialBoundingVolume {
 /// Characterisic traits for the tree.
 #[derive(Debug, serde::Serialize)]
 pub struct Traits {
-	diameter_breast_height: f32,
-	trunk_cross_area: f32,
-	crown_diameter: f32,
-	crown_projected_area: f32,
-	crown_volume: f32,
-	crown_surface: f32,
-	stem_volume: f32,
-	wood_volume: f32,
+	pub diameter_breast_height: f32,
+	pub trunk_cross_area: f32,
+	pub crown_diameter: f32,
+	pub crown_projected_area: f32,
+	pub crown_volume: f32,
+	pub crown_surface: f32,
+	pub stem_volume: f32,
+	pub wood_volume: f32,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn hull(points: &[na::Point3<f64>]) -> Vec<[u32; 3]> {
+		let points = points.iter().copied().enumerate().collect::<Vec<_>>();
+		convex_hull(&points, &HullProgress::default())
+	}
+
+	/// Signed volume enclosed by the faces.
+	fn volume(points: &[na::Point3<f64>], faces: &[[u32; 3]]) -> f64 {
+		faces
+			.iter()
+			.map(|&[a, b, c]| {
+				let [a, b, c] = [a, b, c].map(|idx| points[idx as usize].coords);
+				a.dot(&b.cross(&c)) / 6.0
+			})
+			.sum()
+	}
+
+	/// Every edge is used once in each direction.
+	fn closed(faces: &[[u32; 3]]) -> bool {
+		let edges = faces
+			.iter()
+			.flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+			.collect::<HashSet<_>>();
+		edges.len() == faces.len() * 3 && edges.iter().all(|&(a, b)| edges.contains(&(b, a)))
+	}
+
+	fn cube() -> Vec<na::Point3<f64>> {
+		(0..8)
+			.map(|i| na::point![(i & 1) as f64, ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64])
+			.collect()
+	}
+
+	#[test]
+	fn cube_with_inner_points() {
+		let mut points = cube();
+		points.extend([na::point![0.5, 0.5, 0.5], na::point![0.2, 0.7, 0.4]]);
+		let faces = hull(&points);
+		assert_eq!(faces.len(), 12);
+		assert!(closed(&faces));
+		assert!(faces.iter().flatten().all(|&idx| idx < 8));
+		assert!((volume(&points, &faces).abs() - 1.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn duplicates() {
+		let points = cube()
+			.into_iter()
+			.flat_map(|p| [p, p, p + na::vector![1e-9, 0.0, 0.0]])
+			.collect::<Vec<_>>();
+		let faces = hull(&points);
+		assert_eq!(faces.len(), 12);
+		assert!(closed(&faces));
+		// no face with the same position twice
+		assert!(faces.iter().all(|face| {
+			let [a, b, c] = face.map(|idx| points[idx as usize]);
+			(a - b).norm() > 0.5 && (b - c).norm() > 0.5 && (c - a).norm() > 0.5
+		}));
+	}
+
+	#[test]
+	fn planar() {
+		// tilted square grid with inner points
+		let points = (0..25)
+			.map(|i| {
+				let (x, y) = ((i % 5) as f64, (i / 5) as f64);
+				na::point![x, y, 0.5 * x + 0.25 * y]
+			})
+			.collect::<Vec<_>>();
+		let faces = hull(&points);
+		// two sided polygon with the 4 corners
+		assert_eq!(faces.len(), 4);
+		assert!(faces
+			.iter()
+			.flatten()
+			.all(|&idx| [0, 4, 20, 24].contains(&idx)));
+		assert!(volume(&points, &faces).abs() < 1e-9);
+		let area = faces
+			.iter()
+			.map(|&[a, b, c]| {
+				let [a, b, c] = [a, b, c].map(|idx| points[idx as usize]);
+				(b - a).cross(&(c - a)).norm() / 2.0
+			})
+			.sum::<f64>();
+		// both sides of the 4 x 4 square, stretched by the slope
+		assert!((area - 2.0 * 16.0 * 1.3125f64.sqrt()).abs() < 1e-9);
+	}
+
+	#[test]
+	fn few_unique_points() {
+		let triangle = [
+			na::point![0.0, 0.0, 0.0],
+			na::point![1.0, 0.0, 0.0],
+			na::point![0.0, 1.0, 0.0],
+		];
+		let points = [triangle, triangle].concat();
+		let faces = hull(&points);
+		assert_eq!(faces.len(), 2);
+		assert!(closed(&faces));
+
+		assert!(hull(&[triangle[0], triangle[1], triangle[1]]).is_empty());
+		assert!(hull(&[triangle[0]; 5]).is_empty());
+		assert!(hull(&[]).is_empty());
+	}
+
+	#[test]
+	fn planar_hull_square() {
+		let points = [
+			na::point![0.0, 0.0, 0.0],
+			na::point![1.0, 0.0, 0.0],
+			na::point![1.0, 1.0, 0.0],
+			na::point![0.0, 1.0, 0.0],
+			na::point![0.5, 0.5, 0.0],
+		];
+		let faces = planar_hull(&points, [0, 1, 3], 1e-9);
+		assert_eq!(faces.len(), 4);
+		assert!(faces.iter().flatten().all(|&idx| idx != 4));
+	}
 }
@@ -0,0 +1,213 @@
+use nalgebra as na;
+use std::{collections::HashMap, ops::Not, sync::Arc};
+
+use crate::{
+	calculations::{CalculationSettings, SegmentData},
+	id,
+	units::Units,
+};
+
+use super::hull::Traits;
+
+/// Maximal number of rows calculated each frame.
+const ROWS_PER_FRAME: usize = 4;
+
+/// Cached metrics for a single segment.
+#[derive(Debug, Clone, Copy)]
+struct Row {
+	id: u32,
+	height: f32,
+	diameter: f32,
+	crown_diameter: f32,
+	coords: Option<(f64, f64)>,
+}
+
+/// Column to sort the table by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+	Id,
+	Height,
+	Diameter,
+	CrownDiameter,
+	Longitude,
+	Latitude,
+}
+
+impl Column {
+	fn name(self) -> &'static str {
+		match self {
+			Self::Id => "Id",
+			Self::Height => "Height",
+			Self::Diameter => "Diameter",
+			Self::CrownDiameter => "Crown",
+			Self::Longitude => "Longitude",
+			Self::Latitude => "Latitude",
+		}
+	}
+
+	fn compare(self, a: &Row, b: &Row) -> std::cmp::Ordering {
+		let coord = |row: &Row, f: fn((f64, f64)) -> f64| row.coords.map(f).unwrap_or(f64::MAX);
+		match self {
+			Self::Id => a.id.cmp(&b.id),
+			Self::Height => a.height.total_cmp(&b.height),
+			Self::Diameter => a.diameter.total_cmp(&b.diameter),
+			Self::CrownDiameter => a.crown_diameter.total_cmp(&b.crown_diameter),
+			Self::Longitude => coord(a, |c| c.0).total_cmp(&coord(b, |c| c.0)),
+			Self::Latitude => coord(a, |c| c.1).total_cmp(&coord(b, |c| c.1)),
+		}
+	}
+}
+
+/// Table with the metrics for all segments.
+///
+/// Rows are calculated lazily and cached until the segment changes.
+#[derive(Debug)]
+pub struct Inventory {
+	pub open: bool,
+	rows: HashMap<u32, Row>,
+	sort: Column,
+	ascending: bool,
+}
+
+impl Inventory {
+	pub fn new() -> Self {
+		Self {
+			open: false,
+			rows: HashMap::new(),
+			sort: Column::Id,
+			ascending: true,
+		}
+	}
+
+	/// Remove the cached row for a changed segment.
+	pub fn invalidate(&mut self, idx: u32) {
+		self.rows.remove(&idx);
+	}
+
+	/// Calculate missing rows, returns `true` if rows are still missing.
+	fn update(
+		&mut self,
+		segments: &mut HashMap<u32, Arc<SegmentData>>,
+		settings: &CalculationSettings,
+		world_offset: na::Point3<f64>,
+		proj: Option<&proj4rs::Proj>,
+	) -> bool {
+		self.rows.retain(|idx, _| segments.contains_key(idx));
+		let missing = segments
+			.keys()
+			.copied()
+			.filter(|idx| self.rows.contains_key(idx).not())
+			.collect::<Vec<_>>();
+		for &id in missing.iter().take(ROWS_PER_FRAME) {
+			let segment = Arc::make_mut(segments.get_mut(&id).unwrap());
+			let diameter = segment.update_info(None, settings).trunk_diameter;
+			let traits = Traits::from_points(
+				&segment.points,
+				&segment.classifications,
+				settings.trunk_diameter_height,
+			);
+			if let Some(proj) = proj {
+				segment.update_location(world_offset, proj);
+			}
+			self.rows.insert(
+				id,
+				Row {
+					id,
+					height: segment.max.y - segment.min.y,
+					diameter,
+					crown_diameter: traits.crown_diameter,
+					coords: segment.coords,
+				},
+			);
+		}
+		missing.len() > ROWS_PER_FRAME
+	}
+
+	/// Draw the table, returns the clicked segment.
+	pub fn ui(
+		&mut self,
+		ctx: &egui::Context,
+		segments: &mut HashMap<u32, Arc<SegmentData>>,
+		settings: &CalculationSettings,
+		world_offset: na::Point3<f64>,
+		source_location: &str,
+		units: Units,
+	) -> Option<u32> {
+		if self.open.not() {
+			return None;
+		}
+		let proj = proj4rs::Proj::from_proj_string(source_location).ok();
+		if self.update(segments, settings, world_offset, proj.as_ref()) {
+			ctx.request_repaint();
+		}
+
+		let mut rows = self.rows.values().copied().collect::<Vec<_>>();
+		rows.sort_by(|a, b| self.sort.compare(a, b));
+		if self.ascending.not() {
+			rows.reverse();
+		}
+
+		let mut clicked = None;
+		let mut open = self.open;
+		egui::Window::new("Inventory")
+			.id(id!())
+			.open(&mut open)
+			.default_height(400.0)
+			.show(ctx, |ui| {
+				ui.label(format!("{} of {} segments", rows.len(), segments.len()));
+				ui.separator();
+				egui::ScrollArea::vertical().show(ui, |ui| {
+					egui::Grid::new(id!())
+						.num_columns(6)
+						.striped(true)
+						.show(ui, |ui| {
+							for column in [
+								Column::Id,
+								Column::Height,
+								Column::Diameter,
+								Column::CrownDiameter,
+								Column::Longitude,
+								Column::Latitude,
+							] {
+								let text = match (column == self.sort, self.ascending) {
+									(false, _) => column.name().to_string(),
+									(true, true) => format!("{} ^", column.name()),
+									(true, false) => format!("{} v", column.name()),
+								};
+								if ui.button(text).clicked() {
+									if self.sort == column {
+										self.ascending = self.ascending.not();
+									} else {
+										self.sort = column;
+										self.ascending = true;
+									}
+								}
+							}
+							ui.end_row();
+
+							for row in rows.iter() {
+								if ui.link(format!("{}", row.id)).clicked() {
+									clicked = Some(row.id);
+								}
+								ui.label(units.length(row.height));
+								ui.label(units.diameter(row.diameter));
+								ui.label(units.length(row.crown_diameter));
+								match row.coords {
+									Some((long, lat)) => {
+										ui.label(format!("{:.6}", long.to_degrees()));
+										ui.label(format!("{:.6}", lat.to_degrees()));
+									},
+									None => {
+										ui.label("-");
+										ui.label("-");
+									},
+								}
+								ui.end_row();
+							}
+						});
+				});
+			});
+		self.open = open;
+		clicked
+	}
+}
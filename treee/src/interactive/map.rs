@@ -0,0 +1,220 @@
+use nalgebra as na;
+use std::{collections::HashMap, io::Write, sync::Arc};
+
+use crate::calculations::{CalculationSettings, Classification, SegmentData};
+
+/// Width of the plot area in pixels.
+const WIDTH: f32 = 1000.0;
+
+/// Space around the plot area in pixels.
+const MARGIN: f32 = 60.0;
+
+/// Circle diameter for every meter of trunk diameter, real trunks are too small to see.
+const CIRCLE_SCALE: f32 = 5.0;
+
+/// Trunk position and diameter of a single segment.
+#[derive(Debug, Clone, Copy)]
+struct Tree {
+	id: u32,
+	/// East and north in meters.
+	position: na::Point2<f32>,
+	diameter: f32,
+}
+
+impl Tree {
+	fn new(id: u32, segment: &SegmentData, settings: &CalculationSettings) -> Option<Self> {
+		if segment.points.is_empty() {
+			return None;
+		}
+		let diameter = segment.properties(None, settings).trunk_diameter;
+		let trunk = segment
+			.points
+			.iter()
+			.zip(&segment.classifications)
+			.filter(|&(_, &c)| c == Classification::Trunk)
+			.map(|(p, _)| p)
+			.collect::<Vec<_>>();
+		let center = if trunk.is_empty() {
+			na::center(&segment.min, &segment.max)
+		} else {
+			trunk
+				.iter()
+				.fold(na::Point3::origin(), |sum, p| sum + p.coords)
+				/ trunk.len() as f32
+		};
+		Some(Self {
+			id,
+			position: na::point![center.x, -center.z],
+			diameter: if diameter.is_finite() { diameter } else { 0.0 },
+		})
+	}
+
+	fn radius(&self) -> f32 {
+		self.diameter / 2.0 * CIRCLE_SCALE
+	}
+}
+
+/// Write a top-down map of the trunk positions as SVG.
+///
+/// Every segment is a circle scaled by the trunk diameter with its id.
+/// North is up, with a north arrow, a scale bar and a legend for the circle sizes.
+pub fn write_map(
+	mut writer: impl Write,
+	segments: HashMap<u32, Arc<SegmentData>>,
+	settings: &CalculationSettings,
+) -> Result<(), std::io::Error> {
+	let mut trees = segments
+		.into_iter()
+		.filter_map(|(id, segment)| Tree::new(id, &segment, settings))
+		.collect::<Vec<_>>();
+	trees.sort_by_key(|tree| tree.id);
+
+	let (mut min, mut max) = (
+		na::point![f32::MAX, f32::MAX],
+		na::point![f32::MIN, f32::MIN],
+	);
+	for tree in trees.iter() {
+		let r = na::vector![tree.radius(), tree.radius()];
+		min = min.inf(&(tree.position - r));
+		max = max.sup(&(tree.position + r));
+	}
+	if trees.is_empty() {
+		(min, max) = (na::point![0.0, 0.0], na::point![1.0, 1.0]);
+	}
+	let extent = (max - min).max().max(1.0);
+	let scale = WIDTH / extent;
+	let height = (max.y - min.y).max(1.0) * scale;
+	let to_map = |p: na::Point2<f32>| {
+		(
+			MARGIN + (p.x - min.x) * scale,
+			MARGIN + (max.y - p.y) * scale,
+		)
+	};
+	let (total_width, total_height) = (WIDTH + 2.0 * MARGIN, height + 3.0 * MARGIN);
+
+	writeln!(
+		writer,
+		r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="sans-serif" font-size="10">"#,
+		total_width, total_height,
+	)?;
+	writeln!(writer, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+
+	writeln!(
+		writer,
+		r#"<g fill="forestgreen" fill-opacity="0.5" stroke="darkgreen">"#
+	)?;
+	for tree in trees.iter() {
+		let (x, y) = to_map(tree.position);
+		writeln!(
+			writer,
+			r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}"><title>{} ({:.2}m)</title></circle>"#,
+			x,
+			y,
+			(tree.radius() * scale).max(1.0),
+			tree.id,
+			tree.diameter,
+		)?;
+	}
+	writeln!(writer, "</g>")?;
+	writeln!(writer, r#"<g fill="black">"#)?;
+	for tree in trees.iter() {
+		let (x, y) = to_map(tree.position);
+		let r = (tree.radius() * scale).max(1.0);
+		writeln!(
+			writer,
+			r#"<text x="{:.1}" y="{:.1}">{}</text>"#,
+			x + r + 2.0,
+			y + 3.0,
+			tree.id,
+		)?;
+	}
+	writeln!(writer, "</g>")?;
+
+	// north arrow in the top right corner
+	let (x, y) = (total_width - MARGIN / 2.0, MARGIN / 2.0);
+	writeln!(
+		writer,
+		r#"<path d="M {x} {top} L {right} {bottom} L {x} {middle} L {left} {bottom} Z" fill="black"/>"#,
+		x = x,
+		top = y - 15.0,
+		bottom = y + 10.0,
+		middle = y + 4.0,
+		left = x - 8.0,
+		right = x + 8.0,
+	)?;
+	writeln!(
+		writer,
+		r#"<text x="{}" y="{}" text-anchor="middle" font-size="12">N</text>"#,
+		x,
+		y + 24.0,
+	)?;
+
+	// scale bar with a round length of about a fifth of the width
+	let length = round_length(extent / 5.0);
+	let (x, y) = (MARGIN, MARGIN + height + MARGIN / 2.0);
+	writeln!(
+		writer,
+		r#"<path d="M {x} {y} h {w} M {x} {top} v 10 M {end} {top} v 10" stroke="black" stroke-width="2"/>"#,
+		x = x,
+		y = y,
+		w = length * scale,
+		top = y - 5.0,
+		end = x + length * scale,
+	)?;
+	writeln!(
+		writer,
+		r#"<text x="{}" y="{}" text-anchor="middle">{}m</text>"#,
+		x + length * scale / 2.0,
+		y + 16.0,
+		length,
+	)?;
+
+	// legend with example diameters
+	let x = x + length * scale + MARGIN;
+	writeln!(
+		writer,
+		r#"<text x="{}" y="{}">Trunk diameter, circles are {}x larger</text>"#,
+		x,
+		y - 12.0,
+		CIRCLE_SCALE,
+	)?;
+	let mut x = x;
+	for diameter in [0.2, 0.4, 0.8] {
+		let r = diameter / 2.0 * CIRCLE_SCALE * scale;
+		writeln!(
+			writer,
+			r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}" fill="forestgreen" fill-opacity="0.5" stroke="darkgreen"/>"#,
+			x + r,
+			y + 4.0,
+			r.max(1.0),
+		)?;
+		writeln!(
+			writer,
+			r#"<text x="{:.1}" y="{:.1}">{}m</text>"#,
+			x + 2.0 * r + 4.0,
+			y + 7.0,
+			diameter,
+		)?;
+		x += 2.0 * r + 40.0;
+	}
+	writeln!(
+		writer,
+		r#"<text x="{}" y="{}">{} segments</text>"#,
+		MARGIN,
+		total_height - 8.0,
+		trees.len(),
+	)?;
+
+	writeln!(writer, "</svg>")?;
+	writer.flush()
+}
+
+/// Largest length of 1, 2 or 5 times a power of ten below `length`.
+fn round_length(length: f32) -> f32 {
+	let power = 10.0f32.powf(length.log10().floor());
+	[5.0, 2.0, 1.0]
+		.into_iter()
+		.map(|f| f * power)
+		.find(|&l| l <= length)
+		.unwrap_or(power)
+}
@@ -1,19 +1,51 @@
-mod hull;
-mod trunk_axis;
-
+mod align;
+pub mod batch;
+mod brush;
+mod centerline;
+mod compare;
+mod coordinates;
+pub mod hits;
+pub mod hull;
+mod inventory;
+mod map;
+pub mod npy;
+mod sources;
+pub mod trunk_axis;
+
+use align::{Align, AlignAction};
+use batch::Batch;
+use brush::Brush;
+use centerline::{Centerline, CenterlineFormat};
+use compare::{Compare, CompareAction, Comparison};
+use coordinates::CoordinateFormat;
+use inventory::Inventory;
 use nalgebra as na;
-use std::{collections::HashMap, ops::Not};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sources::Sources;
+use std::{
+	collections::{HashMap, HashSet},
+	io::{Read, Seek, SeekFrom, Write},
+	ops::Not,
+	sync::Arc,
+};
 use trunk_axis::{TrunkAxis, TrunkAxisAlgorithm};
 
 use crate::{
-	calculations::{map_to_u32, CalculationProperties, Classification, SegmentData, SegmentSave},
+	calculations::{
+		map_to_u32, point_normals, CalculationProperties, CalculationSettings, Classification,
+		NeighborsTree, OutlierSettings, SegmentData, SegmentSave, TrunkDiameterMethod,
+	},
+	camera::{Camera, CameraSave},
 	environment::{self, Saver},
+	histogram::Histogram,
 	laz,
-	program::{DisplaySettings, Event},
+	program::{DisplaySettings, Event, Lookup, ViewerSettings},
+	segmenting::SegmentationSettings,
+	units::Units,
 	Error,
 };
 
-use hull::Hull;
+use hull::{Hull, SplitRadialSettings};
 
 /// Special index for the deleted index.
 pub const DELETED_INDEX: u32 = 0;
@@ -28,30 +60,204 @@ macro_rules! id {
 
 /// State for the Interactive phase.
 pub struct Interactive {
-	pub segments: HashMap<u32, SegmentData>,
-	pub deleted: SegmentData,
+	/// Shared with background work like the autosave, changed with `Arc::make_mut`.
+	pub segments: HashMap<u32, Arc<SegmentData>>,
+	pub deleted: Arc<SegmentData>,
 	sender: crossbeam::channel::Sender<Event>,
 
 	pub modus: Modus,
 	pub show_deleted: bool,
+	/// Segment under the cursor, which would be selected by a click.
+	pub hovered: Option<u32>,
+	/// Outline of the brush at the cursor in edit modi.
+	pub brush: Option<Brush>,
+	pub color_modus: ColorModus,
+	/// Input to jump to a segment and the message for invalid input.
+	jump: (String, Option<String>),
+	pub inventory: Inventory,
+	/// Running traits calculation for all segments.
+	batch: Option<Batch>,
+	/// Split radial bounding volume from the last segment view, used for the traits of all segments.
+	split_settings: SplitRadialSettings,
+	/// Cached for the segment, display modus and revision of the display data.
+	histogram: Histogram<(u32, DisplayModus, u64)>,
+	/// Minutes between autosaves, disabled if `0`.
+	pub autosave_interval: u32,
+	last_autosave: web_time::Instant,
+	/// Number of edits to the segments.
+	changes: u64,
+	/// `changes` at the last autosave.
+	autosaved: u64,
+	/// `changes` at the last explicit save, `None` if never saved.
+	saved: Option<u64>,
 	draw_radius: f32,
+	pub calculation_settings: CalculationSettings,
+	pub outlier_settings: OutlierSettings,
+	georeferenced: bool,
 
 	pub source_location: String,
 	world_offset: na::Point3<f64>,
+	/// Random number generator for new segment indices.
+	pub rng: StdRng,
+	/// Settings used to create the segments.
+	pub provenance: Provenance,
+	/// Rotation applied to level the ground, undone for the `.ply` exports.
+	pub leveling: Option<na::UnitQuaternion<f32>>,
+	/// Total transform applied by the alignment per segment.
+	pub alignments: HashMap<u32, na::Isometry3<f32>>,
+	/// Merged projects and the project of every segment.
+	pub sources: Sources,
+	/// Units to display lengths.
+	pub units: Units,
+	coordinate_format: CoordinateFormat,
 }
 
 /// Data to save and load interactive phase.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct InteractiveSave {
-	pub segments: HashMap<u32, SegmentData>,
-	pub deleted: SegmentData,
+	pub segments: HashMap<u32, Arc<SegmentData>>,
+	pub deleted: Arc<SegmentData>,
 	pub world_offset: na::Point3<f64>,
 	pub source_location: String,
 }
 
+/// Start of versioned saves, older saves start directly with the segments.
+const SAVE_MAGIC: &[u8; 8] = b"treeesav";
+
+/// Current version of the save format.
+///
+/// - `0`: only `InteractiveSave`
+/// - `1`: `SessionSave` after `InteractiveSave`
+/// - `2`: `Provenance` after `SessionSave`
+/// - `3`: leveling rotation after `Provenance`
+/// - `4`: `SessionSave` as length-prefixed JSON, missing fields use the defaults
+const SAVE_VERSION: u32 = 4;
+
+/// Working context saved with the segments.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SessionSave {
+	pub camera: Option<CameraSave>,
+	pub viewer: Option<ViewerSettings>,
+	/// Segment with the open view.
+	pub view: Option<u32>,
+	/// Saved separately to read older sessions.
+	#[serde(skip)]
+	pub provenance: Provenance,
+	/// Rotation applied to level the ground, saved separately to read older sessions.
+	#[serde(skip)]
+	pub leveling: Option<na::UnitQuaternion<f32>>,
+	/// Total transform applied by the alignment per segment.
+	pub alignments: HashMap<u32, na::Isometry3<f32>>,
+	/// Merged projects and the project of every segment.
+	pub sources: Sources,
+}
+
+/// Layout of `SessionSave` before version 4.
+#[derive(serde::Deserialize)]
+struct SessionSaveV3 {
+	camera: Option<CameraSave>,
+	viewer: Option<ViewerSettingsV3>,
+	view: Option<u32>,
+}
+
+/// Layout of `ViewerSettings` before version 4, without the classification colors.
+#[derive(serde::Deserialize)]
+struct ViewerSettingsV3 {
+	background: na::Point3<f32>,
+	point_size: f32,
+	lookup: Lookup,
+	eye_dome_radius: f32,
+	eye_dome_sample_count: u32,
+	camera_animated: bool,
+	camera_animation_duration: f32,
+	context_brightness: f32,
+	sample_count: u32,
+}
+
+impl From<SessionSaveV3> for SessionSave {
+	fn from(save: SessionSaveV3) -> Self {
+		Self {
+			camera: save.camera,
+			viewer: save.viewer.map(|viewer| ViewerSettings {
+				background: viewer.background,
+				point_size: viewer.point_size,
+				lookup: viewer.lookup,
+				eye_dome_radius: viewer.eye_dome_radius,
+				eye_dome_sample_count: viewer.eye_dome_sample_count,
+				camera_animated: viewer.camera_animated,
+				camera_animation_duration: viewer.camera_animation_duration,
+				context_brightness: viewer.context_brightness,
+				sample_count: viewer.sample_count,
+				..Default::default()
+			}),
+			view: save.view,
+			..Default::default()
+		}
+	}
+}
+
+/// Settings used to create the segments.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Provenance {
+	/// Program version which created the segments, empty if unknown.
+	pub version: String,
+	/// Parameters of the segmentation, `None` if unknown.
+	pub segmentation: Option<SegmentationSettings>,
+	/// Parameters for the calculations at the last save.
+	pub calculation: CalculationSettings,
+}
+
+impl InteractiveSave {
+	/// Write the versioned save.
+	pub fn write(&self, session: &SessionSave, mut writer: impl Write) -> Result<(), Error> {
+		writer.write_all(SAVE_MAGIC)?;
+		bincode::serialize_into(&mut writer, &SAVE_VERSION)?;
+		bincode::serialize_into(&mut writer, self)?;
+		bincode::serialize_into(&mut writer, &serde_json::to_vec(session)?)?;
+		bincode::serialize_into(&mut writer, &session.provenance)?;
+		bincode::serialize_into(&mut writer, &session.leveling)?;
+		Ok(())
+	}
+
+	/// Read a save, saves without a session use the default session.
+	pub fn read(mut reader: impl Read + Seek) -> Result<(Self, SessionSave), Error> {
+		let mut magic = [0; SAVE_MAGIC.len()];
+		reader.read_exact(&mut magic)?;
+		let version = if &magic == SAVE_MAGIC {
+			bincode::deserialize_from::<_, u32>(&mut reader)?
+		} else {
+			reader.seek(SeekFrom::Start(0))?;
+			0
+		};
+		if version > SAVE_VERSION {
+			return Err(Error::SaveVersion(version));
+		}
+		let save = bincode::deserialize_from::<_, Self>(&mut reader)?;
+		let mut session = if version >= 4 {
+			let data = bincode::deserialize_from::<_, Vec<u8>>(&mut reader)?;
+			serde_json::from_slice::<SessionSave>(&data)?
+		} else if version >= 1 {
+			bincode::deserialize_from::<_, SessionSaveV3>(&mut reader)?.into()
+		} else {
+			SessionSave::default()
+		};
+		if version >= 2 {
+			session.provenance = bincode::deserialize_from(&mut reader)?;
+		}
+		if version >= 3 {
+			session.leveling = bincode::deserialize_from(&mut reader)?;
+		}
+		Ok((save, session))
+	}
+}
+
 /// Default location (europe) to convert position to global coordinates.
 const DEFAULT_LOCATION: &str = "+proj=utm\n+ellps=GRS80\n+zone=32";
 
+/// Default minutes between autosaves.
+const DEFAULT_AUTOSAVE_INTERVAL: u32 = 5;
+
 impl SegmentData {
 	/// First and second intersection with the bounding box.
 	/// Returns `None` if the ray does not hit the bounding box.
@@ -62,20 +268,11 @@ impl SegmentData {
 		start: na::Point3<f32>,
 		direction: na::Vector3<f32>,
 	) -> Option<(f32, f32)> {
-		let mut t_min = f32::NEG_INFINITY;
-		let mut t_max = f32::INFINITY;
-
-		for dir in 0..3 {
-			if direction[dir] != 0.0 {
-				let tx_1 = (self.min[dir] - start[dir]) / direction[dir];
-				let tx_2 = (self.max[dir] - start[dir]) / direction[dir];
-
-				t_min = t_min.max(tx_1.min(tx_2));
-				t_max = t_max.min(tx_1.max(tx_2));
-			}
+		if self.points.is_empty() {
+			return None;
 		}
-
-		(t_max >= t_min && t_max >= 0.0).then_some((t_min, t_max))
+		let (min, max) = self.hits.bounds(&self.points);
+		hits::ray_box(start, direction, min, max)
 	}
 
 	/// Distance to the first point intersection the ray.
@@ -86,66 +283,101 @@ impl SegmentData {
 		direction: na::Vector3<f32>,
 		display_settings: &DisplaySettings,
 	) -> Option<f32> {
-		let mut found = false;
-		let mut best_dist = f32::MAX;
+		self.exact_hit(start, direction, display_settings)
+			.map(|(_, distance)| distance)
+	}
 
-		for &point in self.points.iter() {
-			let diff = point - start;
-			let diff_length = diff.norm();
-			if diff_length >= best_dist {
-				continue;
-			}
-			let cos = direction.dot(&diff.normalize());
-			let sin = (1.0 - cos * cos).sqrt();
-			let distance = sin * diff_length;
-			if distance > display_settings.point_cloud_environment.scale {
-				continue;
-			}
-			let l = cos * diff_length;
-			if l < 0.0 || best_dist < l {
-				continue;
-			}
-			found = true;
-			best_dist = l;
+	/// Index and distance of the first point intersecting the ray.
+	/// Returns `None` if the ray does not hit a point.
+	pub fn exact_hit(
+		&self,
+		start: na::Point3<f32>,
+		direction: na::Vector3<f32>,
+		display_settings: &DisplaySettings,
+	) -> Option<(usize, f32)> {
+		self.hits.get(&self.points).hit(
+			&self.points,
+			start,
+			direction,
+			display_settings.point_cloud_environment.scale,
+		)
+	}
+
+	/// The sphere overlaps the bounding box.
+	pub fn near(&self, center: na::Point3<f32>, radius: f32) -> bool {
+		if self.points.is_empty() {
+			return false;
 		}
-		found.then_some(best_dist)
+		let (min, max) = self.hits.bounds(&self.points);
+		(0..3).all(|dim| ((min[dim] - radius)..(max[dim] + radius)).contains(&center[dim]))
 	}
 
 	/// Remove the points inside the sphere from the segment and add them to the target segment.
 	/// Returns `true` if any point changes segment.
 	pub fn remove(&mut self, center: na::Point3<f32>, radius: f32, target: &mut Self) -> bool {
-		for dim in 0..3 {
-			if ((self.min[dim] - radius)..(self.max[dim] + radius))
-				.contains(&center[dim])
-				.not()
-			{
-				return false;
-			}
+		if self.near(center, radius).not() {
+			return false;
 		}
 		let r2 = radius * radius;
-		let mut changed = false;
+		self.remove_where(|p| (p - center).norm_squared() <= r2, target) > 0
+	}
+
+	/// Move every point matching `condition` to `target`.
+	/// Returns the number of moved points.
+	pub fn remove_where(
+		&mut self,
+		condition: impl Fn(na::Point3<f32>) -> bool,
+		target: &mut Self,
+	) -> usize {
+		let remove = self.points.iter().map(|&p| condition(p)).collect();
+		self.remove_marked(remove, target)
+	}
+
+	/// Move statistical outliers to `target`.
+	/// Returns the number of removed points.
+	pub fn remove_outliers(&mut self, settings: &OutlierSettings, target: &mut Self) -> usize {
+		let outliers = settings.outliers(&self.points);
+		self.remove_marked(outliers, target)
+	}
 
+	/// Move every point marked in `remove` to `target`.
+	///
+	/// Removed points are replaced by the last points, so only the removed positions and the
+	/// end of the segment change.
+	fn remove_marked(&mut self, mut remove: Vec<bool>, target: &mut Self) -> usize {
 		let len = self.points.len();
-		let mut del = 0;
-		let p = self.points.as_mut_slice();
-		let c = self.classifications.as_mut_slice();
-
-		// retain with multiple vecs
-		for i in 0..len {
-			if (p[i] - center).norm_squared() <= r2 {
-				del += 1;
-				target.points.push(p[i]);
-				target.classifications.push(c[i]);
-				changed = true;
+		let mut end = len;
+		let mut i = 0;
+		while i < end {
+			if remove[i] {
+				target.points.push(self.points[i]);
+				target.classifications.push(self.classifications[i]);
+				end -= 1;
+				self.points.swap(i, end);
+				self.classifications.swap(i, end);
+				remove.swap(i, end);
 			} else {
-				p.swap(i - del, i);
-				c.swap(i - del, i);
+				i += 1;
 			}
 		}
-		self.points.truncate(len - del);
-		self.classifications.truncate(len - del);
+		self.points.truncate(end);
+		self.classifications.truncate(end);
 
-		changed
+		len - end
+	}
+
+	/// Mark statistical outliers as noise.
+	/// Returns the number of newly marked points.
+	pub fn mark_outliers(&mut self, settings: &OutlierSettings) -> usize {
+		let outliers = settings.outliers(&self.points);
+		let mut marked = 0;
+		for (c, outlier) in self.classifications.iter_mut().zip(outliers) {
+			if outlier && *c != Classification::Noise {
+				*c = Classification::Noise;
+				marked += 1;
+			}
+		}
+		marked
 	}
 
 	/// Change classification for every point inside the sphere.
@@ -155,21 +387,25 @@ impl SegmentData {
 		radius: f32,
 		classification: Classification,
 	) -> bool {
-		for dim in 0..3 {
-			if ((self.min[dim] - radius)..(self.max[dim] + radius))
-				.contains(&center[dim])
-				.not()
-			{
-				return false;
-			}
+		if self.near(center, radius).not() {
+			return false;
 		}
 		let r2 = radius * radius;
+		self.change_classification_where(|p| (p - center).norm_squared() <= r2, classification)
+	}
+
+	/// Change classification for every point matching `condition`.
+	pub fn change_classification_where(
+		&mut self,
+		condition: impl Fn(na::Point3<f32>) -> bool,
+		classification: Classification,
+	) -> bool {
 		let mut changed = false;
 		self.points
 			.iter()
 			.zip(self.classifications.as_mut_slice())
 			.for_each(|(&p, c)| {
-				if (p - center).norm_squared() > r2 {
+				if condition(p).not() {
 					return;
 				}
 				*c = classification;
@@ -180,21 +416,26 @@ impl SegmentData {
 
 	/// Update cashed information and data required for rendering.
 	fn changed(&mut self, idx: u32, sender: &crossbeam::channel::Sender<Event>) {
+		self.hits.clear();
 		if self.points.is_empty() {
 			return;
 		}
 
-		(self.min, self.max) = (self.points[0], self.points[0]);
+		self.min = na::Point3::new(f32::MAX, f32::MAX, f32::MAX);
+		self.max = na::Point3::new(f32::MIN, f32::MIN, f32::MIN);
 		let (mut trunk_min, mut trunk_max) = (f32::MAX, f32::MIN);
 		let (mut crown_min, mut crown_max) = (f32::MAX, f32::MIN);
 
 		for (&p, &c) in self.points.iter().zip(&self.classifications) {
-			for dim in 0..3 {
-				self.min[dim] = self.min[dim].min(p[dim]);
-				self.max[dim] = self.max[dim].max(p[dim]);
+			// outliers would stretch the bounds used for the traits
+			if c != Classification::Noise {
+				for dim in 0..3 {
+					self.min[dim] = self.min[dim].min(p[dim]);
+					self.max[dim] = self.max[dim].max(p[dim]);
+				}
 			}
 			match c {
-				Classification::Ground => {},
+				Classification::Ground | Classification::Noise => {},
 				Classification::Trunk => {
 					trunk_min = trunk_min.min(p.y);
 					trunk_max = trunk_max.max(p.y);
@@ -205,6 +446,10 @@ impl SegmentData {
 				},
 			}
 		}
+		if self.min.x > self.max.x {
+			// only noise, use all points
+			(self.min, self.max) = self.hits.bounds(&self.points);
+		}
 		if trunk_min > trunk_max {
 			self.info.trunk_height = 0.0;
 		} else {
@@ -221,25 +466,54 @@ impl SegmentData {
 	}
 
 	/// Update information for the viewed segment.
-	fn update_info(&mut self, calc_curve: bool) -> CalculationProperties {
+	///
+	/// The curvature is only calculated with `neighbors`, which must contain the points.
+	fn update_info(
+		&mut self,
+		neighbors: Option<&NeighborsTree>,
+		settings: &CalculationSettings,
+	) -> CalculationProperties {
 		self.info.update(
 			&self.points,
 			&self.classifications,
 			self.min.y,
 			self.max.y,
-			calc_curve,
+			neighbors,
+			settings,
+		)
+	}
+
+	/// Calculated properties without changing the cached information.
+	pub fn properties(
+		&self,
+		neighbors: Option<&NeighborsTree>,
+		settings: &CalculationSettings,
+	) -> CalculationProperties {
+		let mut info = self.info;
+		info.update(
+			&self.points,
+			&self.classifications,
+			self.min.y,
+			self.max.y,
+			neighbors,
+			settings,
 		)
 	}
 
 	/// Update the world coordinates.
-	fn update_location(&mut self, world_offset: na::Point3<f64>, proj: &proj4rs::Proj) {
+	pub fn update_location(&mut self, world_offset: na::Point3<f64>, proj: &proj4rs::Proj) {
+		self.coords = Some(self.location(world_offset, proj));
+	}
+
+	/// World coordinates of the center.
+	pub fn location(&self, world_offset: na::Point3<f64>, proj: &proj4rs::Proj) -> (f64, f64) {
 		let to = proj4rs::Proj::from_proj_string("+proj=latlong +ellps=GRS80").unwrap();
 		let mut point = (
 			world_offset.x + ((self.min.x + self.max.x) / 2.0) as f64,
 			-(world_offset.z + ((self.min.z + self.max.z) / 2.0) as f64),
 		);
 		proj4rs::transform::transform(proj, &to, &mut point).unwrap();
-		self.coords = Some(point);
+		point
 	}
 }
 
@@ -250,30 +524,68 @@ impl Interactive {
 		world_offset: na::Point3<f64>,
 	) -> (Self, crossbeam::channel::Receiver<Event>) {
 		let (sender, receiver) = crossbeam::channel::unbounded();
-		let deleted = SegmentData::new(Vec::new());
+		let deleted = Arc::new(SegmentData::new(Vec::new()));
 
 		let interactive = Self {
-			segments,
+			segments: segments
+				.into_iter()
+				.map(|(idx, segment)| (idx, Arc::new(segment)))
+				.collect(),
 			modus: Modus::SelectView,
 			deleted,
 			draw_radius: 0.5,
+			calculation_settings: CalculationSettings::default(),
+			outlier_settings: OutlierSettings::default(),
+			georeferenced: false,
 			sender,
 			show_deleted: false,
+			hovered: None,
+			brush: None,
+			color_modus: ColorModus::Segment,
+			jump: (String::new(), None),
+			inventory: Inventory::new(),
+			batch: None,
+			split_settings: SplitRadialSettings::default(),
+			histogram: Histogram::new(),
+			autosave_interval: DEFAULT_AUTOSAVE_INTERVAL,
+			last_autosave: web_time::Instant::now(),
+			changes: 0,
+			autosaved: 0,
+			saved: None,
 			source_location: DEFAULT_LOCATION.into(),
 			world_offset,
+			rng: StdRng::from_entropy(),
+			provenance: Provenance {
+				version: env!("CARGO_PKG_VERSION").into(),
+				..Default::default()
+			},
+			leveling: None,
+			alignments: HashMap::new(),
+			sources: Sources::default(),
+			units: Units::Metric,
+			coordinate_format: CoordinateFormat::Degrees,
 		};
 
 		(interactive, receiver)
 	}
 
+	/// Random unused segment index.
+	fn new_index(&mut self) -> u32 {
+		loop {
+			let idx = self.rng.gen();
+			if idx != DELETED_INDEX && self.segments.contains_key(&idx).not() {
+				return idx;
+			}
+		}
+	}
+
 	/// Load a Interactive from a file.
 	pub fn load(
 		source: environment::Source,
-	) -> Result<(Self, crossbeam::channel::Receiver<Event>), Error> {
+	) -> Result<(Self, crossbeam::channel::Receiver<Event>, SessionSave), Error> {
 		let (sender, receiver) = crossbeam::channel::unbounded();
 
-		let reader = source.reader();
-		let save = bincode::deserialize_from::<_, InteractiveSave>(reader)?;
+		let (save, session) = InteractiveSave::read(source.reader()?)?;
 
 		let mut segments = HashMap::new();
 		for (idx, data) in save.segments {
@@ -288,58 +600,189 @@ impl Interactive {
 			sender,
 			deleted: save.deleted,
 			draw_radius: 0.5,
+			calculation_settings: CalculationSettings::default(),
+			outlier_settings: OutlierSettings::default(),
+			georeferenced: false,
 			show_deleted: false,
+			hovered: None,
+			brush: None,
+			color_modus: ColorModus::Segment,
+			jump: (String::new(), None),
+			inventory: Inventory::new(),
+			batch: None,
+			split_settings: SplitRadialSettings::default(),
+			histogram: Histogram::new(),
+			autosave_interval: DEFAULT_AUTOSAVE_INTERVAL,
+			last_autosave: web_time::Instant::now(),
+			changes: 0,
+			autosaved: 0,
+			saved: None,
 			source_location: save.source_location,
 			world_offset: save.world_offset,
+			rng: StdRng::from_entropy(),
+			provenance: session.provenance.clone(),
+			leveling: session.leveling,
+			alignments: session.alignments.clone(),
+			sources: session.sources.clone(),
+			units: Units::Metric,
+			coordinate_format: CoordinateFormat::Degrees,
 		};
 
-		Ok((interactive, receiver))
+		Ok((interactive, receiver, session))
+	}
+
+	/// Data for the segments.
+	fn save(&self) -> InteractiveSave {
+		InteractiveSave {
+			segments: self.segments.clone(),
+			deleted: self.deleted.clone(),
+			world_offset: self.world_offset,
+			source_location: self.source_location.clone(),
+		}
+	}
+
+	/// Current working context.
+	fn session(&self, camera: &Camera, viewer: ViewerSettings) -> SessionSave {
+		SessionSave {
+			camera: Some(camera.save()),
+			viewer: Some(viewer),
+			view: match &self.modus {
+				Modus::View(view) => Some(view.idx),
+				_ => None,
+			},
+			provenance: Provenance {
+				calculation: self.calculation_settings,
+				..self.provenance.clone()
+			},
+			leveling: self.leveling,
+			alignments: self
+				.alignments
+				.iter()
+				.filter(|(idx, _)| self.segments.contains_key(idx))
+				.map(|(&idx, &transform)| (idx, transform))
+				.collect(),
+			sources: self
+				.sources
+				.filtered(|idx| self.segments.contains_key(&idx)),
+		}
+	}
+
+	/// Count an edit to the segments.
+	pub fn modified(&mut self) {
+		self.changes += 1;
+	}
+
+	/// An explicit save with `changes` finished.
+	pub fn saved(&mut self, changes: u64) {
+		self.saved = Some(self.saved.map_or(changes, |saved| saved.max(changes)));
+	}
+
+	/// All edits are in an explicit save, the autosave is not needed anymore.
+	pub fn is_saved(&self) -> bool {
+		self.saved == Some(self.changes)
+	}
+
+	/// Write the autosave if segments changed and the interval passed.
+	///
+	/// The segments are shared with the background save, edits copy them.
+	pub fn autosave(&mut self, camera: &Camera, viewer: ViewerSettings) {
+		if self.autosave_interval == 0
+			|| self.changes == self.autosaved
+			|| self.last_autosave.elapsed().as_secs() < self.autosave_interval as u64 * 60
+		{
+			return;
+		}
+		self.last_autosave = web_time::Instant::now();
+		self.autosaved = self.changes;
+		let save = self.save();
+		let session = self.session(camera, viewer);
+		environment::autosave(move |mut saver| {
+			if let Err(err) = save.write(&session, saver.inner()) {
+				log::warn!("Failed to autosave: {}", err);
+				return;
+			}
+			saver.save();
+		});
 	}
 
 	/// Add the segments from another saved Interactive
+	///
+	/// The points are moved into the coordinates of this Interactive, including the leveling of
+	/// both sessions.
 	pub fn add(&mut self, source: environment::Source) -> Result<(), Error> {
-		let reader = source.reader();
-		let mut save = bincode::deserialize_from::<_, InteractiveSave>(reader)?;
+		let (mut save, session) = InteractiveSave::read(source.reader()?)?;
 
 		let diff = save.world_offset - self.world_offset;
 		let diff = (diff.norm_squared() > 0.1).then_some(diff);
+		let leveling = self.leveling;
+		let transform = |p: na::Point3<f32>| {
+			let p = session
+				.leveling
+				.map_or(p, |r| r.inverse_transform_point(&p));
+			let p = diff.map_or(p, |diff| (p.cast::<f64>() + diff).cast::<f32>());
+			leveling.map_or(p, |r| r * p)
+		};
 
-		if let Some(diff) = diff {
+		if diff.is_some() || session.leveling != leveling {
 			for segment in save.segments.values_mut() {
-				for p in segment.points.iter_mut() {
-					*p = (p.cast::<f64>() + diff).cast::<f32>();
+				for p in Arc::make_mut(segment).points.iter_mut() {
+					*p = transform(*p);
 				}
 			}
 		}
+		// same transform as `transform`, to move the alignments into these coordinates
+		let change =
+			na::Isometry3::from_parts(na::Translation3::identity(), leveling.unwrap_or_default())
+				* na::Isometry3::translation(
+					diff.map_or(0.0, |diff| diff.x as f32),
+					diff.map_or(0.0, |diff| diff.y as f32),
+					diff.map_or(0.0, |diff| diff.z as f32),
+				) * na::Isometry3::from_parts(
+				na::Translation3::identity(),
+				session.leveling.unwrap_or_default().inverse(),
+			);
 
+		let mut indices = HashMap::new();
 		for (mut idx, mut segment) in save.segments {
-			while self.segments.contains_key(&idx) {
-				idx = rand::random();
+			let previous = idx;
+			let alignment = session.alignments.get(&idx).copied();
+			if self.segments.contains_key(&idx) {
+				idx = self.new_index();
 			}
-			segment.changed(idx, &self.sender);
+			indices.insert(previous, idx);
+			if let Some(alignment) = alignment {
+				self.alignments
+					.insert(idx, change * alignment * change.inverse());
+			}
+			Arc::make_mut(&mut segment).changed(idx, &self.sender);
 			self.segments.insert(idx, segment);
 		}
 
-		for (mut p, c) in save
+		let deleted = Arc::make_mut(&mut self.deleted);
+		for (&p, &c) in save
 			.deleted
 			.points
-			.into_iter()
-			.zip(save.deleted.classifications)
+			.iter()
+			.zip(&save.deleted.classifications)
 		{
-			if let Some(diff) = diff {
-				p = (p.cast::<f64>() + diff).cast::<f32>();
-			}
-
-			self.deleted.points.push(p);
-			self.deleted.classifications.push(c);
+			deleted.points.push(transform(p));
+			deleted.classifications.push(c);
 		}
+		deleted.changed(DELETED_INDEX, &self.sender);
+		self.sources.add(source.name(), session.sources, &indices);
+		self.modified();
 
 		Ok(())
 	}
 
-	pub fn add_points(&mut self, source: environment::Source) -> Result<(), Error> {
-		let laz = laz::Laz::new(source, Some(self.world_offset))?;
+	pub fn add_points(
+		&mut self,
+		source: environment::Source,
+		convention: laz::Convention,
+	) -> Result<(), Error> {
+		let laz = laz::Laz::new(source, Some(self.world_offset), convention)?;
 		let size = laz.total();
+		let leveling = self.leveling;
 		let (sender, reciever) = crossbeam::channel::unbounded();
 		let (points, laz) = rayon::join(
 			move || {
@@ -352,7 +795,7 @@ impl Interactive {
 			move || {
 				laz.read(|chunk| {
 					for p in chunk {
-						sender.send(p).unwrap();
+						sender.send(leveling.map_or(p, |r| r * p)).unwrap();
 					}
 					Ok(())
 				})
@@ -361,17 +804,21 @@ impl Interactive {
 		let () = laz?;
 
 		let mut segment = SegmentData::new(points);
-		let mut idx = rand::random();
-		while self.segments.contains_key(&idx) {
-			idx = rand::random();
-		}
+		let idx = self.new_index();
 		segment.changed(idx, &self.sender);
-		self.segments.insert(idx, segment);
+		self.segments.insert(idx, Arc::new(segment));
+		self.modified();
 		Ok(())
 	}
 
 	/// Draw the UI
-	pub fn ui(&mut self, ui: &mut egui::Ui) {
+	pub fn ui(
+		&mut self,
+		ui: &mut egui::Ui,
+		camera: &mut Camera,
+		viewer: ViewerSettings,
+		state: &render::State,
+	) {
 		let enabled = matches!(self.modus, Modus::View(_)).not();
 
 		ui.add_enabled_ui(enabled, |ui| {
@@ -379,19 +826,13 @@ impl Interactive {
 				.add_sized([ui.available_width(), 0.0], egui::Button::new("Save"))
 				.clicked()
 			{
-				let mut segments = HashMap::new();
-				for (&idx, segment) in self.segments.iter() {
-					segments.insert(idx, segment.clone());
-				}
-				let save = InteractiveSave {
-					segments,
-					deleted: self.deleted.clone(),
-					world_offset: self.world_offset,
-					source_location: self.source_location.clone(),
-				};
+				let save = self.save();
+				let session = self.session(camera, viewer);
+				let (sender, changes) = (self.sender.clone(), self.changes);
 				environment::Saver::start("pointcloud", "ipc", move |mut saver| {
-					bincode::serialize_into(saver.inner(), &save).unwrap();
+					save.write(&session, saver.inner()).unwrap();
 					saver.save();
+					_ = sender.send(Event::Saved(changes));
 				});
 			}
 			if ui
@@ -400,6 +841,88 @@ impl Interactive {
 			{
 				environment::Source::start(&self.sender);
 			}
+			if ui
+				.add_sized([ui.available_width(), 0.0], egui::Button::new("Inventory"))
+				.clicked()
+			{
+				self.inventory.open = self.inventory.open.not();
+			}
+
+			ui.separator();
+			ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Process All"));
+			ui.add_enabled_ui(self.batch.is_none(), |ui| {
+				ui.columns(2, |columns| {
+					for (ui, (csv, name)) in
+						columns.iter_mut().zip([(true, "CSV"), (false, "JSON")])
+					{
+						if ui
+							.add_sized([ui.available_width(), 0.0], egui::Button::new(name))
+							.on_hover_text(
+								"Calculate the traits for all segments, with the split radial bounding volume of the last segment view",
+							)
+							.clicked()
+						{
+							self.batch = Some(Batch::start(
+								self.segments.clone(),
+								self.world_offset,
+								self.source_location.clone(),
+								self.calculation_settings,
+								self.split_settings,
+								csv,
+							));
+						}
+					}
+				});
+				if ui
+					.add_sized([ui.available_width(), 0.0], egui::Button::new("Map"))
+					.on_hover_text("Top-down map of the trunk positions and diameters")
+					.clicked()
+				{
+					let segments = self.segments.clone();
+					let settings = self.calculation_settings;
+					environment::Saver::start("map", "svg", move |mut saver| {
+						map::write_map(saver.inner(), segments, &settings).unwrap();
+						saver.save();
+					});
+				}
+				if ui
+					.add_sized([ui.available_width(), 0.0], egui::Button::new("Cloud"))
+					.on_hover_text("All points with the segment id and classification")
+					.clicked()
+				{
+					let segments = self.segments.clone();
+					let georeference = Georeference {
+						offset: self.world_offset,
+						location: self.source_location.clone(),
+						enabled: self.georeferenced,
+						leveling: self.leveling,
+					};
+					environment::Saver::start("cloud", "ply", move |mut saver| {
+						save_segments(&mut saver, &segments, &georeference).unwrap();
+						saver.save();
+					});
+				}
+				if ui
+					.add_sized([ui.available_width(), 0.0], egui::Button::new("NumPy"))
+					.on_hover_text("All points with the segment id and classification as .npy")
+					.clicked()
+				{
+					let segments = self.segments.clone();
+					environment::Saver::start("cloud", "npy", move |mut saver| {
+						npy::write_npy(saver.inner(), &segments).unwrap();
+						saver.save();
+					});
+				}
+			});
+			if let Some(batch) = &mut self.batch {
+				if batch.ui(ui).not() {
+					self.batch = None;
+				}
+			}
+
+			if let Some((project, offset)) = self.sources.ui(ui, self.hovered) {
+				self.move_project(project, offset);
+			}
 
 			ui.separator();
 			ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Modus"));
@@ -430,6 +953,33 @@ impl Interactive {
 			{
 				self.modus = Modus::SelectCombine;
 			}
+			if ui
+				.add(egui::RadioButton::new(
+					matches!(self.modus, Modus::MultiCombine(_)),
+					"Merge",
+				))
+				.clicked()
+			{
+				self.modus = Modus::MultiCombine(HashSet::new());
+			}
+			if ui
+				.add(egui::RadioButton::new(
+					matches!(self.modus, Modus::SelectSplit | Modus::Split(..)),
+					"Split",
+				))
+				.clicked()
+			{
+				self.modus = Modus::SelectSplit;
+			}
+			if ui
+				.add(egui::RadioButton::new(
+					matches!(self.modus, Modus::Level(..)),
+					"Level",
+				))
+				.clicked()
+			{
+				self.modus = Modus::Level(Vec::new(), None);
+			}
 			if ui
 				.add(egui::RadioButton::new(
 					matches!(self.modus, Modus::Spawn),
@@ -448,6 +998,79 @@ impl Interactive {
 			{
 				self.modus = Modus::Delete;
 			}
+
+			ui.separator();
+			ui.add_sized(
+				[ui.available_width(), 0.0],
+				egui::Label::new("Jump to Segment"),
+			);
+			ui.horizontal(|ui| {
+				let (input, message) = &mut self.jump;
+				let response = ui.add(
+					egui::TextEdit::singleline(input)
+						.hint_text("Id")
+						.desired_width(ui.available_width() - 50.0),
+				);
+				let submit = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+				if ui.button("Open").clicked() || submit {
+					let idx = input
+						.trim()
+						.parse::<u32>()
+						.ok()
+						.filter(|idx| self.segments.contains_key(idx));
+					*message = match idx {
+						Some(_) => None,
+						None => Some(format!("No segment with id '{}'", input.trim())),
+					};
+					if let Some(idx) = idx {
+						self.focus(idx, camera, state);
+					}
+				}
+			});
+			if let Some(message) = &self.jump.1 {
+				ui.add_sized([ui.available_width(), 0.0], egui::Label::new(message));
+			}
+
+			if let Modus::Split(..) = self.modus {
+				ui.separator();
+				ui.add_sized(
+					[ui.available_width(), 0.0],
+					egui::Label::new("Select second point"),
+				);
+			}
+
+			if let Modus::Level(picked, correction) = &self.modus {
+				ui.separator();
+				ui.add_sized(
+					[ui.available_width(), 0.0],
+					egui::Label::new(format!("Select ground point {} of 3", picked.len() + 1)),
+				);
+				if let Some(correction) = correction {
+					ui.add_sized(
+						[ui.available_width(), 0.0],
+						egui::Label::new(format!("Corrected tilt by {:.2}°", correction)),
+					);
+				}
+			}
+
+			if let Modus::MultiCombine(selected) = &mut self.modus {
+				ui.separator();
+				ui.add_sized(
+					[ui.available_width(), 0.0],
+					egui::Label::new(format!("{} Selected", selected.len())),
+				);
+				if ui
+					.add_enabled(
+						selected.len() >= 2,
+						egui::Button::new("Merge Selected")
+							.min_size([ui.available_width(), 0.0].into()),
+					)
+					.clicked()
+				{
+					let selected = std::mem::take(selected);
+					self.merge(selected);
+				}
+			}
 		});
 
 		ui.separator();
@@ -464,7 +1087,38 @@ impl Interactive {
 
 			ui.label("Deleted");
 			ui.checkbox(&mut self.show_deleted, "Show");
-			ui.end_row()
+			ui.end_row();
+
+			ui.label("Autosave");
+			ui.add(
+				egui::Slider::new(&mut self.autosave_interval, 0..=30)
+					.suffix("min")
+					.custom_formatter(|value, _| match value as u32 {
+						0 => "Off".into(),
+						value => format!("{}", value),
+					}),
+			);
+			ui.end_row();
+
+			ui.label("Units");
+			self.units.ui(ui);
+			ui.end_row();
+
+			ui.label("Color");
+			egui::ComboBox::from_id_salt(id!())
+				.selected_text(self.color_modus.name())
+				.width(ui.available_width())
+				.show_ui(ui, |ui| {
+					for v in [
+						ColorModus::Segment,
+						ColorModus::Height,
+						ColorModus::TrunkHeight,
+						ColorModus::CrownHeight,
+					] {
+						ui.selectable_value(&mut self.color_modus, v, v.name());
+					}
+				});
+			ui.end_row();
 		});
 
 		{
@@ -478,21 +1132,58 @@ impl Interactive {
 				.lost_focus()
 			{
 				if let Modus::View(ref view) = self.modus {
-					let seg = self.segments.get_mut(&view.idx).unwrap();
+					let seg = Arc::make_mut(self.segments.get_mut(&view.idx).unwrap());
 					match proj4rs::Proj::from_proj_string(&self.source_location) {
 						Ok(proj) => seg.update_location(self.world_offset, &proj),
 						Err(err) => eprintln!("{}", err),
 					}
 				}
+				self.modified();
 			};
 		}
+
+		ui.separator();
+		ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Provenance"));
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			let unknown = || "Unknown".to_string();
+			ui.label("Version");
+			ui.label(if self.provenance.version.is_empty() {
+				unknown()
+			} else {
+				self.provenance.version.clone()
+			});
+			ui.end_row();
+
+			let segmentation = self.provenance.segmentation;
+			ui.label("Distance");
+			ui.label(segmentation.map_or_else(unknown, |s| self.units.length(s.max_distance)));
+			ui.end_row();
+
+			ui.label("Seed");
+			ui.label(segmentation.map_or_else(unknown, |s| format!("{}", s.seed)));
+			ui.end_row();
+
+			ui.label("Leveled");
+			ui.label(match self.leveling {
+				Some(rotation) => format!("{:.2}°", rotation.angle().to_degrees()),
+				None => "No".into(),
+			});
+			ui.end_row();
+		});
 	}
 
-	pub fn extra_ui(&mut self, ctx: &egui::Context, state: &render::State) {
+	pub fn extra_ui(
+		&mut self,
+		ctx: &egui::Context,
+		display_settings: &mut DisplaySettings,
+		state: &render::State,
+	) {
 		let Modus::View(view) = &mut self.modus else {
 			return;
 		};
 		let mut close_view = false;
+		let mut align_action = None;
+		let mut compare_action = None;
 
 		egui::SidePanel::right("extra-panel")
 			.resizable(false)
@@ -501,15 +1192,23 @@ impl Interactive {
 				egui::ScrollArea::vertical().show(ui, |ui| {
 					ui.add_sized(
 						[ui.available_width(), 0.0],
-						egui::Label::new(egui::RichText::new("Segment").heading()),
+						egui::Label::new(
+							egui::RichText::new(format!("Segment {}", view.idx)).heading(),
+						),
 					);
+					if let Some(name) = self.sources.name(view.idx) {
+						ui.add_sized([ui.available_width(), 0.0], egui::Label::new(name));
+					}
 					close_view = ui
 						.add_sized([ui.available_width(), 0.0], egui::Button::new("Return"))
 						.clicked();
 
 					ui.separator();
 
-					let segment = self.segments.get_mut(&view.idx).unwrap();
+					let segment = &self.segments[&view.idx];
+
+					view.picked_ui(ui, segment, self.world_offset, self.units);
+					ui.separator();
 
 					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Edit Points"));
 					ui.radio_value(&mut view.modus, ViewModus::Delete, "Delete");
@@ -517,6 +1216,62 @@ impl Interactive {
 					ui.radio_value(&mut view.modus, ViewModus::Trunk, "Trunk");
 					ui.radio_value(&mut view.modus, ViewModus::Ground, "Ground");
 
+					ui.separator();
+					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Outliers"));
+					self.outlier_settings.ui(ui);
+					let (clean, mark) = ui.columns(2, |columns| {
+						(
+							columns[0]
+								.add_sized(
+									[columns[0].available_width(), 0.0],
+									egui::Button::new("Clean"),
+								)
+								.on_hover_text("Delete the outliers")
+								.clicked(),
+							columns[1]
+								.add_sized(
+									[columns[1].available_width(), 0.0],
+									egui::Button::new("Mark"),
+								)
+								.on_hover_text("Keep the outliers and classify them as noise")
+								.clicked(),
+						)
+					});
+					if clean {
+						let segment = Arc::make_mut(self.segments.get_mut(&view.idx).unwrap());
+						let deleted = Arc::make_mut(&mut self.deleted);
+						let removed = segment.remove_outliers(&self.outlier_settings, deleted);
+						if removed > 0 {
+							deleted.changed(DELETED_INDEX, &self.sender);
+							view.segment_changed(
+								segment,
+								&self.calculation_settings,
+								&self.sender,
+								state,
+							);
+							self.changes += 1;
+						}
+						view.outliers = Some(format!("Removed {} points", removed));
+					}
+					if mark {
+						let segment = Arc::make_mut(self.segments.get_mut(&view.idx).unwrap());
+						let marked = segment.mark_outliers(&self.outlier_settings);
+						if marked > 0 {
+							view.segment_changed(
+								segment,
+								&self.calculation_settings,
+								&self.sender,
+								state,
+							);
+							self.changes += 1;
+						}
+						view.outliers = Some(format!("Marked {} points as noise", marked));
+					}
+					let segment = &self.segments[&view.idx];
+					if let Some(outliers) = &view.outliers {
+						ui.add_sized([ui.available_width(), 0.0], egui::Label::new(outliers));
+					}
+
 					ui.separator();
 					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Display"));
 					ui.radio_value(
@@ -531,23 +1286,200 @@ impl Interactive {
 						"Expansion",
 					);
 					ui.radio_value(&mut view.display_modus, DisplayModus::Height, "Height");
+					if view.compare.result.is_some() {
+						ui.radio_value(&mut view.display_modus, DisplayModus::Distance, "Distance");
+					}
 
-					ui.separator();
-					if ui
-						.add_sized(
-							[ui.available_width(), 0.0],
-							egui::Button::new("Update Curvature"),
-						)
+					if view.display_modus == DisplayModus::Classification {
+						display_settings.classes_ui(ui, state);
+					}
+
+					if let Some(range) = view.ranges.get_mut(view.display_modus) {
+						let previous = *range;
+						egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+							ui.label("Min");
+							ui.add(egui::Slider::new(&mut range.0, 0.0..=1.0).max_decimals(2));
+							ui.end_row();
+
+							ui.label("Max");
+							ui.add(egui::Slider::new(&mut range.1, 0.0..=1.0).max_decimals(2));
+							ui.end_row();
+						});
+						ui.columns(2, |ui| {
+							if ui[0]
+								.add_sized(
+									[ui[0].available_width(), 0.0],
+									egui::Button::new("Auto"),
+								)
+								.clicked()
+							{
+								let mut percentiles = view.display_data.percentiles;
+								*range = *percentiles.get_mut(view.display_modus).unwrap();
+							}
+							if ui[1]
+								.add_sized(
+									[ui[1].available_width(), 0.0],
+									egui::Button::new("Full"),
+								)
+								.clicked()
+							{
+								*range = (0.0, 1.0);
+							}
+						});
+						range.1 = range.1.max(range.0);
+						if *range != previous {
+							view.display_data.update(
+								state,
+								segment,
+								&view.calculations_properties,
+								&view.ranges,
+							);
+						}
+					}
+
+					ui.separator();
+					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Histogram"));
+					let properties = &view.calculations_properties;
+					let key = (view.idx, view.display_modus, view.display_data.revision);
+					match view.display_modus {
+						DisplayModus::Classification => self.histogram.ui(ui, key, || {
+							segment.classifications.iter().map(|c| match c {
+								Classification::Ground => 1.0 / 8.0,
+								Classification::Trunk => 3.0 / 8.0,
+								Classification::Crown => 6.0 / 8.0,
+								Classification::Noise => 1.0,
+							})
+						}),
+						DisplayModus::Curve => self
+							.histogram
+							.ui(ui, key, || properties.curve.iter().copied()),
+						DisplayModus::Expansion => self.histogram.ui(ui, key, || {
+							let max = properties
+								.expansion
+								.iter()
+								.copied()
+								.max_by(|a, b| a.total_cmp(b))
+								.unwrap_or_default();
+							properties.expansion.iter().map(move |e| e / max)
+						}),
+						DisplayModus::Height => self
+							.histogram
+							.ui(ui, key, || properties.height.iter().copied()),
+						DisplayModus::Distance => match &view.compare.result {
+							Some(result) => self.histogram.ui(ui, key, || result.normalized()),
+							None => self.histogram.ui(ui, key, std::iter::empty),
+						},
+					}
+
+					ui.separator();
+					if ui
+						.add_sized(
+							[ui.available_width(), 0.0],
+							egui::Button::new("Update Curvature"),
+						)
 						.clicked()
 					{
-						view.calculations_properties = segment.update_info(true);
-						view.display_data =
-							DisplayData::new(state, segment, &view.calculations_properties);
+						let segment = Arc::make_mut(self.segments.get_mut(&view.idx).unwrap());
+						view.calculations_properties =
+							segment.update_info(Some(&view.neighbors), &self.calculation_settings);
+						view.display_data.update(
+							state,
+							segment,
+							&view.calculations_properties,
+							&view.ranges,
+						);
 					}
 
 					ui.separator();
-					view.hull
-						.ui(ui, segment, view.trunk_axis.transform(), state);
+					ui.add_sized(
+						[ui.available_width(), 0.0],
+						egui::Label::new("Calculations"),
+					);
+					let settings = &mut self.calculation_settings;
+					let mut changed = false;
+					egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+						ui.label("Diameter Height");
+						changed |= ui
+							.add(
+								egui::Slider::new(&mut settings.trunk_diameter_height, 0.5..=3.0)
+									.suffix("m"),
+							)
+							.changed();
+						ui.end_row();
+
+						ui.label("Slice Width");
+						changed |= ui
+							.add(
+								egui::Slider::new(&mut settings.slice_width, 0.05..=0.5)
+									.suffix("m"),
+							)
+							.changed();
+						ui.end_row();
+
+						ui.label("Neighbors");
+						changed |= ui
+							.add(egui::Slider::new(&mut settings.neighbors_count, 4..=64))
+							.changed();
+						ui.end_row();
+
+						ui.label("Search");
+						changed |= ui
+							.checkbox(&mut settings.approximate_neighbors, "Approximate")
+							.changed();
+						ui.end_row();
+
+						ui.label("Diameter");
+						egui::ComboBox::from_id_salt(id!())
+							.selected_text(format!("{:?}", settings.trunk_diameter_method))
+							.width(ui.available_width())
+							.show_ui(ui, |ui| {
+								for v in [
+									TrunkDiameterMethod::ConvexArea,
+									TrunkDiameterMethod::CircleFit,
+								] {
+									changed |= ui
+										.selectable_value(
+											&mut settings.trunk_diameter_method,
+											v,
+											format!("{:?}", v),
+										)
+										.changed();
+								}
+							});
+						ui.end_row();
+					});
+					if changed {
+						let segment = Arc::make_mut(self.segments.get_mut(&view.idx).unwrap());
+						view.calculations_properties =
+							segment.update_info(Some(&view.neighbors), settings);
+						view.display_data.update(
+							state,
+							segment,
+							&view.calculations_properties,
+							&view.ranges,
+						);
+					}
+					let segment = &self.segments[&view.idx];
+
+					let georeference = Georeference {
+						offset: self.world_offset,
+						location: self.source_location.clone(),
+						enabled: self.georeferenced,
+						leveling: self.leveling,
+					};
+
+					ui.separator();
+					view.hull.ui(
+						ui,
+						segment,
+						view.trunk_axis.transform(),
+						&self.calculation_settings,
+						&georeference,
+						state,
+					);
+					if let Some(split) = view.hull.split_settings() {
+						self.split_settings = split;
+					}
 
 					ui.separator();
 					if view.trunk_axis.ui(ui, segment, state) {
@@ -555,34 +1487,95 @@ impl Interactive {
 							.update(segment, view.trunk_axis.transform(), state);
 					}
 
+					ui.separator();
+					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Centerline"));
+					if let Some(format) = view.centerline.ui(
+						ui,
+						segment,
+						self.calculation_settings.slice_width,
+						self.units,
+						state,
+					) {
+						let points = centerline::centerline(
+							&segment.points,
+							&segment.classifications,
+							self.calculation_settings.slice_width,
+						);
+						let georeference = georeference.clone();
+						let extension = match format {
+							CenterlineFormat::Csv => "csv",
+							CenterlineFormat::Ply => "ply",
+						};
+						environment::Saver::start("centerline", extension, move |mut saver| {
+							centerline::save_centerline(&mut saver, &points, format, &georeference)
+								.unwrap();
+							saver.save();
+						});
+					}
+
 					ui.separator();
 					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Heights"));
 					egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
 						ui.label("Crown");
-						ui.label(format!("{:.2}m", segment.info.crown_height));
+						ui.label(self.units.length(segment.info.crown_height));
 						ui.end_row();
 
 						ui.label("Trunk");
-						ui.label(format!("{:.2}m", segment.info.trunk_height));
+						ui.label(self.units.length(segment.info.trunk_height));
+						ui.end_row();
+
+						ui.label("Diameter");
+						ui.label(
+							self.units
+								.diameter(view.calculations_properties.trunk_diameter),
+						);
+						ui.end_row();
+
+						ui.label("Lean");
+						ui.label(format!("{:.1}°", view.calculations_properties.stem.lean));
+						ui.end_row();
+
+						ui.label("Sweep");
+						ui.label(self.units.length(view.calculations_properties.stem.sweep))
+							.on_hover_text("Largest distance of the trunk center from the axis");
 						ui.end_row();
 					});
 
-					if let Some((long, lat)) = segment.coords {
-						ui.separator();
-						ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Coordinates"));
-						egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
-							ui.label("Lat");
-							ui.label(format_degrees(lat));
-							ui.end_row();
+					ui.separator();
+					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Coordinates"));
+					let raw = (
+						self.world_offset.x + ((segment.min.x + segment.max.x) / 2.0) as f64,
+						-(self.world_offset.z + ((segment.min.z + segment.max.z) / 2.0) as f64),
+					);
+					egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+						ui.label("Format");
+						self.coordinate_format.ui(ui);
+						ui.end_row();
 
-							ui.label("Long");
-							ui.label(format_degrees(long));
+						for (label, value) in self.coordinate_format.rows(segment.coords, raw) {
+							ui.label(label);
+							ui.label(value);
 							ui.end_row();
-						});
-					}
+						}
+					});
+
+					ui.separator();
+					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Align"));
+					let segment = &self.segments[&view.idx];
+					align_action = view.align.ui(
+						ui,
+						na::center(&segment.min, &segment.max),
+						self.alignments.get(&view.idx),
+						self.units,
+					);
+
+					ui.separator();
+					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Compare"));
+					compare_action = view.compare.ui(ui, self.units);
 
 					ui.separator();
 					ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Save"));
+					ui.checkbox(&mut self.georeferenced, "Georeferenced");
 					if ui
 						.add_sized([ui.available_width(), 0.0], egui::Button::new("Points"))
 						.clicked()
@@ -591,12 +1584,17 @@ impl Interactive {
 						let points = seg.points.clone();
 						let classifications = seg.classifications.clone();
 						let calculations_properties = view.calculations_properties.clone();
+						let georeference = georeference.clone();
+						let settings = self.calculation_settings;
 						environment::Saver::start("points", "ply", move |mut saver| {
+							let normals = point_normals(&points, &settings);
 							save_points(
 								&mut saver,
 								&points,
 								&classifications,
 								&calculations_properties,
+								&normals,
+								&georeference,
 								|_| true,
 							)
 							.unwrap();
@@ -618,6 +1616,8 @@ impl Interactive {
 							offset: self.world_offset,
 							longitude: seg.coords.map(|c| c.0.to_degrees()),
 							latitude: seg.coords.map(|c| c.1.to_degrees()),
+							stem: view.calculations_properties.stem,
+							trunk_axis: view.trunk_axis.save(),
 						};
 						environment::Saver::start("segment", "json", move |mut saver| {
 							serde_json::to_writer_pretty(saver.inner(), &save).unwrap();
@@ -638,12 +1638,17 @@ impl Interactive {
 							let points = seg.points.clone();
 							let classifications = seg.classifications.clone();
 							let calculations_properties = view.calculations_properties.clone();
+							let georeference = georeference.clone();
+							let settings = self.calculation_settings;
 							environment::Saver::start(file, "ply", move |mut saver| {
+								let normals = point_normals(&points, &settings);
 								save_points(
 									&mut saver,
 									&points,
 									&classifications,
 									&calculations_properties,
+									&normals,
+									&georeference,
 									|c| c == classification,
 								)
 								.unwrap();
@@ -660,22 +1665,154 @@ impl Interactive {
 						segments.insert(view.idx, seg.clone());
 						let save = InteractiveSave {
 							segments,
-							deleted: SegmentData::new(Vec::new()),
+							deleted: Arc::new(SegmentData::new(Vec::new())),
 							world_offset: self.world_offset,
 							source_location: self.source_location.clone(),
 						};
 						environment::Saver::start("segment", "ipc", move |mut saver| {
-							bincode::serialize_into(saver.inner(), &save).unwrap();
+							save.write(&SessionSave::default(), saver.inner()).unwrap();
+							saver.save();
+						})
+					}
+					if ui
+						.add_sized([ui.available_width(), 0.0], egui::Button::new("NumPy"))
+						.clicked()
+					{
+						let seg = self.segments.get(&view.idx).unwrap();
+						let segments = HashMap::from([(view.idx, seg.clone())]);
+						environment::Saver::start("segment", "npy", move |mut saver| {
+							npy::write_npy(saver.inner(), &segments).unwrap();
 							saver.save();
 						})
 					}
 				});
 			});
+		if let Some(action) = align_action {
+			self.align(action, state);
+		}
+		if let Some(action) = compare_action {
+			self.compare(action, state);
+		}
 		if close_view {
 			self.modus = Modus::SelectView;
 		}
 	}
 
+	/// Points of the other segments within `distance` of the bounds of the segment.
+	fn nearby_points(&self, idx: u32, distance: f32) -> Vec<na::Point3<f32>> {
+		let segment = &self.segments[&idx];
+		let expand = na::Vector3::repeat(distance);
+		let (min, max) = (segment.min - expand, segment.max + expand);
+		self.segments
+			.iter()
+			.filter(|(&other_idx, _)| other_idx != idx)
+			.filter(|(_, other)| {
+				(0..3).all(|dim| other.min[dim] <= max[dim] && other.max[dim] >= min[dim])
+			})
+			.flat_map(|(_, other)| other.points.iter().copied())
+			.filter(|p| (0..3).all(|dim| (min[dim]..=max[dim]).contains(&p[dim])))
+			.collect()
+	}
+
+	/// Compare the viewed segment with the other segments or save the difference.
+	fn compare(&mut self, action: CompareAction, state: &render::State) {
+		let Modus::View(view) = &self.modus else {
+			return;
+		};
+		let idx = view.idx;
+		match action {
+			CompareAction::Compare => {
+				let threshold = view.compare.threshold;
+				let reference = self.nearby_points(idx, threshold * compare::COLOR_RANGE);
+				let comparison =
+					Comparison::new(&self.segments[&idx].points, &reference, threshold, state);
+				let Modus::View(view) = &mut self.modus else {
+					unreachable!();
+				};
+				view.compare.result = Some(comparison);
+				view.display_modus = DisplayModus::Distance;
+				self.histogram.invalidate();
+			},
+			CompareAction::Export => {
+				let Some(comparison) = view.compare.result.as_ref() else {
+					return;
+				};
+				let points = self.segments[&idx].points.clone();
+				let difference = comparison.difference.clone();
+				let georeference = Georeference {
+					offset: self.world_offset,
+					location: self.source_location.clone(),
+					enabled: self.georeferenced,
+					leveling: self.leveling,
+				};
+				environment::Saver::start("difference", "ply", move |mut saver| {
+					compare::save_difference(&mut saver, &points, &difference, &georeference)
+						.unwrap();
+					saver.save();
+				});
+			},
+		}
+	}
+
+	/// Move the viewed segment by the transform or align it with the other segments.
+	fn align(&mut self, action: AlignAction, state: &render::State) {
+		let Modus::View(view) = &self.modus else {
+			return;
+		};
+		let (idx, max_distance) = (view.idx, view.align.max_distance);
+		let transform = match action {
+			AlignAction::Move(transform) => transform,
+			AlignAction::Auto => {
+				let target = self.nearby_points(idx, max_distance);
+				let result = align::icp(&self.segments[&idx].points, &target, max_distance);
+				let Modus::View(view) = &mut self.modus else {
+					unreachable!();
+				};
+				let Some((transform, mean)) = result else {
+					view.align.message = Some("No overlapping points".into());
+					return;
+				};
+				view.align.message = Some(format!(
+					"Moved {}, rotated {:.2}°, mean distance {}",
+					self.units.length(transform.translation.vector.norm()),
+					transform.rotation.angle().to_degrees(),
+					self.units.length(mean),
+				));
+				transform
+			},
+		};
+
+		let Modus::View(view) = &mut self.modus else {
+			return;
+		};
+		let total = self
+			.alignments
+			.entry(idx)
+			.or_insert_with(na::Isometry3::identity);
+		*total = transform * *total;
+		let segment = Arc::make_mut(self.segments.get_mut(&idx).unwrap());
+		for p in segment.points.iter_mut() {
+			*p = transform * *p;
+		}
+		view.segment_changed(segment, &self.calculation_settings, &self.sender, state);
+		self.modified();
+	}
+
+	/// Translate all segments of the project.
+	fn move_project(&mut self, project: usize, offset: na::Vector3<f32>) {
+		for (&idx, segment) in self.segments.iter_mut() {
+			if self.sources.project(idx) != project {
+				continue;
+			}
+			let segment = Arc::make_mut(segment);
+			for p in segment.points.iter_mut() {
+				*p += offset;
+			}
+			segment.changed(idx, &self.sender);
+		}
+		self.modified();
+	}
+
 	/// Get the first segment and distance hit by the ray.
 	fn select(
 		&self,
@@ -685,6 +1822,12 @@ impl Interactive {
 	) -> Option<(u32, f32)> {
 		let mut potential = Vec::new();
 		for (&idx, segment) in self.segments.iter() {
+			if display_settings.hidden(segment.points.len()) && self.is_active(idx).not() {
+				continue;
+			}
+			if self.sources.visible(idx).not() {
+				continue;
+			}
 			let Some(distance) = segment.raycast_distance(start, direction) else {
 				continue;
 			};
@@ -709,11 +1852,170 @@ impl Interactive {
 		best.map(|idx| (idx, distance))
 	}
 
+	/// Segment is hovered or used by the current modus.
+	pub fn is_active(&self, idx: u32) -> bool {
+		if self.hovered == Some(idx) {
+			return true;
+		}
+		match &self.modus {
+			Modus::Draw(active) | Modus::Combine(active) | Modus::Split(active, _) => {
+				*active == idx
+			},
+			Modus::MultiCombine(selected) => selected.contains(&idx),
+			Modus::View(view) => view.idx == idx,
+			_ => false,
+		}
+	}
+
+	/// Color value for every segment if the segments are colored by a trait.
+	/// The values are scaled to the full range between the smallest and largest trait.
+	/// Colored by segment, only the segments of tinted projects have a value.
+	pub fn segment_colors(&self) -> Option<HashMap<u32, u32>> {
+		if self.color_modus == ColorModus::Segment {
+			let tints = self
+				.segments
+				.keys()
+				.filter_map(|&idx| Some((idx, map_to_u32(self.sources.tint(idx)?))))
+				.collect::<HashMap<_, _>>();
+			return tints.is_empty().not().then_some(tints);
+		}
+		let values = self
+			.segments
+			.iter()
+			.map(|(&idx, segment)| Some((idx, self.color_modus.value(segment)?)))
+			.collect::<Option<Vec<_>>>()?;
+		let (min, max) = values
+			.iter()
+			.fold((f32::MAX, f32::MIN), |(min, max), &(_, v)| {
+				(min.min(v), max.max(v))
+			});
+		let range = (max - min).max(f32::EPSILON);
+		Some(
+			values
+				.into_iter()
+				.map(|(idx, v)| (idx, map_to_u32((v - min) / range)))
+				.collect(),
+		)
+	}
+
+	/// Update the hovered segment for modi which select a segment on click
+	/// and the brush for edit modi.
+	pub fn hover(
+		&mut self,
+		start: na::Point3<f32>,
+		direction: na::Vector3<f32>,
+		display_settings: &DisplaySettings,
+		state: &render::State,
+	) {
+		self.brush = self
+			.brush_hit(start, direction, display_settings)
+			.map(|hit| Brush::sphere(hit, self.draw_radius, state));
+		if let Modus::View(view) = &mut self.modus {
+			view.picked = self.segments[&view.idx]
+				.exact_hit(start, direction, display_settings)
+				.map(|(idx, _)| idx);
+		}
+		self.hovered = match self.modus {
+			Modus::SelectView
+			| Modus::SelectDraw
+			| Modus::Draw(_)
+			| Modus::SelectCombine
+			| Modus::Combine(_)
+			| Modus::MultiCombine(_)
+			| Modus::SelectSplit => self
+				.select(start, direction, display_settings)
+				.map(|(idx, _)| idx),
+			_ => None,
+		};
+	}
+
+	/// Merge all segments into the segment with the lowest index.
+	fn merge(&mut self, selected: HashSet<u32>) {
+		let mut selected = selected.into_iter().collect::<Vec<_>>();
+		selected.sort_unstable();
+		let Some((&idx, others)) = selected.split_first() else {
+			return;
+		};
+		let mut target = self.segments.remove(&idx).unwrap();
+		let data = Arc::make_mut(&mut target);
+		for other in others {
+			_ = self.sender.send(Event::RemovePointCloud(*other));
+			self.sources.remove(*other);
+			let other = self.segments.remove(other).unwrap();
+			data.points.extend_from_slice(&other.points);
+			data.classifications
+				.extend_from_slice(&other.classifications);
+		}
+		data.changed(idx, &self.sender);
+		self.segments.insert(idx, target);
+		self.modified();
+	}
+
+	/// Split the segment with a vertical plane through `first` and `second`.
+	fn split(&mut self, idx: u32, first: na::Point3<f32>, second: na::Point3<f32>) {
+		let line = na::vector![second.x - first.x, second.z - first.z];
+		if line.norm_squared() < 0.01 {
+			return;
+		}
+		let normal = na::vector![-line.y, line.x];
+		let side =
+			|p: na::Point3<f32>| na::vector![p.x - first.x, p.z - first.z].dot(&normal) > 0.0;
+
+		let segment = &self.segments[&idx];
+		let count = segment.points.iter().filter(|&&p| side(p)).count();
+		if count == 0 || count == segment.points.len() {
+			return;
+		}
+		let segment = Arc::make_mut(self.segments.get_mut(&idx).unwrap());
+		let mut other = SegmentData::new(Vec::new());
+		segment.remove_where(side, &mut other);
+		segment.changed(idx, &self.sender);
+
+		let other_idx = self.new_index();
+		self.sources.inherit(idx, other_idx);
+		other.changed(other_idx, &self.sender);
+		self.segments.insert(other_idx, Arc::new(other));
+		self.modified();
+	}
+
+	/// Rotate all points so the plane through the picked ground points is level.
+	/// Returns the tilt correction in degrees.
+	fn level(&mut self, picked: [na::Point3<f32>; 3]) -> Option<f32> {
+		let [a, b, c] = picked;
+		let mut normal = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON)?;
+		if normal.y < 0.0 {
+			normal = -normal;
+		}
+		let rotation = na::UnitQuaternion::rotation_between(&normal, &na::Vector3::y())?;
+
+		for (&idx, segment) in self.segments.iter_mut() {
+			let segment = Arc::make_mut(segment);
+			for p in segment.points.iter_mut() {
+				*p = rotation * *p;
+			}
+			segment.changed(idx, &self.sender);
+		}
+		let deleted = Arc::make_mut(&mut self.deleted);
+		for p in deleted.points.iter_mut() {
+			*p = rotation * *p;
+		}
+		deleted.changed(DELETED_INDEX, &self.sender);
+		self.modified();
+
+		self.leveling = Some(match self.leveling {
+			Some(previous) => rotation * previous,
+			None => rotation,
+		});
+		Some(rotation.angle().to_degrees())
+	}
+
 	/// Handle mouse click.
+	/// With `add` the clicked segment is added to the current selection.
 	pub fn click(
 		&mut self,
 		start: na::Point3<f32>,
 		direction: na::Vector3<f32>,
+		add: bool,
 		display_settings: &DisplaySettings,
 		state: &render::State,
 	) {
@@ -734,6 +2036,49 @@ impl Interactive {
 					Modus::SelectCombine
 				};
 			},
+			Modus::MultiCombine(_) => {
+				let hit = self.select(start, direction, display_settings);
+				let Modus::MultiCombine(selected) = &mut self.modus else {
+					unreachable!();
+				};
+				match hit {
+					Some((idx, _)) if add => {
+						if selected.remove(&idx).not() {
+							selected.insert(idx);
+						}
+					},
+					Some((idx, _)) => *selected = HashSet::from([idx]),
+					None if add => {},
+					None => selected.clear(),
+				}
+			},
+			Modus::SelectSplit => {
+				if let Some((idx, distance)) = self.select(start, direction, display_settings) {
+					self.modus = Modus::Split(idx, start + direction * distance);
+				}
+			},
+			&mut Modus::Split(idx, first) => {
+				// intersect with the horizontal plane through the first point
+				let t = (first.y - start.y) / direction.y;
+				if t.is_finite().not() || t <= 0.0 {
+					return;
+				}
+				self.split(idx, first, start + direction * t);
+				self.modus = Modus::SelectSplit;
+			},
+			Modus::Level(..) => {
+				let Some((_, distance)) = self.select(start, direction, display_settings) else {
+					return;
+				};
+				let Modus::Level(picked, _) = &mut self.modus else {
+					unreachable!();
+				};
+				picked.push(start + direction * distance);
+				if let Ok(points) = <[_; 3]>::try_from(picked.as_slice()) {
+					let correction = self.level(points);
+					self.modus = Modus::Level(Vec::new(), correction);
+				}
+			},
 			Modus::Spawn => {
 				let Some((_, distance)) = self.select(start, direction, display_settings) else {
 					return;
@@ -742,6 +2087,12 @@ impl Interactive {
 				let mut new_segment = SegmentData::new(Vec::new());
 				let mut empty = Vec::new();
 				for (&other, segment) in self.segments.iter_mut() {
+					if self.sources.visible(other).not()
+						|| segment.near(hit, self.draw_radius).not()
+					{
+						continue;
+					}
+					let segment = Arc::make_mut(segment);
 					let seg_changed = segment.remove(hit, self.draw_radius, &mut new_segment);
 					if segment.points.is_empty() {
 						empty.push(other);
@@ -757,13 +2108,11 @@ impl Interactive {
 					return;
 				}
 
-				let mut idx = rand::random();
-				while idx == DELETED_INDEX || self.segments.contains_key(&idx) {
-					idx = rand::random();
-				}
+				let idx = self.new_index();
 				new_segment.update_render(idx, &self.sender);
-				self.segments.insert(idx, new_segment);
+				self.segments.insert(idx, Arc::new(new_segment));
 				self.modus = Modus::Draw(idx);
+				self.modified();
 			},
 			Modus::Delete => {},
 
@@ -771,36 +2120,257 @@ impl Interactive {
 				let Some((idx, _)) = self.select(start, direction, display_settings) else {
 					return;
 				};
-				let seg = self.segments.get_mut(&idx).unwrap();
-				let calculations_properties = seg.update_info(true);
+				self.open_view(idx, state);
+			},
+
+			Modus::View { .. } => {},
+		}
+	}
+
+	/// Move the camera to a segment and open its view.
+	fn focus(&mut self, idx: u32, camera: &mut Camera, state: &render::State) {
+		let segment = &self.segments[&idx];
+		let center = na::center(&segment.min, &segment.max);
+		camera.focus(center, (segment.max - segment.min).norm() / 2.0, state);
+		self.open_view(idx, state);
+	}
+
+	/// Draw the inventory table and focus the clicked segment.
+	pub fn inventory_ui(
+		&mut self,
+		ctx: &egui::Context,
+		camera: &mut Camera,
+		state: &render::State,
+	) {
+		if let Some(idx) = self.inventory.ui(
+			ctx,
+			&mut self.segments,
+			&self.calculation_settings,
+			self.world_offset,
+			&self.source_location,
+			self.units,
+		) {
+			self.focus(idx, camera, state);
+		}
+	}
+
+	/// Send all segments again and reopen the view, after the device was lost.
+	pub fn recreate_render(&mut self, state: &render::State) {
+		for (&idx, segment) in self.segments.iter() {
+			segment.update_render(idx, &self.sender);
+		}
+		self.deleted.update_render(DELETED_INDEX, &self.sender);
+		self.brush = None;
+		if let Modus::View(view) = &self.modus {
+			let idx = view.idx;
+			self.open_view(idx, state);
+		}
+	}
+
+	/// Open the focused view for a segment.
+	pub fn open_view(&mut self, idx: u32, state: &render::State) {
+		self.histogram.invalidate();
+		let seg = Arc::make_mut(self.segments.get_mut(&idx).unwrap());
+		let neighbors = NeighborsTree::new(&seg.points);
+		let calculations_properties = seg.update_info(Some(&neighbors), &self.calculation_settings);
+
+		match proj4rs::Proj::from_proj_string(&self.source_location) {
+			Ok(proj) => seg.update_location(self.world_offset, &proj),
+			Err(err) => eprintln!("{}", err),
+		}
+
+		let ranges = DisplayRanges::default();
+		let display_data = DisplayData::new(state, seg, &calculations_properties, &ranges);
+		let trunk_axis = TrunkAxis::new(
+			&seg.points,
+			&seg.classifications,
+			TrunkAxisAlgorithm::None,
+			state,
+		);
+
+		self.modus = Modus::View(View {
+			idx,
+			hull: Hull::None,
+			display_modus: DisplayModus::Classification,
+			modus: ViewModus::Delete,
+			display_data,
+			calculations_properties,
+			cloud: render::PointCloud::new(state, &seg.points),
+			neighbors,
+			trunk_axis,
+			centerline: Centerline::default(),
+			outliers: None,
+			align: Align::default(),
+			compare: Compare::default(),
+			ranges,
+			picked: None,
+			points: seg.points.clone(),
+		});
+	}
 
-				match proj4rs::Proj::from_proj_string(&self.source_location) {
-					Ok(proj) => seg.update_location(self.world_offset, &proj),
-					Err(err) => eprintln!("{}", err),
+	/// Apply the current modus to all points inside the selection.
+	pub fn select_inside(
+		&mut self,
+		inside: impl Fn(na::Point3<f32>) -> bool,
+		state: &render::State,
+	) {
+		match self.modus {
+			Modus::Delete => {
+				let mut changed = false;
+				let mut empty = Vec::new();
+				for (&other, segment) in self.segments.iter_mut() {
+					if self.sources.visible(other).not()
+						|| segment.points.iter().any(|&p| inside(p)).not()
+					{
+						continue;
+					}
+					let segment = Arc::make_mut(segment);
+					segment.remove_where(&inside, Arc::make_mut(&mut self.deleted));
+					segment.changed(other, &self.sender);
+					changed = true;
+					if segment.points.is_empty() {
+						empty.push(other);
+					}
+				}
+				for empty in empty {
+					_ = self.sender.send(Event::RemovePointCloud(empty));
+					self.segments.remove(&empty);
 				}
+				if changed {
+					Arc::make_mut(&mut self.deleted).changed(DELETED_INDEX, &self.sender);
+					self.modified();
+				}
+			},
+			Modus::Draw(idx) => {
+				let mut target = self.segments.remove(&idx).unwrap();
+				let data = Arc::make_mut(&mut target);
+				let mut changed = false;
+				let mut empty = Vec::new();
+				for (&other, segment) in self.segments.iter_mut() {
+					if self.sources.visible(other).not()
+						|| segment.points.iter().any(|&p| inside(p)).not()
+					{
+						continue;
+					}
+					let segment = Arc::make_mut(segment);
+					segment.remove_where(&inside, data);
+					segment.changed(other, &self.sender);
+					changed = true;
+					if segment.points.is_empty() {
+						empty.push(other);
+					}
+				}
+				if self.show_deleted && self.deleted.points.iter().any(|&p| inside(p)) {
+					let deleted = Arc::make_mut(&mut self.deleted);
+					deleted.remove_where(&inside, data);
+					deleted.changed(DELETED_INDEX, &self.sender);
+					changed = true;
+				}
+				if changed {
+					data.changed(idx, &self.sender);
+				}
+				self.segments.insert(idx, target);
+				for empty in empty {
+					_ = self.sender.send(Event::RemovePointCloud(empty));
+					self.segments.remove(&empty);
+				}
+				if changed {
+					self.modified();
+				}
+			},
+			Modus::View(ref mut view) => {
+				let seg = Arc::make_mut(self.segments.get_mut(&view.idx).unwrap());
+				let changed = match view.modus {
+					ViewModus::Delete => {
+						let deleted = Arc::make_mut(&mut self.deleted);
+						if seg.remove_where(&inside, deleted) > 0 {
+							deleted.changed(DELETED_INDEX, &self.sender);
+							true
+						} else {
+							false
+						}
+					},
+					ViewModus::Crown => {
+						seg.change_classification_where(&inside, Classification::Crown)
+					},
+					ViewModus::Trunk => {
+						seg.change_classification_where(&inside, Classification::Trunk)
+					},
+					ViewModus::Ground => {
+						seg.change_classification_where(&inside, Classification::Ground)
+					},
+				};
+				if changed {
+					view.segment_changed(seg, &self.calculation_settings, &self.sender, state);
+					self.changes += 1;
+				}
+			},
+			_ => {},
+		}
+	}
 
-				let display_data = DisplayData::new(state, seg, &calculations_properties);
-				let trunk_axis = TrunkAxis::new(
-					&seg.points,
-					&seg.classifications,
-					TrunkAxisAlgorithm::None,
-					state,
-				);
+	/// Position of the brush for the current edit modus.
+	fn brush_hit(
+		&self,
+		start: na::Point3<f32>,
+		direction: na::Vector3<f32>,
+		display_settings: &DisplaySettings,
+	) -> Option<na::Point3<f32>> {
+		let distance = match &self.modus {
+			Modus::Delete => self
+				.select(start, direction, display_settings)
+				.map(|(_, distance)| distance),
+			Modus::Draw(_) => self
+				.select(start, direction, display_settings)
+				.map(|(_, distance)| distance)
+				.or_else(|| {
+					if self.show_deleted.not() {
+						return None;
+					}
+					self.deleted.raycast_distance(start, direction)?;
+					self.deleted
+						.exact_distance(start, direction, display_settings)
+				}),
+			Modus::View(view) => {
+				let seg = &self.segments[&view.idx];
+				let mut distance = seg.exact_distance(start, direction, display_settings);
 
-				self.modus = Modus::View(View {
-					idx,
-					hull: Hull::None,
-					display_modus: DisplayModus::Classification,
-					modus: ViewModus::Delete,
-					display_data,
-					calculations_properties,
-					cloud: render::PointCloud::new(state, &seg.points),
-					trunk_axis,
-				})
+				if self.show_deleted {
+					if let Some(del_distance) = self
+						.deleted
+						.exact_distance(start, direction, display_settings)
+						.or_else(|| seg.exact_distance(start, direction, display_settings))
+					{
+						if distance.map(|d| d > del_distance).unwrap_or(true) {
+							distance = Some(del_distance);
+						}
+					};
+				}
+				distance
 			},
+			_ => None,
+		};
+		distance.map(|distance| start + direction * distance)
+	}
 
-			Modus::View { .. } => {},
+	/// Scale the brush radius and show the brush at the cursor.
+	/// Returns `false` if the current modus does not use the brush.
+	pub fn scroll_radius(
+		&mut self,
+		delta: f32,
+		start: na::Point3<f32>,
+		direction: na::Vector3<f32>,
+		display_settings: &DisplaySettings,
+		state: &render::State,
+	) -> bool {
+		if matches!(self.modus, Modus::Delete | Modus::Draw(_) | Modus::View(_)).not() {
+			return false;
 		}
+		self.draw_radius = (self.draw_radius * 1.1f32.powf(-delta)).clamp(0.1, 10.0);
+		self.brush = self
+			.brush_hit(start, direction, display_settings)
+			.map(|hit| Brush::sphere(hit, self.draw_radius, state));
+		true
 	}
 
 	/// Handle mouse drag.
@@ -813,14 +2383,20 @@ impl Interactive {
 	) {
 		match self.modus {
 			Modus::Delete => {
-				let Some((_, distance)) = self.select(start, direction, display_settings) else {
+				let Some(hit) = self.brush_hit(start, direction, display_settings) else {
 					return;
 				};
-				let hit = start + direction * distance;
+				self.brush = Some(Brush::sphere(hit, self.draw_radius, state));
 				let mut changed = false;
 				let mut empty = Vec::new();
 				for (&other, segment) in self.segments.iter_mut() {
-					if segment.remove(hit, self.draw_radius, &mut self.deleted) {
+					if self.sources.visible(other).not()
+						|| segment.near(hit, self.draw_radius).not()
+					{
+						continue;
+					}
+					let segment = Arc::make_mut(segment);
+					if segment.remove(hit, self.draw_radius, Arc::make_mut(&mut self.deleted)) {
 						segment.changed(other, &self.sender);
 						changed = true;
 					}
@@ -829,33 +2405,31 @@ impl Interactive {
 					}
 				}
 				for empty in empty {
+					_ = self.sender.send(Event::RemovePointCloud(empty));
 					self.segments.remove(&empty);
 				}
 				if changed {
-					self.deleted.changed(DELETED_INDEX, &self.sender);
+					Arc::make_mut(&mut self.deleted).changed(DELETED_INDEX, &self.sender);
+					self.modified();
 				}
 			},
 			Modus::Draw(idx) => {
-				let Some(distance) = self
-					.select(start, direction, display_settings)
-					.map(|(_, distance)| distance)
-					.or_else(|| {
-						if self.show_deleted.not() {
-							return None;
-						}
-						self.deleted.raycast_distance(start, direction)?;
-						self.deleted
-							.exact_distance(start, direction, display_settings)
-					})
-				else {
+				let Some(hit) = self.brush_hit(start, direction, display_settings) else {
 					return;
 				};
-				let hit = start + direction * distance;
+				self.brush = Some(Brush::sphere(hit, self.draw_radius, state));
 				let mut target = self.segments.remove(&idx).unwrap();
+				let data = Arc::make_mut(&mut target);
 				let mut changed = false;
 				let mut empty = Vec::new();
 				for (&other, segment) in self.segments.iter_mut() {
-					if segment.remove(hit, self.draw_radius, &mut target) {
+					if self.sources.visible(other).not()
+						|| segment.near(hit, self.draw_radius).not()
+					{
+						continue;
+					}
+					let segment = Arc::make_mut(segment);
+					if segment.remove(hit, self.draw_radius, data) {
 						segment.changed(other, &self.sender);
 						changed = true;
 					}
@@ -863,53 +2437,59 @@ impl Interactive {
 						empty.push(other);
 					}
 				}
-				if self.show_deleted && self.deleted.remove(hit, self.draw_radius, &mut target) {
-					self.deleted.changed(DELETED_INDEX, &self.sender);
+				if self.show_deleted
+					&& self.deleted.near(hit, self.draw_radius)
+					&& Arc::make_mut(&mut self.deleted).remove(hit, self.draw_radius, data)
+				{
+					Arc::make_mut(&mut self.deleted).changed(DELETED_INDEX, &self.sender);
 					changed = true;
 				}
 
 				if changed {
-					target.changed(idx, &self.sender);
+					data.changed(idx, &self.sender);
 				}
 				self.segments.insert(idx, target);
 				for empty in empty {
+					_ = self.sender.send(Event::RemovePointCloud(empty));
 					self.segments.remove(&empty);
 				}
-			},
-			Modus::View(ref mut view) => {
-				let seg = self.segments.get_mut(&view.idx).unwrap();
-				let mut distance = seg.exact_distance(start, direction, display_settings);
-
-				if self.show_deleted {
-					if let Some(del_distance) = self
-						.deleted
-						.exact_distance(start, direction, display_settings)
-						.or_else(|| seg.exact_distance(start, direction, display_settings))
-					{
-						if distance.map(|d| d > del_distance).unwrap_or(true) {
-							distance = Some(del_distance);
-						}
-					};
+				if changed {
+					self.modified();
 				}
-				let Some(distance) = distance else {
+			},
+			Modus::View(_) => {
+				let Some(hit) = self.brush_hit(start, direction, display_settings) else {
 					return;
 				};
-				let hit = start + direction * distance;
+				self.brush = Some(Brush::sphere(hit, self.draw_radius, state));
+				let Modus::View(view) = &mut self.modus else {
+					unreachable!();
+				};
+				if self.segments[&view.idx].near(hit, self.draw_radius).not()
+					&& (self.show_deleted && self.deleted.near(hit, self.draw_radius)).not()
+				{
+					return;
+				}
+				let seg = Arc::make_mut(self.segments.get_mut(&view.idx).unwrap());
 
 				let mut changed = false;
 
 				if self.show_deleted
 					&& view.modus != ViewModus::Delete
-					&& self.deleted.remove(hit, self.draw_radius, seg)
+					&& self.deleted.near(hit, self.draw_radius)
 				{
-					self.deleted.changed(DELETED_INDEX, &self.sender);
-					changed = true;
+					let deleted = Arc::make_mut(&mut self.deleted);
+					if deleted.remove(hit, self.draw_radius, seg) {
+						deleted.changed(DELETED_INDEX, &self.sender);
+						changed = true;
+					}
 				}
 
 				changed |= match view.modus {
 					ViewModus::Delete => {
-						if seg.remove(hit, self.draw_radius, &mut self.deleted) {
-							self.deleted.changed(DELETED_INDEX, &self.sender);
+						let deleted = Arc::make_mut(&mut self.deleted);
+						if seg.remove(hit, self.draw_radius, deleted) {
+							deleted.changed(DELETED_INDEX, &self.sender);
 							true
 						} else {
 							false
@@ -927,12 +2507,8 @@ impl Interactive {
 					},
 				};
 				if changed {
-					seg.changed(view.idx, &self.sender);
-					view.calculations_properties = seg.update_info(false);
-					view.display_data = DisplayData::new(state, seg, &view.calculations_properties);
-					view.cloud = render::PointCloud::new(state, &seg.points);
-					view.hull.update(seg, view.trunk_axis.transform(), state);
-					view.trunk_axis.update(seg, state);
+					view.segment_changed(seg, &self.calculation_settings, &self.sender, state);
+					self.changes += 1;
 				}
 			},
 			Modus::Combine(idx) => {
@@ -943,11 +2519,14 @@ impl Interactive {
 					return;
 				}
 				_ = self.sender.send(Event::RemovePointCloud(other));
-				let mut other = self.segments.remove(&other).unwrap();
-				let target = self.segments.get_mut(&idx).unwrap();
-				target.points.append(&mut other.points);
-				target.classifications.append(&mut other.classifications);
+				let other = self.segments.remove(&other).unwrap();
+				let target = Arc::make_mut(self.segments.get_mut(&idx).unwrap());
+				target.points.extend_from_slice(&other.points);
+				target
+					.classifications
+					.extend_from_slice(&other.classifications);
 				target.changed(idx, &self.sender);
+				self.modified();
 			},
 			_ => {},
 		}
@@ -962,6 +2541,12 @@ pub enum Modus {
 	Draw(u32),
 	SelectCombine,
 	Combine(u32),
+	MultiCombine(HashSet<u32>),
+	SelectSplit,
+	/// Segment to split and first point of the split line.
+	Split(u32, na::Point3<f32>),
+	/// Picked ground points and the last tilt correction in degrees.
+	Level(Vec<na::Point3<f32>>, Option<f32>),
 	Spawn,
 	Delete,
 	View(View),
@@ -979,6 +2564,104 @@ pub struct View {
 
 	pub hull: Hull,
 	pub trunk_axis: TrunkAxis,
+	pub centerline: Centerline,
+	/// Result of the last outlier cleaning.
+	pub outliers: Option<String>,
+	pub align: Align,
+	pub compare: Compare,
+	pub ranges: DisplayRanges,
+	/// Index of the hovered point.
+	pub picked: Option<usize>,
+	/// Points in `cloud`, to upload only the changed points.
+	points: Vec<na::Point3<f32>>,
+	/// Neighbors of `points` for the curvature, updated with the changed points.
+	neighbors: NeighborsTree,
+}
+
+impl View {
+	/// Update cached data after the points of the viewed segment changed.
+	fn segment_changed(
+		&mut self,
+		segment: &mut SegmentData,
+		settings: &CalculationSettings,
+		sender: &crossbeam::channel::Sender<Event>,
+		state: &render::State,
+	) {
+		segment.changed(self.idx, sender);
+		// distances are outdated after the points changed
+		self.compare.result = None;
+		if self.display_modus == DisplayModus::Distance {
+			self.display_modus = DisplayModus::Classification;
+		}
+		self.calculations_properties = segment.update_info(None, settings);
+		self.display_data
+			.update(state, segment, &self.calculations_properties, &self.ranges);
+		let changed = changed_range(&self.points, &segment.points);
+		self.cloud.update_range(state, &segment.points, changed);
+		self.neighbors.update(&self.points, &segment.points);
+		self.points.clone_from(&segment.points);
+		self.hull
+			.update(segment, self.trunk_axis.transform(), state);
+		self.trunk_axis.update(segment, state);
+		self.centerline.update(segment, settings.slice_width, state);
+		self.picked = None;
+	}
+
+	/// Show the properties of the hovered point.
+	fn picked_ui(
+		&self,
+		ui: &mut egui::Ui,
+		segment: &SegmentData,
+		world_offset: na::Point3<f64>,
+		units: Units,
+	) {
+		ui.add_sized(
+			[ui.available_width(), 0.0],
+			egui::Label::new("Hovered Point"),
+		);
+		let Some(idx) = self.picked.filter(|&idx| idx < segment.points.len()) else {
+			ui.add_sized(
+				[ui.available_width(), 0.0],
+				egui::Label::new("Move the mouse over a point"),
+			);
+			return;
+		};
+		let point = segment.points[idx];
+		let properties = &self.calculations_properties;
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			ui.label("Index");
+			ui.label(format!("{}", idx));
+			ui.end_row();
+
+			ui.label("Position");
+			ui.label(format!(
+				"{:.2}, {:.2}, {:.2}",
+				world_offset.x + point.x as f64,
+				-(world_offset.z + point.z as f64),
+				world_offset.y + point.y as f64,
+			));
+			ui.end_row();
+
+			ui.label("Height");
+			ui.label(units.length(point.y - segment.min.y));
+			ui.end_row();
+
+			ui.label("Classification");
+			ui.label(format!("{:?}", segment.classifications[idx]));
+			ui.end_row();
+
+			for (name, values) in [
+				("Curve", &properties.curve),
+				("Expansion", &properties.expansion),
+			] {
+				if let Some(value) = values.get(idx) {
+					ui.label(name);
+					ui.label(format!("{:.3}", value));
+					ui.end_row();
+				}
+			}
+		});
+	}
 }
 
 /// Display data for selected segment.
@@ -988,6 +2671,90 @@ pub struct DisplayData {
 	pub curve: render::PointCloudProperty,
 	pub expansion: render::PointCloudProperty,
 	pub height: render::PointCloudProperty,
+	/// 2nd and 98th percentile of the normalized properties.
+	pub percentiles: DisplayRanges,
+	/// Values for classification, curve, expansion and height on the GPU.
+	values: [Vec<u32>; 4],
+	/// Incremented for every update of the values or the ranges.
+	pub revision: u64,
+}
+
+/// Range from the first to the last different value.
+/// Everything after the shorter slice is different if the lengths differ.
+fn changed_range<T: PartialEq>(old: &[T], new: &[T]) -> std::ops::Range<usize> {
+	let same = |(a, b): (&T, &T)| a == b;
+	let start = old.iter().zip(new).take_while(|&v| same(v)).count();
+	if old.len() != new.len() {
+		return start..new.len();
+	}
+	let end = new.len()
+		- old[start..]
+			.iter()
+			.rev()
+			.zip(new[start..].iter().rev())
+			.take_while(|&v| same(v))
+			.count();
+	start..end
+}
+
+/// Normalized value ranges mapped to the full color range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayRanges {
+	pub curve: (f32, f32),
+	pub expansion: (f32, f32),
+	pub height: (f32, f32),
+}
+
+impl Default for DisplayRanges {
+	fn default() -> Self {
+		Self {
+			curve: (0.0, 1.0),
+			expansion: (0.0, 1.0),
+			height: (0.0, 1.0),
+		}
+	}
+}
+
+impl DisplayRanges {
+	/// Range for the display modus, `None` for classifications.
+	pub fn get_mut(&mut self, modus: DisplayModus) -> Option<&mut (f32, f32)> {
+		match modus {
+			DisplayModus::Classification | DisplayModus::Distance => None,
+			DisplayModus::Curve => Some(&mut self.curve),
+			DisplayModus::Expansion => Some(&mut self.expansion),
+			DisplayModus::Height => Some(&mut self.height),
+		}
+	}
+}
+
+/// Value to color the segments in the overview.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorModus {
+	Segment,
+	Height,
+	TrunkHeight,
+	CrownHeight,
+}
+
+impl ColorModus {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Segment => "Segment",
+			Self::Height => "Height",
+			Self::TrunkHeight => "Trunk Height",
+			Self::CrownHeight => "Crown Height",
+		}
+	}
+
+	/// Trait value for a segment, `None` to use the segment colors.
+	pub fn value(self, segment: &SegmentData) -> Option<f32> {
+		match self {
+			Self::Segment => None,
+			Self::Height => Some(segment.max.y - segment.min.y),
+			Self::TrunkHeight => Some(segment.info.trunk_height),
+			Self::CrownHeight => Some(segment.info.crown_height),
+		}
+	}
 }
 
 /// Display modus to render.
@@ -997,11 +2764,59 @@ pub enum DisplayModus {
 	Curve,
 	Expansion,
 	Height,
+	/// Distance from the last comparison.
+	Distance,
 }
 
 impl DisplayData {
 	/// Create DisplayData from values.
-	pub fn new(state: &render::State, seg: &SegmentData, calc: &CalculationProperties) -> Self {
+	pub fn new(
+		state: &render::State,
+		seg: &SegmentData,
+		calc: &CalculationProperties,
+		ranges: &DisplayRanges,
+	) -> Self {
+		let (percentiles, values) = Self::values(seg, calc, ranges);
+		Self {
+			classification: render::PointCloudProperty::new(state, &values[0]),
+			curve: render::PointCloudProperty::new(state, &values[1]),
+			expansion: render::PointCloudProperty::new(state, &values[2]),
+			height: render::PointCloudProperty::new(state, &values[3]),
+			percentiles,
+			values,
+			revision: 0,
+		}
+	}
+
+	/// Update the values, only the changed values are uploaded.
+	pub fn update(
+		&mut self,
+		state: &render::State,
+		seg: &SegmentData,
+		calc: &CalculationProperties,
+		ranges: &DisplayRanges,
+	) {
+		let (percentiles, values) = Self::values(seg, calc, ranges);
+		let properties = [
+			&mut self.classification,
+			&mut self.curve,
+			&mut self.expansion,
+			&mut self.height,
+		];
+		for ((property, old), new) in properties.into_iter().zip(&self.values).zip(&values) {
+			property.update_range(state, new, changed_range(old, new));
+		}
+		self.percentiles = percentiles;
+		self.values = values;
+		self.revision += 1;
+	}
+
+	/// Percentiles and the values for classification, curve, expansion and height.
+	fn values(
+		seg: &SegmentData,
+		calc: &CalculationProperties,
+		ranges: &DisplayRanges,
+	) -> (DisplayRanges, [Vec<u32>; 4]) {
 		let max_expansion = calc
 			.expansion
 			.iter()
@@ -1011,22 +2826,24 @@ impl DisplayData {
 		let expansion = calc
 			.expansion
 			.iter()
-			.copied()
-			.map(|e| map_to_u32(e / max_expansion))
+			.map(|e| e / max_expansion)
 			.collect::<Vec<_>>();
 
-		let curve = calc
-			.curve
-			.iter()
-			.copied()
-			.map(map_to_u32)
-			.collect::<Vec<_>>();
-		let height = calc
-			.height
-			.iter()
-			.copied()
-			.map(map_to_u32)
-			.collect::<Vec<_>>();
+		let percentiles = DisplayRanges {
+			curve: percentiles(&calc.curve),
+			expansion: percentiles(&expansion),
+			height: percentiles(&calc.height),
+		};
+		let stretch = |values: &[f32], (min, max): (f32, f32)| {
+			let range = (max - min).max(f32::EPSILON);
+			values
+				.iter()
+				.map(|&v| map_to_u32(((v - min) / range).clamp(0.0, 1.0)))
+				.collect::<Vec<_>>()
+		};
+		let expansion = stretch(&expansion, ranges.expansion);
+		let curve = stretch(&calc.curve, ranges.curve);
+		let height = stretch(&calc.height, ranges.height);
 
 		let classification = seg
 			.classifications
@@ -1035,15 +2852,11 @@ impl DisplayData {
 				Classification::Ground => u32::MAX / 8,
 				Classification::Trunk => u32::MAX / 8 * 3,
 				Classification::Crown => u32::MAX / 8 * 6,
+				Classification::Noise => u32::MAX,
 			})
 			.collect::<Vec<_>>();
 
-		Self {
-			classification: render::PointCloudProperty::new(state, &classification),
-			curve: render::PointCloudProperty::new(state, &curve),
-			expansion: render::PointCloudProperty::new(state, &expansion),
-			height: render::PointCloudProperty::new(state, &height),
-		}
+		(percentiles, [classification, curve, expansion, height])
 	}
 }
 
@@ -1056,13 +2869,67 @@ pub enum ViewModus {
 	Ground,
 }
 
-/// Format radians as degrees, minutes and seconds.
-fn format_degrees(val: f64) -> String {
-	let deg = val.to_degrees();
-	let min = deg.fract() * if deg >= 0.0 { 60.0 } else { -60.0 };
-	let deg = deg.trunc() as isize;
-	let (min, sec) = (min.trunc() as isize, min.fract() * 60.0);
-	format!("{:0>2}°{:0>2}'{:0>4.1}\"", deg, min, sec)
+/// Position of exported points.
+#[derive(Debug, Clone)]
+pub struct Georeference {
+	pub offset: na::Point3<f64>,
+	pub location: String,
+	/// Add the offset to the coordinates, otherwise the offset is only a comment.
+	pub enabled: bool,
+	/// Rotation applied to level the ground, undone for the written positions.
+	pub leveling: Option<na::UnitQuaternion<f32>>,
+}
+
+impl Georeference {
+	/// Write the comments and position properties for a `.ply` header.
+	pub fn write_header(&self, writer: &mut impl std::io::Write) -> Result<(), std::io::Error> {
+		let location = self.location.split_whitespace().collect::<Vec<_>>();
+		writeln!(writer, "comment crs {}", location.join(" "))?;
+		if self.enabled {
+			writeln!(writer, "property double x")?;
+			writeln!(writer, "property double y")?;
+			writeln!(writer, "property double z")?;
+		} else {
+			writeln!(
+				writer,
+				"comment offset {} {} {}",
+				self.offset.x, -self.offset.z, self.offset.y
+			)?;
+			writeln!(writer, "property float x")?;
+			writeln!(writer, "property float y")?;
+			writeln!(writer, "property float z")?;
+		}
+		Ok(())
+	}
+
+	/// Write the position for a point, without a line break.
+	pub fn write_position(
+		&self,
+		writer: &mut impl std::io::Write,
+		p: na::Point3<f32>,
+	) -> Result<(), std::io::Error> {
+		self.write_position_separated(writer, p, " ")
+	}
+
+	/// Write the position for a point with `separator` between the coordinates.
+	pub fn write_position_separated(
+		&self,
+		writer: &mut impl std::io::Write,
+		p: na::Point3<f32>,
+		separator: &str,
+	) -> Result<(), std::io::Error> {
+		let p = match self.leveling {
+			Some(rotation) => rotation.inverse_transform_point(&p),
+			None => p,
+		};
+		if self.enabled {
+			// double precision for large world coordinates
+			let p = self.offset + p.coords.cast::<f64>();
+			write!(writer, "{1}{0}{2}{0}{3}", separator, p.x, -p.z, p.y)
+		} else {
+			write!(writer, "{1}{0}{2}{0}{3}", separator, p.x, -p.z, p.y)
+		}
+	}
 }
 
 /// Save points as `.ply`.
@@ -1071,6 +2938,8 @@ pub fn save_points(
 	points: &[na::Point3<f32>],
 	classifications: &[Classification],
 	calculations_properties: &CalculationProperties,
+	normals: &[na::Vector3<f32>],
+	georeference: &Georeference,
 	valid: impl Fn(Classification) -> bool,
 ) -> Result<(), std::io::Error> {
 	use std::io::Write;
@@ -1080,9 +2949,10 @@ pub fn save_points(
 	writeln!(writer, "ply")?;
 	writeln!(writer, "format ascii 1.0")?;
 	writeln!(writer, "element vertex {}", count)?;
-	writeln!(writer, "property float x")?;
-	writeln!(writer, "property float y")?;
-	writeln!(writer, "property float z")?;
+	georeference.write_header(&mut writer)?;
+	writeln!(writer, "property float nx")?;
+	writeln!(writer, "property float ny")?;
+	writeln!(writer, "property float nz")?;
 	writeln!(writer, "property float expansion")?;
 	writeln!(writer, "property float height")?;
 	writeln!(writer, "property float curve")?;
@@ -1091,12 +2961,12 @@ pub fn save_points(
 		if valid(classifications[idx]).not() {
 			continue;
 		}
+		georeference.write_position(&mut writer, *p)?;
+		let n = normals[idx];
+		write!(writer, " {} {} {}", n.x, -n.z, n.y)?;
 		writeln!(
 			writer,
-			"{} {} {} {} {} {}",
-			p.x,
-			-p.z,
-			p.y,
+			" {} {} {}",
 			calculations_properties.expansion[idx],
 			calculations_properties.height[idx],
 			calculations_properties.curve[idx]
@@ -1105,3 +2975,185 @@ pub fn save_points(
 
 	Ok(())
 }
+
+/// Save all segments as a single `.ply`.
+///
+/// Every point has the `segment_id` and the `classification` (0 ground, 1 trunk, 2 crown, 3 noise).
+pub fn save_segments(
+	saver: &mut Saver,
+	segments: &HashMap<u32, Arc<SegmentData>>,
+	georeference: &Georeference,
+) -> Result<(), std::io::Error> {
+	use std::io::Write;
+
+	let mut ids = segments.keys().copied().collect::<Vec<_>>();
+	ids.sort_unstable();
+	let count = segments.values().map(|s| s.points.len()).sum::<usize>();
+	let mut writer = saver.inner();
+	writeln!(writer, "ply")?;
+	writeln!(writer, "format ascii 1.0")?;
+	writeln!(writer, "element vertex {}", count)?;
+	georeference.write_header(&mut writer)?;
+	writeln!(writer, "property uint segment_id")?;
+	writeln!(writer, "property uchar classification")?;
+	writeln!(writer, "end_header")?;
+	for id in ids {
+		let segment = &segments[&id];
+		for (p, &c) in segment.points.iter().zip(&segment.classifications) {
+			georeference.write_position(&mut writer, *p)?;
+			writeln!(writer, " {} {}", id, c as u8)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// 2nd and 98th percentile of the values.
+fn percentiles(values: &[f32]) -> (f32, f32) {
+	if values.is_empty() {
+		return (0.0, 1.0);
+	}
+	let mut values = values.to_vec();
+	let low = values.len() * 2 / 100;
+	let high = (values.len() * 98 / 100).min(values.len() - 1);
+	let low = *values.select_nth_unstable_by(low, |a, b| a.total_cmp(b)).1;
+	let high = *values.select_nth_unstable_by(high, |a, b| a.total_cmp(b)).1;
+	(low, high)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn save() -> InteractiveSave {
+		InteractiveSave {
+			segments: HashMap::new(),
+			deleted: Arc::new(SegmentData::new(Vec::new())),
+			world_offset: na::point![1.0, 2.0, 3.0],
+			source_location: DEFAULT_LOCATION.into(),
+		}
+	}
+
+	#[test]
+	fn session_roundtrip() {
+		let viewer = ViewerSettings {
+			class_colors: [na::point![1.0, 0.0, 0.0]; 3],
+			..Default::default()
+		};
+		let alignment = na::Isometry3::new(na::vector![1.0, 0.0, 2.0], na::vector![0.0, 0.5, 0.0]);
+		let session = SessionSave {
+			viewer: Some(viewer),
+			view: Some(7),
+			alignments: HashMap::from([(7, alignment)]),
+			..Default::default()
+		};
+		let mut data = Vec::new();
+		save().write(&session, &mut data).unwrap();
+
+		let (_, read) = InteractiveSave::read(std::io::Cursor::new(data)).unwrap();
+		assert_eq!(read.viewer, Some(viewer));
+		assert_eq!(read.view, Some(7));
+		assert_eq!(read.alignments.get(&7), Some(&alignment));
+	}
+
+	#[test]
+	fn merged_sources() {
+		let mut sources = Sources::default();
+		sources.add("first".into(), Sources::default(), &HashMap::from([(1, 2)]));
+		let mut added = Sources::default();
+		added.add(
+			"second".into(),
+			Sources::default(),
+			&HashMap::from([(3, 3)]),
+		);
+		sources.add("merged".into(), added, &HashMap::from([(3, 4), (5, 5)]));
+		sources.inherit(4, 6);
+
+		assert_eq!(sources.name(1), Some("Project"));
+		assert_eq!(sources.name(2), Some("first"));
+		assert_eq!(sources.name(4), Some("second"));
+		assert_eq!(sources.name(5), Some("merged"));
+		assert_eq!(sources.project(6), sources.project(4));
+		assert_eq!(sources.projects.len(), 4);
+	}
+
+	#[test]
+	fn session_version_3() {
+		let mut data = Vec::new();
+		data.extend_from_slice(SAVE_MAGIC);
+		bincode::serialize_into(&mut data, &3u32).unwrap();
+		bincode::serialize_into(&mut data, &save()).unwrap();
+		// viewer settings without the classification colors
+		let viewer = (
+			na::point![0.1f32, 0.2, 0.3],
+			0.5f32,
+			Lookup::Warm,
+			1.0f32,
+			4u32,
+			true,
+			0.5f32,
+			0.0f32,
+			1u32,
+		);
+		bincode::serialize_into(&mut data, &(None::<CameraSave>, Some(viewer), Some(7u32)))
+			.unwrap();
+		bincode::serialize_into(&mut data, &Provenance::default()).unwrap();
+		bincode::serialize_into(&mut data, &None::<na::UnitQuaternion<f32>>).unwrap();
+
+		let (_, read) = InteractiveSave::read(std::io::Cursor::new(data)).unwrap();
+		let viewer = read.viewer.unwrap();
+		assert_eq!(viewer.point_size, 0.5);
+		assert_eq!(viewer.lookup, Lookup::Warm);
+		assert_eq!(viewer.class_colors, ViewerSettings::default().class_colors);
+		assert_eq!(read.view, Some(7));
+	}
+
+	#[test]
+	fn noise_outside_bounds() {
+		let mut segment = SegmentData::new(vec![
+			na::point![0.0, 0.0, 0.0],
+			na::point![1.0, 2.0, 1.0],
+			na::point![0.0, 10.0, 0.0],
+		]);
+		segment.classifications[2] = Classification::Noise;
+		let (sender, _receiver) = crossbeam::channel::unbounded();
+		segment.changed(0, &sender);
+
+		assert_eq!(segment.max, na::point![1.0, 2.0, 1.0]);
+		assert!(segment.near(na::point![0.0, 10.0, 0.0], 0.1));
+		let hit = segment.raycast_distance(na::point![0.0, 10.0, -5.0], na::vector![0.0, 0.0, 1.0]);
+		assert!(hit.is_some());
+	}
+
+	#[test]
+	fn georeference_roundtrip() {
+		let leveling = na::UnitQuaternion::from_euler_angles(0.1, 0.0, 0.2);
+		let georeference = Georeference {
+			offset: na::point![512_345.125, 250.5, -5_432_109.875],
+			location: DEFAULT_LOCATION.into(),
+			enabled: true,
+			leveling: Some(leveling),
+		};
+		let mut data = Vec::new();
+		georeference
+			.write_position_separated(&mut data, leveling * na::point![1.5, 2.0, -3.25], ",")
+			.unwrap();
+
+		// too large for single precision
+		let written = String::from_utf8(data)
+			.unwrap()
+			.split(',')
+			.map(|value| value.parse::<f64>().unwrap())
+			.collect::<Vec<_>>();
+		let expected = [512_346.625, 5_432_113.125, 252.5];
+		assert_eq!(written.len(), 3);
+		for (written, expected) in written.into_iter().zip(expected) {
+			assert!(
+				(written - expected).abs() < 1e-4,
+				"{} != {}",
+				written,
+				expected
+			);
+		}
+	}
+}
@@ -0,0 +1,47 @@
+use std::{collections::HashMap, io::Write, sync::Arc};
+
+use crate::calculations::SegmentData;
+
+/// Fields of a point record, packed without padding.
+const DESCRIPTION: &str =
+	"[('x', '<f4'), ('y', '<f4'), ('z', '<f4'), ('segment', '<u4'), ('classification', 'u1')]";
+
+/// Write the points of the segments as a NumPy `.npy` file, sorted by segment id.
+///
+/// The array has the shape `(points,)` with one record per point. `x`, `y` and `z` are
+/// little-endian `f32` with z up, `segment` is the id as `u32` and `classification` an `u8`
+/// (0 ground, 1 trunk, 2 crown, 3 noise).
+///
+/// Source: <https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html>
+pub fn write_npy(
+	mut writer: impl Write,
+	segments: &HashMap<u32, Arc<SegmentData>>,
+) -> Result<(), std::io::Error> {
+	let mut ids = segments.keys().copied().collect::<Vec<_>>();
+	ids.sort_unstable();
+	let count = segments.values().map(|s| s.points.len()).sum::<usize>();
+
+	let mut header = format!(
+		"{{'descr': {}, 'fortran_order': False, 'shape': ({},), }}",
+		DESCRIPTION, count
+	);
+	// magic, version and header length are 10 bytes, the data starts aligned to 64 bytes
+	let total = (10 + header.len() + 1).next_multiple_of(64);
+	header.extend(std::iter::repeat(' ').take(total - 10 - header.len() - 1));
+	header.push('\n');
+
+	writer.write_all(b"\x93NUMPY\x01\x00")?;
+	writer.write_all(&(header.len() as u16).to_le_bytes())?;
+	writer.write_all(header.as_bytes())?;
+	for id in ids {
+		let segment = &segments[&id];
+		for (p, &c) in segment.points.iter().zip(&segment.classifications) {
+			for v in [p.x, -p.z, p.y] {
+				writer.write_all(&v.to_le_bytes())?;
+			}
+			writer.write_all(&id.to_le_bytes())?;
+			writer.write_all(&[c as u8])?;
+		}
+	}
+	writer.flush()
+}
@@ -0,0 +1,151 @@
+use nalgebra as na;
+use std::collections::HashMap;
+
+use crate::id;
+
+/// Project the segments were loaded from.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Project {
+	pub name: String,
+	/// Color value for all segments of the project if the segments are colored by segment.
+	pub tint: Option<f32>,
+	/// Translation applied to the project after it was added.
+	pub offset: na::Vector3<f32>,
+	pub visible: bool,
+}
+
+impl Project {
+	pub fn new(name: String) -> Self {
+		Self {
+			name,
+			tint: None,
+			offset: na::Vector3::zeros(),
+			visible: true,
+		}
+	}
+}
+
+/// Projects merged into the session and the project of every segment.
+///
+/// Empty until a second project is added, segments without an entry belong to the first project.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Sources {
+	pub projects: Vec<Project>,
+	pub segments: HashMap<u32, usize>,
+}
+
+impl Sources {
+	/// Index of the project for the segment.
+	pub fn project(&self, idx: u32) -> usize {
+		self.segments.get(&idx).copied().unwrap_or(0)
+	}
+
+	pub fn name(&self, idx: u32) -> Option<&str> {
+		self.projects
+			.get(self.project(idx))
+			.map(|project| project.name.as_str())
+	}
+
+	/// Segments of hidden projects are not rendered and can not be selected or edited.
+	pub fn visible(&self, idx: u32) -> bool {
+		self.projects
+			.get(self.project(idx))
+			.map_or(true, |project| project.visible)
+	}
+
+	pub fn tint(&self, idx: u32) -> Option<f32> {
+		self.projects.get(self.project(idx))?.tint
+	}
+
+	/// Add the projects of another session, `indices` maps the segments of the session to the
+	/// new indices. The first project of the session is named after the file.
+	pub fn add(&mut self, name: String, mut added: Self, indices: &HashMap<u32, u32>) {
+		if self.projects.is_empty() {
+			self.projects.push(Project::new("Project".into()));
+		}
+		let base = self.projects.len();
+		match added.projects.first_mut() {
+			Some(first) => first.name = name,
+			None => added.projects.push(Project::new(name)),
+		}
+		self.projects.extend(added.projects);
+		for (old, &new) in indices {
+			self.segments
+				.insert(new, base + added.segments.get(old).copied().unwrap_or(0));
+		}
+	}
+
+	/// Segment `other` is part of the same project as `idx`.
+	pub fn inherit(&mut self, idx: u32, other: u32) {
+		if let Some(&project) = self.segments.get(&idx) {
+			self.segments.insert(other, project);
+		}
+	}
+
+	pub fn remove(&mut self, idx: u32) {
+		self.segments.remove(&idx);
+	}
+
+	/// Only keep the entries for the segments.
+	pub fn filtered(&self, keep: impl Fn(u32) -> bool) -> Self {
+		Self {
+			projects: self.projects.clone(),
+			segments: self
+				.segments
+				.iter()
+				.filter(|&(&idx, _)| keep(idx))
+				.map(|(&idx, &project)| (idx, project))
+				.collect(),
+		}
+	}
+
+	/// Draw the UI for the projects and the project of the hovered segment.
+	/// Returns the project and translation if an offset changed.
+	pub fn ui(
+		&mut self,
+		ui: &mut egui::Ui,
+		hovered: Option<u32>,
+	) -> Option<(usize, na::Vector3<f32>)> {
+		if self.projects.is_empty() {
+			return None;
+		}
+		let mut moved = None;
+		ui.separator();
+		ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Projects"));
+		for (index, project) in self.projects.iter_mut().enumerate() {
+			ui.push_id(index, |ui| {
+				ui.checkbox(&mut project.visible, project.name.as_str());
+				egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+					ui.label("Tint");
+					ui.horizontal(|ui| {
+						let mut tinted = project.tint.is_some();
+						if ui.checkbox(&mut tinted, "").changed() {
+							project.tint = tinted.then_some(0.5);
+						}
+						if let Some(tint) = &mut project.tint {
+							ui.add(egui::Slider::new(tint, 0.0..=1.0).show_value(false));
+						}
+					});
+					ui.end_row();
+
+					ui.label("Offset");
+					ui.horizontal(|ui| {
+						let previous = project.offset;
+						for value in project.offset.iter_mut() {
+							ui.add(egui::DragValue::new(value).speed(0.05).suffix("m"));
+						}
+						if project.offset != previous {
+							moved = Some((index, project.offset - previous));
+						}
+					});
+					ui.end_row();
+				});
+			});
+		}
+		if let Some(name) = hovered.and_then(|idx| self.name(idx)) {
+			ui.label(format!("Hovered: {}", name));
+		}
+		moved
+	}
+}
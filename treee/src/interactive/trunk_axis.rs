@@ -5,12 +5,15 @@ use crate::{
 	id,
 };
 
+use super::centerline::centerline;
+
 /// Algorithm to calculate the trunk origin and axis
 #[derive(Debug, Clone, Copy)]
 pub enum TrunkAxisAlgorithm {
 	None,
 	AverageDirection(usize),
 	LowHighLayer(f32),
+	PrincipalAxis,
 }
 
 /// Data to render the trunk axis
@@ -43,11 +46,65 @@ impl TrunkAxisRender {
 	}
 }
 
+/// Trunk axis for the segment information.
+///
+/// Positions use the same coordinates as the segment bounds.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct TrunkAxisSave {
+	pub bottom: na::Point3<f32>,
+	pub top: na::Point3<f32>,
+	pub direction: na::Vector3<f32>,
+}
+
+/// Shape of the stem relative to a straight trunk.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct StemForm {
+	/// Angle between the trunk axis and the vertical in degrees.
+	pub lean: f32,
+	/// Largest distance between the trunk centerline and the trunk axis.
+	pub sweep: f32,
+}
+
+impl StemForm {
+	/// Calculate the stem form from the fitted slices of the centerline.
+	///
+	/// The axis connects the lowest and highest fitted center, interpolated slices are ignored.
+	pub fn new(
+		points: &[na::Point3<f32>],
+		classifications: &[Classification],
+		slice_width: f32,
+	) -> Self {
+		let centers = centerline(points, classifications, slice_width)
+			.into_iter()
+			.filter(|p| p.fitted)
+			.map(|p| p.position)
+			.collect::<Vec<_>>();
+		let (Some(&bottom), Some(&top)) = (centers.first(), centers.last()) else {
+			return Self::default();
+		};
+		let Some(direction) = (top - bottom).try_normalize(f32::EPSILON) else {
+			return Self::default();
+		};
+
+		let sweep = centers
+			.iter()
+			.map(|p| {
+				let offset = p - bottom;
+				(offset - direction * offset.dot(&direction)).norm()
+			})
+			.fold(0.0, f32::max);
+
+		Self { lean: lean(direction), sweep }
+	}
+}
+
 /// Origin and axis for the trunk
 #[derive(Debug)]
 pub struct TrunkAxis {
 	origin: na::Point3<f32>,
 	direction: na::Vector3<f32>,
+	/// Length of the trunk along the axis.
+	length: f32,
 	algorithm: TrunkAxisAlgorithm,
 	render: Option<TrunkAxisRender>,
 }
@@ -57,16 +114,17 @@ impl TrunkAxis {
 		if matches!(self.algorithm, TrunkAxisAlgorithm::None) {
 			return None;
 		}
+		Some(axis_transform(self.origin, self.direction))
+	}
 
-		let y = self.direction;
-		let x = y.cross(&na::Vector3::z());
-		let z = x.cross(&y);
-		let rot = na::Rotation3::from_matrix_unchecked(na::Matrix::from_columns(&[x, y, z]));
-
-		let transform = na::Affine3::identity()
-			* na::Translation3::new(self.origin.x, self.origin.y, self.origin.z)
-			* rot;
-		Some(transform)
+	/// Axis to save, `None` without an axis.
+	pub fn save(&self) -> Option<TrunkAxisSave> {
+		self.render.as_ref()?;
+		Some(TrunkAxisSave {
+			bottom: self.origin,
+			top: self.origin + self.direction * self.length,
+			direction: self.direction,
+		})
 	}
 
 	pub fn update(&mut self, segment: &SegmentData, state: &render::State) {
@@ -92,6 +150,9 @@ impl TrunkAxis {
 			TrunkAxisAlgorithm::LowHighLayer(layer) => {
 				Self::new_low_high_layer(points, classifications, layer, state)
 			},
+			TrunkAxisAlgorithm::PrincipalAxis => {
+				Self::new_principal_axis(points, classifications, state)
+			},
 		}
 	}
 
@@ -99,6 +160,7 @@ impl TrunkAxis {
 		Self {
 			origin: na::point![0.0, 0.0, 0.0],
 			direction: na::Vector3::y(),
+			length: 0.0,
 			algorithm,
 			render: None,
 		}
@@ -171,6 +233,7 @@ impl TrunkAxis {
 		Self {
 			origin,
 			direction,
+			length: max - min,
 			algorithm: TrunkAxisAlgorithm::AverageDirection(layers),
 
 			render: Some(TrunkAxisRender::new(origin, direction, max - min, state)),
@@ -226,11 +289,32 @@ impl TrunkAxis {
 		Self {
 			origin,
 			direction,
+			length: (target - origin).norm(),
 			algorithm: TrunkAxisAlgorithm::LowHighLayer(layer_width),
 			render: Some(TrunkAxisRender::new(origin, direction, max - min, state)),
 		}
 	}
 
+	/// Use the direction with the largest variance of the trunk points
+	pub fn new_principal_axis(
+		points: &[na::Point3<f32>],
+		classifications: &[Classification],
+		state: &render::State,
+	) -> Self {
+		let Some((origin, direction, length)) = principal_axis(points, classifications) else {
+			log::warn!("No points for trunk");
+			return Self::new_empty(TrunkAxisAlgorithm::PrincipalAxis);
+		};
+
+		Self {
+			origin,
+			direction,
+			length,
+			algorithm: TrunkAxisAlgorithm::PrincipalAxis,
+			render: Some(TrunkAxisRender::new(origin, direction, length, state)),
+		}
+	}
+
 	pub fn ui(&mut self, ui: &mut egui::Ui, segment: &SegmentData, state: &render::State) -> bool {
 		let mut changed = false;
 		ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Trunk Axis"));
@@ -264,8 +348,18 @@ impl TrunkAxis {
 			self.algorithm = TrunkAxisAlgorithm::LowHighLayer(1.0);
 			changed |= true;
 		}
+		if ui
+			.add(egui::RadioButton::new(
+				matches!(self.algorithm, TrunkAxisAlgorithm::PrincipalAxis),
+				"Principal Axis",
+			))
+			.clicked()
+		{
+			self.algorithm = TrunkAxisAlgorithm::PrincipalAxis;
+			changed |= true;
+		}
 		match &mut self.algorithm {
-			TrunkAxisAlgorithm::None => {},
+			TrunkAxisAlgorithm::None | TrunkAxisAlgorithm::PrincipalAxis => {},
 			TrunkAxisAlgorithm::AverageDirection(layers) => {
 				ui.separator();
 				ui.add_sized(
@@ -303,3 +397,107 @@ impl TrunkAxis {
 		}
 	}
 }
+
+/// Transform from the local space with the y-axis along `direction` into the world space.
+pub fn axis_transform(origin: na::Point3<f32>, direction: na::Vector3<f32>) -> na::Affine3<f32> {
+	let y = direction;
+	let x = y.cross(&na::Vector3::z());
+	let z = x.cross(&y);
+	let rot = na::Rotation3::from_matrix_unchecked(na::Matrix::from_columns(&[x, y, z]));
+
+	na::Affine3::identity() * na::Translation3::new(origin.x, origin.y, origin.z) * rot
+}
+
+/// Angle between `direction` and the vertical in degrees.
+pub fn lean(direction: na::Vector3<f32>) -> f32 {
+	direction.y.abs().clamp(0.0, 1.0).acos().to_degrees()
+}
+
+/// Principal component of the trunk points.
+///
+/// Returns the lowest point on the axis, the upwards direction and the length of the trunk.
+pub fn principal_axis(
+	points: &[na::Point3<f32>],
+	classifications: &[Classification],
+) -> Option<(na::Point3<f32>, na::Vector3<f32>, f32)> {
+	let points = points
+		.iter()
+		.zip(classifications)
+		.filter_map(|(&p, &c)| (c == Classification::Trunk).then_some(p))
+		.collect::<Vec<_>>();
+	if points.len() < 2 {
+		return None;
+	}
+
+	let mean = points
+		.iter()
+		.fold(na::Vector3::zeros(), |sum, p| sum + p.coords)
+		/ points.len() as f32;
+	let mut covariance = na::Matrix3::zeros();
+	for p in points.iter() {
+		let d = p.coords - mean;
+		covariance += d * d.transpose();
+	}
+
+	let eigen = covariance.symmetric_eigen();
+	let (idx, _) = eigen.eigenvalues.argmax();
+	let mut direction = eigen.eigenvectors.column(idx).into_owned().normalize();
+	if direction.y < 0.0 {
+		direction = -direction;
+	}
+
+	let (mut min, mut max) = (f32::MAX, f32::MIN);
+	for p in points.iter() {
+		let t = (p.coords - mean).dot(&direction);
+		min = min.min(t);
+		max = max.max(t);
+	}
+
+	Some((
+		na::Point3::from(mean + direction * min),
+		direction,
+		max - min,
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Trunk with a circular cross section, bent sideways in the middle.
+	fn bent_trunk(bend: f32) -> Vec<na::Point3<f32>> {
+		let mut points = Vec::new();
+		for layer in 0..150 {
+			let y = layer as f32 * 0.02;
+			let offset = bend * (y / 3.0 * std::f32::consts::PI).sin();
+			for i in 0..20 {
+				let angle = i as f32 / 20.0 * std::f32::consts::TAU;
+				points.push(na::point![offset + 0.2 * angle.cos(), y, 0.2 * angle.sin()]);
+			}
+		}
+		points
+	}
+
+	#[test]
+	fn stem_form() {
+		let points = bent_trunk(0.0);
+		let classifications = vec![Classification::Trunk; points.len()];
+		let stem = StemForm::new(&points, &classifications, 0.5);
+		assert!(stem.lean < 0.1);
+		assert!(stem.sweep < 0.001);
+
+		let points = bent_trunk(0.1);
+		let stem = StemForm::new(&points, &classifications, 0.5);
+		assert!(stem.lean < 0.1);
+		assert!((0.05..0.1).contains(&stem.sweep), "{}", stem.sweep);
+	}
+
+	#[test]
+	fn stem_form_without_trunk() {
+		let points = bent_trunk(0.0);
+		let classifications = vec![Classification::Crown; points.len()];
+		let stem = StemForm::new(&points, &classifications, 0.5);
+		assert_eq!(stem.lean, 0.0);
+		assert_eq!(stem.sweep, 0.0);
+	}
+}
@@ -11,7 +11,56 @@ use nalgebra as na;
 use rayon::prelude::*;
 use std::io::{Read, Seek, SeekFrom};
 
-use crate::{environment, Error};
+use crate::{environment, id, Error};
+
+/// Axis pointing up in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpAxis {
+	#[default]
+	Z,
+	Y,
+}
+
+/// Orientation of the source coordinates, the points are converted to y up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Convention {
+	pub up: UpAxis,
+	pub left_handed: bool,
+}
+
+impl Convention {
+	/// Convert a position from the source.
+	pub fn map(self, v: na::Vector3<f64>) -> na::Vector3<f64> {
+		match (self.up, self.left_handed) {
+			(UpAxis::Z, false) => na::vector![v.x, v.z, -v.y],
+			(UpAxis::Z, true) => na::vector![v.x, v.z, v.y],
+			(UpAxis::Y, false) => na::vector![v.x, v.y, v.z],
+			(UpAxis::Y, true) => na::vector![v.x, v.y, -v.z],
+		}
+	}
+
+	/// Returns `true` if the convention changed.
+	pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+		let previous = *self;
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			ui.label("Up Axis");
+			egui::ComboBox::from_id_salt(id!())
+				.selected_text(format!("{:?}", self.up))
+				.width(ui.available_width())
+				.show_ui(ui, |ui| {
+					for v in [UpAxis::Z, UpAxis::Y] {
+						ui.selectable_value(&mut self.up, v, format!("{:?}", v));
+					}
+				});
+			ui.end_row();
+
+			ui.label("Left-handed");
+			ui.checkbox(&mut self.left_handed, "");
+			ui.end_row();
+		});
+		*self != previous
+	}
+}
 
 /// Load `.laz` files with multiple threads
 pub struct Laz {
@@ -21,6 +70,7 @@ pub struct Laz {
 	center: na::Point3<f64>,
 	offset: na::Point3<f64>,
 	scale: na::Point3<f64>,
+	convention: Convention,
 
 	source: environment::Source,
 
@@ -33,8 +83,9 @@ impl Laz {
 	pub fn new(
 		source: environment::Source,
 		center: Option<na::Point3<f64>>,
+		convention: Convention,
 	) -> Result<Self, Error> {
-		let mut reader = source.reader();
+		let mut reader = source.reader()?;
 
 		let header = Header::new(&mut reader)?;
 
@@ -69,8 +120,9 @@ impl Laz {
 			header.z_scale_factor,
 		);
 		let offset = na::Point3::new(header.x_offset, header.y_offset, header.z_offset);
-		let min = na::Point3::new(header.min_x, header.min_z, -header.max_y);
-		let max = na::Point3::new(header.max_x, header.max_z, -header.min_y);
+		let a = convention.map(na::vector![header.min_x, header.min_y, header.min_z]);
+		let b = convention.map(na::vector![header.max_x, header.max_y, header.max_z]);
+		let (min, max) = (a.inf(&b).into(), a.sup(&b).into());
 		let center = center.unwrap_or(na::center(&min, &max));
 
 		reader.seek(SeekFrom::Start(header.header_size as u64))?;
@@ -114,6 +166,7 @@ impl Laz {
 			point_length,
 			scale,
 			offset,
+			convention,
 			center,
 			source,
 
@@ -137,6 +190,10 @@ impl Laz {
 			.map_init(
 				|| self.source.reader(),
 				|file, (s, l)| {
+					// the error is not clone, so it is recreated for every chunk
+					let file = file
+						.as_mut()
+						.map_err(|err| std::io::Error::new(err.kind(), err.to_string()))?;
 					let slice = if l == 0 {
 						Vec::new()
 					} else {
@@ -170,6 +227,7 @@ impl Laz {
 
 						offset: self.offset,
 						scale: self.scale,
+						convention: self.convention,
 						center: self.center,
 					};
 					cb(chunk)
@@ -186,6 +244,7 @@ pub struct Chunk {
 	point_length: usize,
 	offset: na::Point3<f64>,
 	scale: na::Point3<f64>,
+	convention: Convention,
 	center: na::Point3<f64>,
 }
 
@@ -207,7 +266,7 @@ impl Chunk {
 		self.current += self.point_length;
 		let v = self.offset
 			+ na::vector![x as f64, y as f64, z as f64].zip_map(&self.scale.coords, |a, b| a * b);
-		(na::vector![v.x, v.z, -v.y] - self.center.coords)
+		(self.convention.map(v.coords) - self.center.coords)
 			.map(|x| x as f32)
 			.into()
 	}
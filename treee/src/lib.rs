@@ -1,11 +1,18 @@
 mod calculations;
 mod camera;
 mod empty;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+mod histogram;
 mod interactive;
 mod laz;
 mod loading;
 mod program;
+mod progress;
 mod segmenting;
+mod units;
 
 use nalgebra as na;
 use program::Event;
@@ -13,8 +20,68 @@ use program::Program;
 use std::io::{Read, Seek, Write};
 use std::sync::Arc;
 
+pub use calculations::CalculationSettings;
+#[cfg(not(target_arch = "wasm32"))]
+pub use headless::{export_npy, extract_traits, session_traits};
+pub use interactive::batch::SegmentTraits;
+pub use laz::{Convention, UpAxis};
+
+/// Settings for the start of the program.
+#[derive(Debug, Clone)]
+pub struct Settings {
+	/// Source to open after the start.
+	pub input: Option<std::path::PathBuf>,
+	/// Default maximum distance for the segmentation.
+	pub segmenting_max_distance: f32,
+	/// Default parameters for the calculations of a single segment.
+	pub calculation: CalculationSettings,
+	/// Maximum segments calculated at the same time, all threads if `None`.
+	pub calculation_threads: Option<usize>,
+	/// Seed for the random number generators, random if `None`.
+	pub seed: Option<u64>,
+	/// Directory for diagrams of the segmentation slices.
+	pub debug_svg: Option<std::path::PathBuf>,
+	/// Orientation of the loaded `.laz` files.
+	pub convention: laz::Convention,
+	/// Address to load points from a network stream.
+	#[cfg(feature = "stream")]
+	pub stream: Option<String>,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			input: None,
+			segmenting_max_distance: segmenting::DEFAULT_MAX_DISTANCE,
+			calculation: CalculationSettings::default(),
+			calculation_threads: None,
+			seed: None,
+			debug_svg: None,
+			convention: laz::Convention::default(),
+			#[cfg(feature = "stream")]
+			stream: None,
+		}
+	}
+}
+
+impl Settings {
+	/// Random number generator from the seed.
+	pub fn rng(&self) -> rand::rngs::StdRng {
+		use rand::SeedableRng;
+
+		match self.seed {
+			Some(seed) => rand::rngs::StdRng::seed_from_u64(seed),
+			None => rand::rngs::StdRng::from_entropy(),
+		}
+	}
+}
+
 /// Main loop
-pub async fn try_main(error_handler: fn(Error)) {
+///
+/// The event loop must be created before anything else, macOS requires it to be the first
+/// interaction with `NSApplication`. The window, GPU and file dialogs are only created from
+/// within the event loop.
+pub async fn try_main(settings: Settings, error_handler: fn(Error)) {
 	let event_loop = match winit::event_loop::EventLoop::with_user_event().build() {
 		Ok(v) => v,
 		Err(err) => return error_handler(err.into()),
@@ -24,6 +91,7 @@ pub async fn try_main(error_handler: fn(Error)) {
 
 	let app = App {
 		state: State::Starting(proxy),
+		settings,
 		error_handler,
 	};
 
@@ -65,13 +133,23 @@ pub enum Error {
 	#[error("Corrupt file")]
 	CorruptFile,
 
+	#[error("Unsupported file format '{0}'")]
+	FileFormat(String),
+
+	#[error("Unsupported save version {0}, update treee to load it")]
+	SaveVersion(u32),
+
 	#[error(transparent)]
 	Bincode(#[from] bincode::Error),
+
+	#[error(transparent)]
+	Json(#[from] serde_json::Error),
 }
 
 /// App state
 struct App {
 	state: State,
+	settings: Settings,
 	error_handler: fn(Error),
 }
 
@@ -97,13 +175,17 @@ impl winit::application::ApplicationHandler<State> for App {
 	fn resumed(&mut self, event_loop: &EventLoop) {
 		match self.state {
 			State::Starting(ref proxy) => {
-				let window = event_loop
-					.create_window(
-						winit::window::Window::default_attributes()
-							.with_title("Treee")
-							.with_min_inner_size(winit::dpi::LogicalSize { width: 10, height: 10 }),
-					)
-					.unwrap();
+				let window = match event_loop.create_window(
+					winit::window::Window::default_attributes()
+						.with_title("Treee")
+						.with_min_inner_size(winit::dpi::LogicalSize { width: 10, height: 10 }),
+				) {
+					Ok(window) => window,
+					Err(err) => {
+						event_loop.exit();
+						return (self.error_handler)(err.into());
+					},
+				};
 
 				#[cfg(target_arch = "wasm32")]
 				{
@@ -157,10 +239,16 @@ impl winit::application::ApplicationHandler<State> for App {
 
 			match event {
 				winit::event::WindowEvent::CloseRequested => {
+					// keep the autosave for edits which are not in an explicit save
+					if let program::World::Interactive(interactive) = &program.world {
+						if interactive.is_saved() {
+							environment::remove_autosave();
+						}
+					}
 					event_loop.exit();
 				},
 				winit::event::WindowEvent::RedrawRequested => {
-					program.render();
+					program.render()?;
 				},
 				winit::event::WindowEvent::Resized(_size) => {
 					program.resized();
@@ -186,10 +274,17 @@ impl winit::application::ApplicationHandler<State> for App {
 						winit::event::MouseScrollDelta::LineDelta(_, y) => -y,
 						winit::event::MouseScrollDelta::PixelDelta(pos) => -pos.y as f32 / 100.0,
 					};
-					program
-						.display_settings
-						.camera
-						.scroll(delta, &program.state);
+					program.mouse_wheel(delta);
+				},
+				winit::event::WindowEvent::Touch(touch) => {
+					let position = na::point![touch.location.x as f32, touch.location.y as f32];
+					program.touch(touch.id, touch.phase, position);
+				},
+				winit::event::WindowEvent::PinchGesture { delta, .. } => {
+					program.pinch(delta as f32);
+				},
+				winit::event::WindowEvent::PanGesture { delta, .. } => {
+					program.pan(na::vector![delta.x, delta.y]);
 				},
 				_ => {},
 			}
@@ -220,7 +315,7 @@ impl App {
 				#[cfg(not(target_arch = "wasm32"))]
 				{
 					use pollster::FutureExt;
-					let app = match Program::new(window).block_on() {
+					let app = match Program::new(window, self.settings.clone()).block_on() {
 						Ok(program) => State::Running(program),
 						Err(err) => return (self.error_handler)(err),
 					};
@@ -232,8 +327,9 @@ impl App {
 				#[cfg(target_arch = "wasm32")]
 				{
 					let error_handler = self.error_handler;
+					let settings = self.settings.clone();
 					wasm_bindgen_futures::spawn_local(async move {
-						let app = match Program::new(window).await {
+						let app = match Program::new(window, settings).await {
 							Ok(program) => State::Running(program),
 							Err(err) => return error_handler(err),
 						};
@@ -261,7 +357,86 @@ pub mod environment {
 	use std::{fs::File, io::BufWriter};
 
 	use super::*;
+	use crate::program::ViewerSettings;
+
+	/// Directory for the persistent files.
+	fn config_path() -> Option<std::path::PathBuf> {
+		let base = std::env::var_os("APPDATA")
+			.or_else(|| std::env::var_os("XDG_CONFIG_HOME"))
+			.map(std::path::PathBuf::from)
+			.or_else(|| {
+				std::env::var_os("HOME").map(|home| std::path::PathBuf::from(home).join(".config"))
+			})?;
+		Some(base.join("treee"))
+	}
+
+	/// Location for the persistent settings.
+	fn settings_path() -> Option<std::path::PathBuf> {
+		Some(config_path()?.join("settings.json"))
+	}
+
+	/// Location for the autosave of the interactive session.
+	fn autosave_path() -> Option<std::path::PathBuf> {
+		Some(config_path()?.join("session.ipc.autosave"))
+	}
+
+	/// Autosave left behind by a session that did not exit cleanly.
+	pub fn autosave_source() -> Option<Source> {
+		let path = autosave_path()?;
+		path.is_file().then(|| Source::new(path))
+	}
+
+	/// Write the autosave in the background without a file dialog.
+	pub fn autosave(action: impl FnOnce(Saver) + Send + 'static) {
+		let Some(path) = autosave_path() else {
+			return;
+		};
+		rayon::spawn(move || {
+			if let Some(parent) = path.parent() {
+				_ = std::fs::create_dir_all(parent);
+			}
+			// write to a temporary file first, a crash while saving keeps the previous autosave
+			let temporary = path.with_extension("tmp");
+			match File::create(&temporary) {
+				Ok(file) => action(Saver {
+					file: BufWriter::new(file),
+					rename: Some((temporary, path)),
+				}),
+				Err(err) => log::warn!("Failed to autosave: {}", err),
+			}
+		});
+	}
+
+	/// Remove the autosave after an explicit save or if the restore is declined.
+	pub fn remove_autosave() {
+		if let Some(path) = autosave_path() {
+			_ = std::fs::remove_file(path);
+		}
+	}
+
+	/// Load the persistent settings, `None` if they were never saved.
+	pub fn load_settings() -> Option<ViewerSettings> {
+		let file = File::open(settings_path()?).ok()?;
+		serde_json::from_reader(std::io::BufReader::new(file)).ok()
+	}
 
+	/// Save the persistent settings.
+	pub fn save_settings(settings: &ViewerSettings) {
+		let Some(path) = settings_path() else {
+			return;
+		};
+		if let Some(parent) = path.parent() {
+			_ = std::fs::create_dir_all(parent);
+		}
+		let res = File::create(&path)
+			.map_err(serde_json::Error::io)
+			.and_then(|file| serde_json::to_writer_pretty(BufWriter::new(file), settings));
+		if let Err(err) = res {
+			log::warn!("Failed to save settings: {}", err);
+		}
+	}
+
+	#[derive(Debug, Clone)]
 	pub struct Source {
 		path: std::path::PathBuf,
 	}
@@ -276,17 +451,34 @@ pub mod environment {
 			}
 		}
 
-		pub fn reader(&self) -> impl Read + Seek + '_ {
-			std::io::BufReader::new(std::fs::File::open(&self.path).unwrap())
+		/// Source from a known path.
+		pub fn new(path: std::path::PathBuf) -> Self {
+			Self { path }
+		}
+
+		pub fn reader(&self) -> std::io::Result<impl Read + Seek + '_> {
+			Ok(std::io::BufReader::new(std::fs::File::open(&self.path)?))
 		}
 
-		pub fn extension(&self) -> &str {
-			self.path.extension().unwrap().to_str().unwrap()
+		pub fn extension(&self) -> Result<&str, Error> {
+			self.path
+				.extension()
+				.and_then(|ext| ext.to_str())
+				.ok_or_else(|| Error::FileFormat(self.path.display().to_string()))
+		}
+
+		/// File name without the extension.
+		pub fn name(&self) -> String {
+			self.path
+				.file_stem()
+				.map_or_else(String::new, |name| name.to_string_lossy().into_owned())
 		}
 	}
 
 	pub struct Saver {
 		file: BufWriter<File>,
+		/// Move the written file from the first to the second path.
+		rename: Option<(std::path::PathBuf, std::path::PathBuf)>,
 	}
 
 	impl Saver {
@@ -302,7 +494,7 @@ pub mod environment {
 					.save_file();
 				if let Some(path) = path {
 					let file = BufWriter::new(File::create(path).unwrap());
-					action(Self { file });
+					action(Self { file, rename: None });
 				}
 			});
 		}
@@ -311,14 +503,56 @@ pub mod environment {
 			&mut self.file
 		}
 
-		pub fn save(self) {}
+		pub fn save(self) {
+			let Some((from, to)) = self.rename else {
+				return;
+			};
+			let res = self
+				.file
+				.into_inner()
+				.map_err(|err| err.into_error())
+				.and_then(|_| std::fs::rename(from, to));
+			if let Err(err) = res {
+				log::warn!("Failed to save: {}", err);
+			}
+		}
 	}
 }
 
 #[cfg(target_arch = "wasm32")]
 pub mod environment {
 	use super::*;
+	use crate::program::ViewerSettings;
 
+	/// Key in the local storage for the persistent settings.
+	const SETTINGS_KEY: &str = "treee-settings";
+
+	/// Load the persistent settings, `None` if they were never saved.
+	pub fn load_settings() -> Option<ViewerSettings> {
+		let storage = web_sys::window()?.local_storage().ok()??;
+		let data = storage.get_item(SETTINGS_KEY).ok()??;
+		serde_json::from_str(&data).ok()
+	}
+
+	/// Save the persistent settings.
+	pub fn save_settings(settings: &ViewerSettings) {
+		let Some(storage) = web_sys::window().and_then(|win| win.local_storage().ok().flatten())
+		else {
+			return;
+		};
+		let Ok(data) = serde_json::to_string(settings) else {
+			return;
+		};
+		_ = storage.set_item(SETTINGS_KEY, &data);
+	}
+
+	/// Autosaves are not supported in the browser.
+	pub fn autosave(_action: impl FnOnce(Saver) + Send + 'static) {}
+
+	/// Autosaves are not supported in the browser.
+	pub fn remove_autosave() {}
+
+	#[derive(Debug, Clone)]
 	pub struct Source {
 		data: Vec<u8>,
 		name: String,
@@ -344,12 +578,23 @@ pub mod environment {
 			Self { data, name }
 		}
 
-		pub fn reader<'a>(&'a self) -> impl Read + Seek + 'a {
-			std::io::Cursor::new(&self.data)
+		pub fn reader<'a>(&'a self) -> std::io::Result<impl Read + Seek + 'a> {
+			Ok(std::io::Cursor::new(&self.data))
+		}
+
+		pub fn extension(&self) -> Result<&str, Error> {
+			self.name
+				.rsplit_once(".")
+				.map(|(_, ext)| ext)
+				.ok_or_else(|| Error::FileFormat(self.name.clone()))
 		}
 
-		pub fn extension(&self) -> &str {
-			self.name.split(".").last().unwrap()
+		/// File name without the extension.
+		pub fn name(&self) -> String {
+			self.name
+				.rsplit_once(".")
+				.map_or(self.name.as_str(), |(name, _)| name)
+				.into()
 		}
 	}
 
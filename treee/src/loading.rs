@@ -8,7 +8,14 @@ use std::{
 	},
 };
 
-use crate::{environment, laz::Laz, program::Event, Error};
+use crate::{
+	calculations::{self, OutlierSettings},
+	environment, id,
+	laz::{Convention, Laz},
+	program::Event,
+	progress::Progress,
+	Error,
+};
 
 /// Phase to load points from a source.
 #[derive(Debug)]
@@ -17,6 +24,23 @@ pub struct Loading {
 	pub max: na::Point3<f32>,
 	pub total: usize,
 	pub shared: Arc<Shared>,
+	progress: Progress,
+	/// Orientation of the sources, changes reload all sources.
+	pub convention: Convention,
+	sources: Vec<environment::Source>,
+	reload: bool,
+
+	outlier_settings: OutlierSettings,
+	cleaning: Option<crossbeam::channel::Receiver<usize>>,
+	removed: Option<usize>,
+	/// Rotation applied to level the ground.
+	pub leveling: Option<na::UnitQuaternion<f32>>,
+	/// Estimated tilt in degrees, `Some(None)` if no ground plane was found.
+	tilt: Option<Option<f32>>,
+	leveling_task: Option<crossbeam::channel::Receiver<Leveled>>,
+	/// Address and state of a running network stream.
+	#[cfg(feature = "stream")]
+	stream: Option<(String, Arc<Stream>)>,
 }
 
 /// Shared state for the workers
@@ -24,22 +48,58 @@ pub struct Loading {
 pub struct Shared {
 	progress: AtomicUsize,
 	sender: crossbeam::channel::Sender<Event>,
-	pub world_offset: na::Point3<f64>,
+	/// Set from the first chunk of a network stream.
+	pub world_offset: Mutex<na::Point3<f64>>,
 	pub slices: Mutex<HashMap<isize, Vec<na::Point3<f32>>>>,
 }
 
+/// Seed for the ground plane estimate, the same points give the same rotation.
+const LEVELING_SEED: u64 = 0;
+
+/// Result of the leveling worker.
+#[derive(Debug)]
+struct Leveled {
+	/// Applied rotation, `None` after undoing the leveling.
+	rotation: Option<na::UnitQuaternion<f32>>,
+	/// Bounds of the rotated points, `None` if no ground plane was found.
+	bounds: Option<(na::Point3<f32>, na::Point3<f32>)>,
+}
+
+/// Points in a chunk from a network stream.
+#[cfg(feature = "stream")]
+const STREAM_CHUNK_POINTS: usize = 16 * 1024;
+
+/// Bytes for a single point in a network stream.
+#[cfg(feature = "stream")]
+const STREAM_POINT_SIZE: usize = 3 * std::mem::size_of::<f32>();
+
+/// State of a network stream, shared with the worker.
+#[cfg(feature = "stream")]
+#[derive(Debug, Default)]
+pub struct Stream {
+	/// Set by the worker after the connection is closed.
+	finished: std::sync::atomic::AtomicBool,
+	/// Set to close the connection.
+	stop: std::sync::atomic::AtomicBool,
+	points: AtomicUsize,
+	bounds: Mutex<Option<(na::Point3<f32>, na::Point3<f32>)>>,
+}
+
 impl Loading {
-	pub fn new(source: environment::Source) -> (Self, crossbeam::channel::Receiver<Event>) {
+	pub fn new(
+		source: environment::Source,
+		convention: Convention,
+	) -> Result<(Self, crossbeam::channel::Receiver<Event>), Error> {
 		let (sender, receiver) = crossbeam::channel::bounded(8);
 
-		let laz = Laz::new(source, None).unwrap();
+		let laz = Laz::new(source.clone(), None, convention)?;
 		let (min, max) = (laz.min, laz.max);
 		let total = laz.total();
 
 		let shared = Shared {
 			slices: Mutex::new(HashMap::new()),
 			progress: AtomicUsize::new(0),
-			world_offset: laz.world_offset,
+			world_offset: Mutex::new(laz.world_offset),
 			sender,
 		};
 		let shared = Arc::new(shared);
@@ -47,25 +107,222 @@ impl Loading {
 		spawn_load_worker(laz, shared.clone());
 
 		_ = shared.sender.send(Event::ClearPointClouds);
-		let loading = Self { min, max, total, shared };
+		let loading = Self {
+			min,
+			max,
+			total,
+			shared,
+			progress: Progress::new("points"),
+			convention,
+			sources: vec![source],
+			reload: false,
+			outlier_settings: OutlierSettings::default(),
+			cleaning: None,
+			removed: None,
+			leveling: None,
+			tilt: None,
+			leveling_task: None,
+			#[cfg(feature = "stream")]
+			stream: None,
+		};
+
+		Ok((loading, receiver))
+	}
+
+	/// Load all sources again after the convention changed.
+	///
+	/// Returns the receiver for the new events, the workers of the old loading stop with the old receiver.
+	pub fn reload(&mut self) -> Result<Option<crossbeam::channel::Receiver<Event>>, Error> {
+		if self.reload.not() {
+			return Ok(None);
+		}
+		self.reload = false;
+		let mut sources = std::mem::take(&mut self.sources).into_iter();
+		let Some(first) = sources.next() else {
+			return Ok(None);
+		};
+		let (mut loading, receiver) = Self::new(first, self.convention)?;
+		for source in sources {
+			loading.add(source)?;
+		}
+		loading.outlier_settings = self.outlier_settings;
+		*self = loading;
+		Ok(Some(receiver))
+	}
+
+	/// Load points from a TCP connection until it is closed or stopped.
+	///
+	/// Every point is three little-endian `f32` for x, y and z with z up, relative to the origin.
+	#[cfg(feature = "stream")]
+	pub fn stream(address: String) -> (Self, crossbeam::channel::Receiver<Event>) {
+		// bounded to slow down the connection if the rendering can not keep up
+		let (sender, receiver) = crossbeam::channel::bounded(8);
+
+		let shared = Arc::new(Shared {
+			slices: Mutex::new(HashMap::new()),
+			progress: AtomicUsize::new(0),
+			world_offset: Mutex::new(na::Point3::origin()),
+			sender,
+		});
+		let stream = Arc::new(Stream::default());
+		spawn_stream_worker(address.clone(), shared.clone(), stream.clone());
+
+		_ = shared.sender.send(Event::ClearPointClouds);
+		let loading = Self {
+			min: na::Point3::origin(),
+			max: na::Point3::origin(),
+			total: 0,
+			shared,
+			progress: Progress::new("points"),
+			convention: Convention::default(),
+			sources: Vec::new(),
+			reload: false,
+			outlier_settings: OutlierSettings::default(),
+			cleaning: None,
+			removed: None,
+			leveling: None,
+			tilt: None,
+			leveling_task: None,
+			stream: Some((address, stream)),
+		};
 
 		(loading, receiver)
 	}
 
 	pub fn ui(&mut self, ui: &mut egui::Ui) {
+		#[cfg(feature = "stream")]
+		if let Some((address, stream)) = &self.stream {
+			if stream.finished.load(Ordering::Relaxed).not() {
+				ui.add_sized(
+					[ui.available_width(), 0.0],
+					egui::Label::new(format!("Streaming from {}", address)),
+				);
+				ui.add_sized(
+					[ui.available_width(), 0.0],
+					egui::Label::new(format!("{} points", stream.points.load(Ordering::Relaxed))),
+				);
+				if ui
+					.add_sized([ui.available_width(), 0.0], egui::Button::new("Stop"))
+					.clicked()
+				{
+					stream.stop.store(true, Ordering::Relaxed);
+				}
+				ui.ctx().request_repaint();
+				return;
+			}
+			if let Some((min, max)) = *stream.bounds.lock().unwrap() {
+				self.min = min;
+				self.max = max;
+			}
+			self.stream = None;
+		}
+
 		let progress = self.shared.progress.load(Ordering::Relaxed);
 		if progress < self.total {
-			let progress = progress as f32 / self.total as f32;
-			ui.add(egui::ProgressBar::new(progress).rounding(egui::Rounding::ZERO));
+			self.progress.ui(ui, progress, self.total);
+		} else if let Some(cleaning) = &self.cleaning {
+			match cleaning.try_recv() {
+				Ok(removed) => {
+					self.removed = Some(removed);
+					self.cleaning = None;
+				},
+				Err(crossbeam::channel::TryRecvError::Empty) => {
+					ui.add_sized(
+						[ui.available_width(), 0.0],
+						egui::Label::new("Removing Outliers"),
+					);
+					ui.vertical_centered(|ui| ui.spinner());
+				},
+				Err(crossbeam::channel::TryRecvError::Disconnected) => self.cleaning = None,
+			}
+		} else if let Some(task) = &self.leveling_task {
+			match task.try_recv() {
+				Ok(leveled) => {
+					if let Some((min, max)) = leveled.bounds {
+						self.min = min;
+						self.max = max;
+						self.tilt = leveled
+							.rotation
+							.map(|r| Some(r.angle().to_degrees()))
+							.or(self.tilt);
+					} else {
+						self.tilt = Some(None);
+					}
+					self.leveling = leveled.rotation;
+					self.leveling_task = None;
+				},
+				Err(crossbeam::channel::TryRecvError::Empty) => {
+					ui.add_sized(
+						[ui.available_width(), 0.0],
+						egui::Label::new("Leveling Ground"),
+					);
+					ui.vertical_centered(|ui| ui.spinner());
+				},
+				Err(crossbeam::channel::TryRecvError::Disconnected) => self.leveling_task = None,
+			}
 		} else {
+			// added points would not be leveled
 			#[cfg(not(target_arch = "wasm32"))]
 			if ui
-				.add_sized([ui.available_width(), 0.0], egui::Button::new("Add"))
+				.add_enabled_ui(self.leveling.is_none(), |ui| {
+					ui.add_sized([ui.available_width(), 0.0], egui::Button::new("Add"))
+				})
+				.inner
 				.clicked()
 			{
 				environment::Source::start(&self.shared.sender);
 			}
 
+			if self.sources.is_empty().not() {
+				ui.separator();
+				ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Orientation"));
+				self.reload |= self.convention.ui(ui);
+			}
+
+			ui.separator();
+			ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Ground"));
+			egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+				ui.label("Level");
+				let mut level = self.leveling.is_some();
+				if ui.checkbox(&mut level, "").changed() {
+					let (sender, reciever) = crossbeam::channel::bounded(1);
+					spawn_leveling_worker(self.leveling, self.shared.clone(), sender);
+					self.leveling_task = Some(reciever);
+				}
+				ui.end_row();
+
+				if let Some(tilt) = self.tilt {
+					ui.label("Tilt");
+					ui.label(match tilt {
+						Some(tilt) => format!("{:.2}°", tilt),
+						None => "No ground found".into(),
+					});
+					ui.end_row();
+				}
+			});
+
+			ui.separator();
+			ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Outliers"));
+			self.outlier_settings.ui(ui);
+			if ui
+				.add_sized(
+					[ui.available_width(), 0.0],
+					egui::Button::new("Remove Outliers"),
+				)
+				.clicked()
+			{
+				let (sender, reciever) = crossbeam::channel::bounded(1);
+				spawn_outlier_worker(self.outlier_settings, self.shared.clone(), sender);
+				self.cleaning = Some(reciever);
+			}
+			if let Some(removed) = self.removed {
+				ui.add_sized(
+					[ui.available_width(), 0.0],
+					egui::Label::new(format!("Removed {} points", removed)),
+				);
+			}
+
+			ui.separator();
 			if ui
 				.add_sized([ui.available_width(), 0.0], egui::Button::new("Continue"))
 				.clicked()
@@ -76,8 +333,12 @@ impl Loading {
 	}
 
 	/// Add new source to load.
-	pub fn add(&mut self, source: environment::Source) {
-		let laz = Laz::new(source, Some(self.shared.world_offset)).unwrap();
+	pub fn add(&mut self, source: environment::Source) -> Result<(), Error> {
+		let laz = Laz::new(
+			source.clone(),
+			Some(*self.shared.world_offset.lock().unwrap()),
+			self.convention,
+		)?;
 		for dim in 0..3 {
 			self.min[dim] = self.min[dim].min(laz.min[dim]);
 			self.max[dim] = self.max[dim].max(laz.max[dim]);
@@ -85,6 +346,8 @@ impl Loading {
 		self.total = laz.total();
 		self.shared.progress.store(0, Ordering::Relaxed);
 		spawn_load_worker(laz, self.shared.clone());
+		self.sources.push(source);
+		Ok(())
 	}
 }
 
@@ -113,3 +376,204 @@ fn spawn_load_worker(laz: Laz, shared: Arc<Shared>) {
 		});
 	});
 }
+
+#[cfg(feature = "stream")]
+fn spawn_stream_worker(address: String, shared: Arc<Shared>, stream: Arc<Stream>) {
+	// blocks for the whole connection, not in the thread pool
+	std::thread::spawn(move || {
+		if let Err(err) = read_stream(&address, &shared, &stream) {
+			log::warn!("Stream from {} failed: {}", address, err);
+		}
+		stream.finished.store(true, Ordering::Relaxed);
+	});
+}
+
+/// Read points until the connection is closed or the stream is stopped.
+///
+/// Points are sent as chunks, after the buffer is full or the sender pauses.
+#[cfg(feature = "stream")]
+fn read_stream(address: &str, shared: &Shared, stream: &Stream) -> Result<(), std::io::Error> {
+	use std::io::{ErrorKind, Read};
+
+	let mut connection = std::net::TcpStream::connect(address)?;
+	// wake up regularly to check for a stop
+	connection.set_read_timeout(Some(std::time::Duration::from_millis(100)))?;
+
+	let mut buffer = vec![0; STREAM_CHUNK_POINTS * STREAM_POINT_SIZE];
+	let mut filled = 0;
+	while stream.stop.load(Ordering::Relaxed).not() {
+		let closed = match connection.read(&mut buffer[filled..]) {
+			Ok(0) => true,
+			Ok(read) => {
+				filled += read;
+				if filled < buffer.len() {
+					continue;
+				}
+				false
+			},
+			Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => false,
+			Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+			Err(err) => return Err(err),
+		};
+
+		let complete = filled - filled % STREAM_POINT_SIZE;
+		if complete > 0 && send_stream_chunk(&buffer[..complete], shared, stream).not() {
+			break;
+		}
+		buffer.copy_within(complete..filled, 0);
+		filled -= complete;
+		if closed {
+			return Ok(());
+		}
+	}
+
+	// points received before the stop
+	let complete = filled - filled % STREAM_POINT_SIZE;
+	send_stream_chunk(&buffer[..complete], shared, stream);
+	Ok(())
+}
+
+/// Add the points in `data` to the slices and send them for rendering.
+/// Returns `false` if the loading is no longer active.
+///
+/// The first point is used as the world offset, the points are relative to it.
+#[cfg(feature = "stream")]
+fn send_stream_chunk(data: &[u8], shared: &Shared, stream: &Stream) -> bool {
+	let points = data
+		.chunks_exact(STREAM_POINT_SIZE)
+		.map(|point| {
+			let [x, y, z] = [0, 1, 2]
+				.map(|i| f32::from_le_bytes(point[i * 4..(i + 1) * 4].try_into().unwrap()));
+			na::point![x, z, -y].cast::<f64>()
+		})
+		.filter(|p| p.iter().all(|v| v.is_finite()))
+		.collect::<Vec<_>>();
+	let Some(&first) = points.first() else {
+		return true;
+	};
+
+	let mut bounds = stream.bounds.lock().unwrap();
+	let offset = {
+		let mut offset = shared.world_offset.lock().unwrap();
+		if bounds.is_none() {
+			*offset = first;
+		}
+		*offset
+	};
+	let points = points
+		.into_iter()
+		.map(|p| (p - offset).cast::<f32>().into())
+		.collect::<Vec<na::Point3<f32>>>();
+	let (mut min, mut max) = bounds.unwrap_or((points[0], points[0]));
+	for p in points.iter() {
+		min = min.inf(p);
+		max = max.sup(p);
+	}
+	*bounds = Some((min, max));
+	drop(bounds);
+
+	let mut slices = shared.slices.lock().unwrap();
+	for &p in points.iter() {
+		let idx = p.y.floor() as isize;
+		slices.entry(idx).or_default().push(p);
+	}
+	drop(slices);
+
+	stream.points.fetch_add(points.len(), Ordering::Relaxed);
+	let segment = vec![0; points.len()];
+	shared
+		.sender
+		.send(Event::PointCloud { idx: None, data: points, segment })
+		.is_ok()
+}
+
+/// Level the ground or undo the applied rotation and send the result.
+fn spawn_leveling_worker(
+	applied: Option<na::UnitQuaternion<f32>>,
+	shared: Arc<Shared>,
+	result: crossbeam::channel::Sender<Leveled>,
+) {
+	rayon::spawn(move || {
+		use rand::SeedableRng;
+
+		let mut slices = shared.slices.lock().unwrap();
+		let rotation = match applied {
+			Some(rotation) => Some(rotation.inverse()),
+			None => calculations::ground_leveling(
+				slices.values().flatten().copied(),
+				&mut rand::rngs::StdRng::seed_from_u64(LEVELING_SEED),
+			),
+		};
+		let Some(rotation) = rotation else {
+			_ = result.send(Leveled { rotation: None, bounds: None });
+			return;
+		};
+
+		let points = slices
+			.drain()
+			.flat_map(|(_, slice)| slice)
+			.map(|p| rotation * p)
+			.collect::<Vec<_>>();
+		let mut bounds = (na::Point3::origin(), na::Point3::origin());
+		if let Some(&first) = points.first() {
+			bounds = points
+				.iter()
+				.fold((first, first), |(min, max), p| (min.inf(p), max.sup(p)));
+		}
+		for p in points {
+			let idx = p.y.floor() as isize;
+			slices.entry(idx).or_default().push(p);
+		}
+		let chunks = slices.values().cloned().collect::<Vec<_>>();
+		drop(slices);
+
+		_ = shared.sender.send(Event::ClearPointClouds);
+		for points in chunks {
+			let segment = vec![0; points.len()];
+			_ = shared
+				.sender
+				.send(Event::PointCloud { idx: None, data: points, segment });
+		}
+		_ = result.send(Leveled {
+			rotation: applied.is_none().then_some(rotation),
+			bounds: Some(bounds),
+		});
+	});
+}
+
+/// Remove outliers from the loaded points and send the removed count.
+fn spawn_outlier_worker(
+	settings: OutlierSettings,
+	shared: Arc<Shared>,
+	result: crossbeam::channel::Sender<usize>,
+) {
+	rayon::spawn(move || {
+		let mut slices = shared.slices.lock().unwrap();
+		let points = slices
+			.drain()
+			.flat_map(|(_, slice)| slice)
+			.collect::<Vec<_>>();
+		let outliers = settings.outliers(&points);
+
+		let mut removed = 0;
+		for (p, outlier) in points.into_iter().zip(outliers) {
+			if outlier {
+				removed += 1;
+				continue;
+			}
+			let idx = p.y.floor() as isize;
+			slices.entry(idx).or_default().push(p);
+		}
+		let chunks = slices.values().cloned().collect::<Vec<_>>();
+		drop(slices);
+
+		_ = shared.sender.send(Event::ClearPointClouds);
+		for points in chunks {
+			let segment = vec![0; points.len()];
+			_ = shared
+				.sender
+				.send(Event::PointCloud { idx: None, data: points, segment });
+		}
+		_ = result.send(removed);
+	});
+}
@@ -1,6 +1,120 @@
 #![windows_subsystem = "windows"]
 
+use clap::Parser;
 use pollster::FutureExt;
+use std::path::PathBuf;
+
+/// Command line arguments.
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Cli {
+	#[command(subcommand)]
+	command: Option<Command>,
+
+	/// Point cloud (`.las`, `.laz`) or saved session (`.ipc`) to open.
+	input: Option<PathBuf>,
+
+	/// Default maximum distance between segments.
+	#[arg(long)]
+	segmenting_max_distance: Option<f32>,
+
+	/// Default height above the ground to measure the trunk diameter.
+	#[arg(long)]
+	trunk_diameter_height: Option<f32>,
+
+	/// Default slice width for the expansion.
+	#[arg(long)]
+	slice_width: Option<f32>,
+
+	/// Default neighbors used to calculate the curvature.
+	#[arg(long)]
+	neighbors_count: Option<usize>,
+
+	/// Search the neighbors for the curvature approximately, faster for large point clouds.
+	#[arg(long)]
+	approximate_neighbors: bool,
+
+	/// Maximum segments calculated at the same time, limits the memory usage.
+	#[arg(long)]
+	calculation_threads: Option<usize>,
+
+	/// Seed for the random number generators, the same seed gives the same segmentation.
+	#[arg(long)]
+	seed: Option<u64>,
+
+	/// Save a diagram for every segmentation slice in the directory.
+	#[arg(long)]
+	debug_svg: Option<PathBuf>,
+
+	/// The y axis points up in the loaded files instead of the z axis.
+	#[arg(long)]
+	y_up: bool,
+
+	/// The coordinates in the loaded files are left-handed.
+	#[arg(long)]
+	left_handed: bool,
+
+	/// Load points from a TCP stream (`host:port`), three little-endian `f32` per point.
+	#[cfg(feature = "stream")]
+	#[arg(long)]
+	stream: Option<String>,
+}
+
+/// Commands without a window.
+#[derive(clap::Subcommand)]
+enum Command {
+	/// Calculate the traits for all segments in a saved session.
+	Traits {
+		/// Saved session (`.ipc`).
+		input: PathBuf,
+		/// Output file (`.csv` or `.json`).
+		output: PathBuf,
+	},
+	/// Write all points in a saved session as a NumPy array.
+	Npy {
+		/// Saved session (`.ipc`).
+		input: PathBuf,
+		/// Output file (`.npy`).
+		output: PathBuf,
+	},
+}
+
+impl Cli {
+	/// Defaults with the overrides from the command line.
+	fn settings(self) -> treee::Settings {
+		let mut settings = treee::Settings {
+			input: self.input,
+			calculation_threads: self.calculation_threads,
+			seed: self.seed,
+			debug_svg: self.debug_svg,
+			convention: treee::Convention {
+				up: if self.y_up {
+					treee::UpAxis::Y
+				} else {
+					treee::UpAxis::Z
+				},
+				left_handed: self.left_handed,
+			},
+			#[cfg(feature = "stream")]
+			stream: self.stream,
+			..Default::default()
+		};
+		if let Some(distance) = self.segmenting_max_distance {
+			settings.segmenting_max_distance = distance;
+		}
+		if let Some(height) = self.trunk_diameter_height {
+			settings.calculation.trunk_diameter_height = height;
+		}
+		if let Some(width) = self.slice_width {
+			settings.calculation.slice_width = width;
+		}
+		if let Some(count) = self.neighbors_count {
+			settings.calculation.neighbors_count = count;
+		}
+		settings.calculation.approximate_neighbors = self.approximate_neighbors;
+		settings
+	}
+}
 
 /// Main function for native OS.
 fn main() {
@@ -8,5 +122,20 @@ fn main() {
 		.with_level(log::LevelFilter::Warn)
 		.init()
 		.unwrap();
-	treee::try_main(|err| println!("Error: {}", err)).block_on();
+	let mut cli = Cli::parse();
+	match cli.command.take() {
+		Some(Command::Traits { input, output }) => {
+			if let Err(err) = treee::extract_traits(&input, &output) {
+				println!("Error: {}", err);
+				std::process::exit(1);
+			}
+		},
+		Some(Command::Npy { input, output }) => {
+			if let Err(err) = treee::export_npy(&input, &output) {
+				println!("Error: {}", err);
+				std::process::exit(1);
+			}
+		},
+		None => treee::try_main(cli.settings(), |err| println!("Error: {}", err)).block_on(),
+	}
 }
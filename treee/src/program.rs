@@ -1,16 +1,25 @@
 use crate::calculations::Calculations;
-use crate::camera::Camera;
+use crate::camera::{self, Camera};
 use crate::empty::Empty;
 use crate::interactive::{self, DisplayModus, Interactive, DELETED_INDEX};
 use crate::loading::Loading;
-use crate::segmenting::{Segmenting, DEFAULT_MAX_DISTANCE};
-use crate::{environment, id, Error};
+use crate::segmenting::{SegmentationSettings, Segmenting};
+use crate::{environment, id, Error, Settings};
 use nalgebra as na;
 use render::PointCloudPass;
 use std::collections::HashMap;
 use std::ops::Not;
 use std::sync::Arc;
 
+/// Minimal time in seconds between hover updates.
+const HOVER_INTERVAL: f32 = 0.05;
+
+/// Highest selectable samples per pixel.
+const MAX_SAMPLE_COUNT: u32 = 4;
+
+/// Maximal number of segments uploaded to the GPU each frame.
+const UPLOADS_PER_FRAME: usize = 8;
+
 /// Events from the current phase to the progam.
 pub enum Event {
 	Done,
@@ -21,10 +30,17 @@ pub enum Event {
 		segment: Vec<u32>,
 	},
 	RemovePointCloud(u32),
+	/// Explicit save of the segments after the number of edits finished.
+	Saved(u64),
 	Load(environment::Source),
+	/// Load points from a network stream at the address.
+	#[cfg(feature = "stream")]
+	Stream(String),
 	Segmented {
 		segments: HashMap<u32, Vec<na::Point3<f32>>>,
 		world_offset: na::Point3<f64>,
+		leveling: Option<na::UnitQuaternion<f32>>,
+		settings: SegmentationSettings,
 	},
 }
 
@@ -38,7 +54,12 @@ pub struct Program {
 	pub keyboard: input::Keyboard,
 
 	mouse: input::Mouse,
+	touches: input::Touches,
+	#[cfg(feature = "gamepad")]
+	gamepad: Option<crate::gamepad::Gamepad>,
 	mouse_start: Option<na::Point2<f32>>,
+	box_start: Option<na::Point2<f32>>,
+	last_hover: web_time::Instant,
 	time: Time,
 	paused: bool,
 
@@ -51,19 +72,63 @@ pub struct Program {
 	pub lines_state: render::LinesState,
 
 	pub display_settings: DisplaySettings,
+	/// Last written persistent settings, changes are written after the pointer is released.
+	saved_settings: ViewerSettings,
 
 	chunks: HashMap<u32, Chunk>,
+	settings: Settings,
 }
 
 /// Single chunk to render.
 struct Chunk {
 	point_cloud: render::PointCloud,
 	segment: render::PointCloudProperty,
+	/// Uniform color value to use instead of the segment.
+	color: Option<(u32, render::PointCloudProperty)>,
+	/// Data to upload again after the device was lost, `None` if the world sends it again.
+	kept: Option<(Vec<na::Point3<f32>>, Vec<u32>)>,
 }
 
 impl Chunk {
+	fn new(state: &render::State, data: &[na::Point3<f32>], segment: &[u32]) -> Self {
+		Self {
+			point_cloud: render::PointCloud::new(state, data),
+			segment: render::PointCloudProperty::new(state, segment),
+			color: None,
+			kept: None,
+		}
+	}
+
+	/// Chunk that keeps the data to upload it again after the device was lost.
+	fn kept(state: &render::State, data: Vec<na::Point3<f32>>, segment: Vec<u32>) -> Self {
+		let chunk = Self::new(state, &data, &segment);
+		Self { kept: Some((data, segment)), ..chunk }
+	}
+
+	/// Upload the kept data to the new device, `None` if the world sends the chunk again.
+	fn recreate(self, state: &render::State) -> Option<Self> {
+		let (data, segment) = self.kept?;
+		Some(Self::kept(state, data, segment))
+	}
+
 	pub fn render<'a>(&'a self, point_cloud_pass: &mut PointCloudPass<'a>) {
-		self.point_cloud.render(point_cloud_pass, &self.segment);
+		let property = match &self.color {
+			Some((_, color)) => color,
+			None => &self.segment,
+		};
+		self.point_cloud.render(point_cloud_pass, property);
+	}
+
+	/// Update the uniform color value.
+	fn set_color(&mut self, color: Option<u32>, state: &render::State) -> bool {
+		if self.color.as_ref().map(|&(value, _)| value) == color {
+			return false;
+		}
+		self.color = color.map(|value| {
+			let data = vec![value; self.point_cloud.instances as usize];
+			(value, render::PointCloudProperty::new(state, &data))
+		});
+		true
 	}
 }
 
@@ -73,11 +138,32 @@ pub struct DisplaySettings {
 	pub point_cloud_environment: render::PointCloudEnvironment,
 	pub lookup_render: render::Lookup,
 	pub lookup_white: render::Lookup,
+	/// Dimmed lookup for the other segments in the segment view.
+	pub lookup_context: render::Lookup,
+	/// Brightness for the other segments in the segment view, hidden if `0.0`.
+	pub context_brightness: f32,
 	pub lookup: Lookup,
 	pub camera: Camera,
+	/// Requested samples per pixel, applied before the next frame.
+	pub sample_count: u32,
+	/// Colors for ground, trunk and crown.
+	pub class_colors: [na::Point3<f32>; 3],
+	pub lookup_classes: render::Lookup,
+	/// Maximal points on the GPU for the segments, unlimited if `0`.
+	pub resident_points: usize,
+	/// Segments with fewer points are not shown or selectable, except the active segments.
+	pub min_segment_points: usize,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	PartialEq,
+	Eq,
+	serde::Serialize,
+	serde::Deserialize
+)]
 pub enum Lookup {
 	Turbo,
 	Warm,
@@ -86,17 +172,104 @@ pub enum Lookup {
 
 impl Lookup {
 	pub fn render(self, state: &render::State) -> render::Lookup {
+		self.render_dimmed(state, 1.0)
+	}
+
+	/// Lookup with darker colors.
+	pub fn render_dimmed(self, state: &render::State, brightness: f32) -> render::Lookup {
 		let bytes = match self {
 			Self::Turbo => include_bytes!("../assets/grad_turbo.png").as_slice(),
 			Self::Warm => include_bytes!("../assets/grad_warm.png").as_slice(),
 			Self::White => include_bytes!("../assets/white.png").as_slice(),
 		};
-		render::Lookup::new_png(state, bytes, u32::MAX)
+		render::Lookup::new_png_dimmed(state, bytes, u32::MAX, brightness)
+	}
+}
+
+/// Visual settings persisted between sessions.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ViewerSettings {
+	pub background: na::Point3<f32>,
+	pub point_size: f32,
+	pub lookup: Lookup,
+	pub eye_dome_radius: f32,
+	pub eye_dome_sample_count: u32,
+	pub camera_animated: bool,
+	pub camera_animation_duration: f32,
+	pub context_brightness: f32,
+	pub sample_count: u32,
+	pub class_colors: [na::Point3<f32>; 3],
+}
+
+impl Default for ViewerSettings {
+	fn default() -> Self {
+		Self {
+			background: na::point![0.3, 0.5, 0.7],
+			point_size: 0.1,
+			lookup: Lookup::Turbo,
+			eye_dome_radius: 1.0,
+			eye_dome_sample_count: 4,
+			camera_animated: true,
+			camera_animation_duration: camera::DEFAULT_ANIMATION_DURATION,
+			context_brightness: 0.0,
+			sample_count: 1,
+			class_colors: [
+				na::point![0.27, 0.42, 0.89],
+				na::point![0.2, 0.95, 0.6],
+				na::point![0.98, 0.5, 0.13],
+			],
+		}
 	}
 }
 
 impl DisplaySettings {
-	pub fn ui(&mut self, ui: &mut egui::Ui, state: &render::State) {
+	/// Segment with `points` is below the minimum size.
+	pub fn hidden(&self, points: usize) -> bool {
+		points < self.min_segment_points
+	}
+
+	/// Current persistent settings.
+	pub fn settings(&self, eye_dome: &render::EyeDome) -> ViewerSettings {
+		ViewerSettings {
+			background: self.background,
+			point_size: self.point_cloud_environment.scale,
+			lookup: self.lookup,
+			eye_dome_radius: eye_dome.radius,
+			eye_dome_sample_count: eye_dome.sample_count,
+			camera_animated: self.camera.animated,
+			camera_animation_duration: self.camera.animation_duration,
+			context_brightness: self.context_brightness,
+			sample_count: self.sample_count,
+			class_colors: self.class_colors,
+		}
+	}
+
+	/// Apply persistent settings.
+	pub fn apply(
+		&mut self,
+		settings: ViewerSettings,
+		state: &render::State,
+		eye_dome: &mut render::EyeDome,
+	) {
+		self.background = settings.background;
+		self.point_cloud_environment.scale = settings.point_size;
+		self.point_cloud_environment.update(state);
+		self.lookup = settings.lookup;
+		self.lookup_render = self.lookup.render(state);
+		self.context_brightness = settings.context_brightness;
+		self.lookup_context = self.lookup.render_dimmed(state, self.context_brightness);
+		eye_dome.radius = settings.eye_dome_radius;
+		eye_dome.sample_count = settings.eye_dome_sample_count;
+		eye_dome.update_settings(state);
+		self.camera.animated = settings.camera_animated;
+		self.camera.animation_duration = settings.camera_animation_duration;
+		self.sample_count = settings.sample_count;
+		self.class_colors = settings.class_colors;
+		self.lookup_classes = classes_lookup(self.class_colors, state);
+	}
+
+	pub fn ui(&mut self, ui: &mut egui::Ui, state: &render::State, eye_dome: &mut render::EyeDome) {
 		ui.add_sized(
 			[ui.available_width(), 0.0],
 			egui::Label::new("Display Settings"),
@@ -130,10 +303,157 @@ impl DisplaySettings {
 				self.lookup_render = self.lookup.render(state);
 			}
 			ui.end_row();
+
+			ui.label("Context");
+			let response = ui
+				.add(egui::Slider::new(&mut self.context_brightness, 0.0..=1.0).max_decimals(2))
+				.on_hover_text("Brightness of the other segments in the segment view");
+			if changed || response.changed() {
+				self.lookup_context = self.lookup.render_dimmed(state, self.context_brightness);
+			}
+			ui.end_row();
+
+			let mut changed = false;
+			ui.label("Eye Dome");
+			changed |= ui
+				.add(
+					egui::Slider::new(&mut eye_dome.radius, 1.0..=8.0)
+						.max_decimals(1)
+						.suffix("px"),
+				)
+				.changed();
+			ui.end_row();
+
+			ui.label("Samples");
+			changed |= ui
+				.add(egui::Slider::new(&mut eye_dome.sample_count, 4..=32))
+				.changed();
+			if changed {
+				eye_dome.update_settings(state);
+			}
+			ui.end_row();
+
+			ui.label("Anti-Aliasing");
+			let name = |count: u32| match count {
+				1 => "Off".to_string(),
+				count => format!("{}x", count),
+			};
+			egui::ComboBox::from_id_salt(id!())
+				.selected_text(name(self.sample_count))
+				.width(ui.available_width())
+				.show_ui(ui, |ui| {
+					for &count in state.supported_sample_counts.iter() {
+						if count <= MAX_SAMPLE_COUNT {
+							ui.selectable_value(&mut self.sample_count, count, name(count));
+						}
+					}
+				});
+			ui.end_row();
+
+			ui.label("Background");
+			let mut background = self.background.coords.data.0[0];
+			if ui.color_edit_button_rgb(&mut background).changed() {
+				self.background = background.into();
+			}
+			ui.end_row();
+
+			ui.label("Resident");
+			ui.add(
+				egui::Slider::new(&mut self.resident_points, 0..=100_000_000)
+					.logarithmic(true)
+					.custom_formatter(|value, _| match value as usize {
+						0 => "All".into(),
+						value => format!("{:.1}M", value as f64 / 1_000_000.0),
+					}),
+			)
+			.on_hover_text(
+				"Maximal points on the GPU, the segments nearest to the camera are shown",
+			);
+			ui.end_row();
+
+			ui.label("Min Points");
+			ui.add(egui::Slider::new(&mut self.min_segment_points, 0..=100_000).logarithmic(true))
+				.on_hover_text("Hide smaller segments from the view and the selection");
+			ui.end_row();
+
+			ui.label("Transitions");
+			ui.horizontal(|ui| {
+				ui.checkbox(&mut self.camera.animated, "");
+				ui.add_enabled(
+					self.camera.animated,
+					egui::Slider::new(&mut self.camera.animation_duration, 0.1..=2.0)
+						.max_decimals(1)
+						.suffix("s"),
+				);
+			});
+			ui.end_row();
+		});
+		if ui
+			.add_sized(
+				[ui.available_width(), 0.0],
+				egui::Button::new("Reset to defaults"),
+			)
+			.clicked()
+		{
+			self.apply(ViewerSettings::default(), state, eye_dome);
+		}
+	}
+
+	/// Legend with editable colors for the classifications.
+	pub fn classes_ui(&mut self, ui: &mut egui::Ui, state: &render::State) {
+		let mut changed = false;
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			for (color, name) in self
+				.class_colors
+				.iter_mut()
+				.zip(["Ground", "Trunk", "Crown"])
+			{
+				let mut rgb = color.coords.data.0[0];
+				if ui.color_edit_button_rgb(&mut rgb).changed() {
+					*color = rgb.into();
+					changed = true;
+				}
+				ui.label(name);
+				ui.end_row();
+			}
+			let [r, g, b, _] = NOISE_COLOR;
+			ui.add_enabled_ui(false, |ui| ui.color_edit_button_srgb(&mut [r, g, b]));
+			ui.label("Noise");
+			ui.end_row();
 		});
+		if changed {
+			self.lookup_classes = classes_lookup(self.class_colors, state);
+		}
 	}
 }
 
+/// Color for noise points, not editable.
+const NOISE_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+/// Lookup for the classification values of `DisplayData`.
+///
+/// Ground, trunk, crown and noise are mapped to 1/8, 3/8, 6/8 and 1.
+fn classes_lookup(colors: [na::Point3<f32>; 3], state: &render::State) -> render::Lookup {
+	let [ground, trunk, crown] = colors.map(|color| {
+		let [r, g, b] = color.coords.data.0[0].map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8);
+		[r, g, b, 255]
+	});
+	render::Lookup::new_colors(
+		state,
+		&[
+			ground,
+			ground,
+			trunk,
+			trunk,
+			trunk,
+			crown,
+			crown,
+			NOISE_COLOR,
+		],
+		u32::MAX,
+	)
+}
+
 /// Current phase.
 pub enum World {
 	Empty(Empty),
@@ -144,8 +464,11 @@ pub enum World {
 }
 
 impl Program {
-	pub async fn new(window: Arc<winit::window::Window>) -> Result<Self, Error> {
-		let (state, window) = render::State::new(window).await?;
+	pub async fn new(
+		window: Arc<winit::window::Window>,
+		settings: Settings,
+	) -> Result<Self, Error> {
+		let (mut state, mut window) = render::State::new(window).await?;
 
 		#[cfg(not(target_arch = "wasm32"))]
 		window.set_window_icon(include_bytes!("../assets/png/tree-fill-big.png"));
@@ -153,11 +476,28 @@ impl Program {
 		#[cfg(windows)]
 		window.set_taskbar_icon(include_bytes!("../assets/png/tree-fill-big.png"));
 
-		let point_cloud_environment = render::PointCloudEnvironment::new(&state, 0, u32::MAX, 0.1);
+		let viewer_settings = environment::load_settings().unwrap_or_default();
+		if state
+			.supported_sample_counts
+			.contains(&viewer_settings.sample_count)
+		{
+			state.sample_count = viewer_settings.sample_count;
+			window.resized(&state);
+		}
+
+		let point_cloud_environment =
+			render::PointCloudEnvironment::new(&state, 0, u32::MAX, viewer_settings.point_size);
 		let point_cloud_state = render::PointCloudState::new(&state);
 		let lines_state = render::LinesState::new(&state);
 		let camera = Camera::new(&state, window.get_aspect());
-		let eye_dome = render::EyeDome::new(&state, window.config(), window.depth_texture(), 0.7);
+		let eye_dome = render::EyeDome::new(
+			&state,
+			window.config(),
+			window.depth_texture(),
+			0.7,
+			viewer_settings.eye_dome_radius,
+			viewer_settings.eye_dome_sample_count,
+		);
 
 		let egui = egui::Context::default();
 		let egui_winit = egui_winit::State::new(
@@ -171,11 +511,22 @@ impl Program {
 		let egui_wgpu =
 			egui_wgpu::Renderer::new(state.device(), state.surface_format(), None, 1, false);
 
-		let lookup = Lookup::Turbo;
+		let lookup = viewer_settings.lookup;
 		let lookup_render = lookup.render(&state);
+		let sample_count = state.sample_count;
+		let lookup_classes = classes_lookup(viewer_settings.class_colors, &state);
+		let lookup_context = lookup.render_dimmed(&state, viewer_settings.context_brightness);
 		let white_lookup = Lookup::White.render(&state);
 
 		let (empty, receiver) = Empty::new();
+		#[cfg(not(target_arch = "wasm32"))]
+		if let Some(path) = &settings.input {
+			empty.open(environment::Source::new(path.clone()));
+		}
+		#[cfg(feature = "stream")]
+		if let Some(address) = &settings.stream {
+			empty.stream(address.clone());
+		}
 		Ok(Self {
 			world: World::Empty(empty),
 			receiver,
@@ -189,7 +540,12 @@ impl Program {
 			paused: false,
 			keyboard: input::Keyboard::new(),
 			mouse: input::Mouse::new(),
+			touches: input::Touches::new(),
+			#[cfg(feature = "gamepad")]
+			gamepad: crate::gamepad::Gamepad::new(),
 			mouse_start: None,
+			box_start: None,
+			last_hover: web_time::Instant::now(),
 			time: Time::new(),
 
 			eye_dome,
@@ -197,21 +553,33 @@ impl Program {
 			lines_state,
 
 			display_settings: DisplaySettings {
-				background: na::point![0.3, 0.5, 0.7],
+				background: viewer_settings.background,
 				point_cloud_environment,
 				lookup,
 				lookup_render,
 				lookup_white: white_lookup,
+				lookup_context,
+				context_brightness: viewer_settings.context_brightness,
 				camera,
+				sample_count,
+				class_colors: viewer_settings.class_colors,
+				lookup_classes,
+				resident_points: 0,
+				min_segment_points: 0,
 			},
 
+			saved_settings: viewer_settings,
 			chunks: HashMap::new(),
+			settings,
 		})
 	}
 
-	pub fn render(&mut self) {
+	pub fn render(&mut self) -> Result<(), Error> {
 		if self.paused {
-			return;
+			return Ok(());
+		}
+		if self.state.is_lost() {
+			self.recover()?;
 		}
 		// handle ui
 		let raw_input = self.egui_winit.take_egui_input(&self.window);
@@ -239,11 +607,17 @@ impl Program {
 								.add_sized([ui.available_width(), 0.0], egui::Button::new("Close"))
 								.clicked()
 							{
+								if let World::Interactive(interactive) = &self.world {
+									if interactive.is_saved() {
+										environment::remove_autosave();
+									}
+								}
 								let (empty, reciever) = Empty::new();
 								self.world = World::Empty(empty);
 								self.receiver = reciever;
 							}
-							self.display_settings.ui(ui, &self.state);
+							self.display_settings
+								.ui(ui, &self.state, &mut self.eye_dome);
 						}
 						ui.separator();
 						match &mut self.world {
@@ -251,16 +625,51 @@ impl Program {
 							World::Loading(loading) => loading.ui(ui),
 							World::Segmenting(segmenting) => segmenting.ui(ui),
 							World::Calculations(calculations) => calculations.ui(ui),
-							World::Interactive(interactive) => interactive.ui(ui),
+							World::Interactive(interactive) => {
+								let viewer = self.display_settings.settings(&self.eye_dome);
+								interactive.ui(
+									ui,
+									&mut self.display_settings.camera,
+									viewer,
+									&self.state,
+								)
+							},
 						}
 					});
 				});
 			if let World::Interactive(interactive) = &mut self.world {
-				interactive.extra_ui(ctx, &self.state);
+				interactive.extra_ui(ctx, &mut self.display_settings, &self.state);
+				interactive.inventory_ui(ctx, &mut self.display_settings.camera, &self.state);
+			}
+			if let Some(start) = self.box_start {
+				let scale = ctx.pixels_per_point();
+				let end = self.mouse.position();
+				let rect = egui::Rect::from_two_pos(
+					egui::pos2(start.x / scale, start.y / scale),
+					egui::pos2(end.x / scale, end.y / scale),
+				);
+				ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, id!()))
+					.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::WHITE));
 			}
 		});
 		self.egui_winit
 			.handle_platform_output(&self.window, full_output.platform_output);
+		if let World::Loading(loading) = &mut self.world {
+			if let Some(receiver) = loading.reload()? {
+				self.receiver = receiver;
+			}
+		}
+		// not while a slider is dragged
+		let settings = self.display_settings.settings(&self.eye_dome);
+		if settings != self.saved_settings
+			&& self.egui.input(|input| input.pointer.any_down()).not()
+		{
+			environment::save_settings(&settings);
+			self.saved_settings = settings;
+		}
+		if self.display_settings.sample_count != self.state.sample_count {
+			self.update_sample_count();
+		}
 
 		let paint_jobs = self
 			.egui
@@ -315,7 +724,7 @@ impl Program {
 					self.eye_dome.render(&mut render_pass);
 					self.egui_wgpu.render(&mut render_pass, &paint_jobs, screen);
 					drop(render_pass);
-				});
+				})?;
 			},
 			&World::Calculations(Calculations { .. }) => {
 				self.window.render(&self.state, |context| {
@@ -346,7 +755,7 @@ impl Program {
 					self.eye_dome.render(&mut render_pass);
 					self.egui_wgpu.render(&mut render_pass, &paint_jobs, screen);
 					drop(render_pass);
-				});
+				})?;
 			},
 			World::Interactive(interactive) => {
 				self.window.render(&self.state, |context| {
@@ -374,29 +783,68 @@ impl Program {
 							point_cloud_pass.lookup(&self.display_settings.lookup_render);
 						}
 					}
+					// skip the segments of hidden projects
+					let visible = |idx: &u32| interactive.sources.visible(*idx);
 					if let interactive::Modus::View(ref view) = interactive.modus {
+						if self.display_settings.context_brightness > 0.0 {
+							point_cloud_pass.lookup(&self.display_settings.lookup_context);
+							for (_, chunk) in self.chunks.iter().filter(|&(idx, _)| {
+								*idx != DELETED_INDEX && *idx != view.idx && visible(idx)
+							}) {
+								chunk.render(point_cloud_pass);
+							}
+							point_cloud_pass.lookup(&self.display_settings.lookup_render);
+						}
+						if view.display_modus == DisplayModus::Classification {
+							point_cloud_pass.lookup(&self.display_settings.lookup_classes);
+						}
 						let property = match view.display_modus {
 							DisplayModus::Classification => &view.display_data.classification,
 							DisplayModus::Curve => &view.display_data.curve,
 							DisplayModus::Expansion => &view.display_data.expansion,
 							DisplayModus::Height => &view.display_data.height,
+							DisplayModus::Distance => match &view.compare.result {
+								Some(result) => &result.property,
+								None => &view.display_data.classification,
+							},
 						};
 						view.cloud.render(point_cloud_pass, property);
 					} else {
-						for (_, chunk) in
-							self.chunks.iter().filter(|&(&idx, _)| idx != DELETED_INDEX)
-						{
+						let selected = match &interactive.modus {
+							interactive::Modus::MultiCombine(selected) => Some(selected),
+							_ => None,
+						};
+						let is_selected = |idx: &u32| {
+							interactive.hovered == Some(*idx)
+								|| selected.is_some_and(|selected| selected.contains(idx))
+						};
+						let (highlighted, normal) = self
+							.chunks
+							.iter()
+							.filter(|&(idx, _)| *idx != DELETED_INDEX && visible(idx))
+							.partition::<Vec<_>, _>(|&(idx, _)| is_selected(idx));
+						for (_, chunk) in normal {
 							chunk.render(point_cloud_pass);
 						}
+						if highlighted.is_empty().not() {
+							point_cloud_pass.lookup(&self.display_settings.lookup_white);
+							for (_, chunk) in highlighted {
+								chunk.render(point_cloud_pass);
+							}
+							point_cloud_pass.lookup(&self.display_settings.lookup_render);
+						}
 					}
 
+					let mut lines_pass = self
+						.lines_state
+						.render(&mut render_pass, self.display_settings.camera.gpu());
 					if let interactive::Modus::View(view) = &interactive.modus {
-						let mut lines_pass = self
-							.lines_state
-							.render(&mut render_pass, self.display_settings.camera.gpu());
-
 						view.hull.render(&view.cloud, &mut lines_pass);
 						view.trunk_axis.render(&mut lines_pass);
+						view.centerline.render(&mut lines_pass);
+					}
+					if let Some(brush) = &interactive.brush {
+						brush.render(lines_pass);
 					}
 					drop(render_pass);
 
@@ -404,12 +852,16 @@ impl Program {
 					self.eye_dome.render(&mut render_pass);
 					self.egui_wgpu.render(&mut render_pass, &paint_jobs, screen);
 					drop(render_pass);
-				});
+				})?;
 			},
 		}
+		Ok(())
 	}
 
 	pub fn update(&mut self) -> Result<(), Error> {
+		if self.state.is_lost() {
+			self.recover()?;
+		}
 		let delta = self.time.elapsed().as_secs_f32();
 		let mut direction = na::vector![0.0, 0.0];
 		if self.keyboard.pressed(input::KeyCode::KeyD)
@@ -449,58 +901,112 @@ impl Program {
 				.camera
 				.vertical_movement(delta * 10.0, &self.state);
 		}
+		if self.display_settings.camera.animate(delta, &self.state) {
+			self.window.request_redraw();
+		}
+		#[cfg(feature = "gamepad")]
+		if let Some(gamepad) = &mut self.gamepad {
+			if gamepad.update(delta, &mut self.display_settings.camera, &self.state) {
+				self.window.request_redraw();
+			}
+		}
 
 		// handle events in the queue at this moment
 		let mut work = self.receiver.len();
 		while let Ok(event) = self.receiver.try_recv() {
 			match event {
-				Event::Load(source) => match source.extension() {
+				Event::Load(source) => match source.extension()? {
 					"laz" | "las" => match &mut self.world {
-						World::Loading(loading) => loading.add(source),
+						World::Loading(loading) => loading.add(source)?,
 						World::Interactive(interactive) => {
-							interactive.add_points(source)?;
+							interactive.add_points(source, self.settings.convention)?;
 						},
 						_ => {
-							let (loading, receiver) = Loading::new(source);
+							let (loading, receiver) =
+								Loading::new(source, self.settings.convention)?;
 							self.world = World::Loading(loading);
 							self.receiver = receiver;
 						},
 					},
 
-					"ipc" => match &mut self.world {
+					"ipc" | "autosave" => match &mut self.world {
 						World::Interactive(interactive) => {
 							interactive.add(source)?;
 						},
 						_ => {
-							let (interactive, receiver) = Interactive::load(source)?;
+							let (mut interactive, receiver, session) = Interactive::load(source)?;
+							interactive.calculation_settings = self.settings.calculation;
+							interactive.rng = self.settings.rng();
+							if let Some(camera) = session.camera {
+								self.display_settings.camera.restore(camera, &self.state);
+							}
+							if let Some(viewer) = session.viewer {
+								self.display_settings.apply(
+									viewer,
+									&self.state,
+									&mut self.eye_dome,
+								);
+							}
+							if let Some(idx) = session
+								.view
+								.filter(|idx| interactive.segments.contains_key(idx))
+							{
+								interactive.open_view(idx, &self.state);
+							}
 							self.world = World::Interactive(interactive);
 							self.receiver = receiver;
 						},
 					},
-					_ => panic!("invalid file format"),
+					extension => return Err(Error::FileFormat(extension.into())),
+				},
+				#[cfg(feature = "stream")]
+				Event::Stream(address) => {
+					let (loading, receiver) = Loading::stream(address);
+					self.world = World::Loading(loading);
+					self.receiver = receiver;
 				},
 				Event::Done => {
 					match std::mem::replace(&mut self.world, World::Empty(Empty::new().0)) {
 						World::Loading(loading) => {
-							let (segmenting, receiver) =
-								Segmenting::new(loading, DEFAULT_MAX_DISTANCE);
+							self.settings.convention = loading.convention;
+							let (segmenting, receiver) = Segmenting::new(
+								loading,
+								self.settings.segmenting_max_distance,
+								self.settings.seed.unwrap_or_else(rand::random),
+								self.settings.debug_svg.clone(),
+							);
 							self.world = World::Segmenting(segmenting);
 							self.receiver = receiver;
 						},
 						World::Calculations(calculations) => {
 							let shared = Arc::try_unwrap(calculations.shared).unwrap();
-							let (interactive, receiver) = Interactive::new(
+							let (mut interactive, receiver) = Interactive::new(
 								shared.segments.into_inner().unwrap(),
 								calculations.world_offset,
 							);
+							interactive.calculation_settings = self.settings.calculation;
+							interactive.provenance.segmentation = Some(calculations.segmentation);
+							interactive.leveling = calculations.leveling;
+							interactive.rng = self.settings.rng();
 							self.world = World::Interactive(interactive);
 							self.receiver = receiver;
 						},
 						world => self.world = world,
 					};
 				},
-				Event::Segmented { segments, world_offset } => {
-					let (calculations, receiver) = Calculations::new(segments, world_offset);
+				Event::Segmented {
+					segments,
+					world_offset,
+					leveling,
+					settings,
+				} => {
+					let threads = self
+						.settings
+						.calculation_threads
+						.unwrap_or_else(rayon::current_num_threads);
+					let (mut calculations, receiver) =
+						Calculations::new(segments, world_offset, settings, threads);
+					calculations.leveling = leveling;
 					self.world = World::Calculations(calculations);
 					self.receiver = receiver;
 				},
@@ -510,6 +1016,9 @@ impl Program {
 				},
 
 				Event::PointCloud { idx, data, segment } => {
+					if let (Some(idx), World::Interactive(interactive)) = (idx, &mut self.world) {
+						interactive.inventory.invalidate(idx);
+					}
 					let idx = idx.unwrap_or_else(|| {
 						let mut idx = rand::random();
 						while self.chunks.contains_key(&idx) {
@@ -517,17 +1026,39 @@ impl Program {
 						}
 						idx
 					});
-					self.chunks.insert(
-						idx,
-						Chunk {
-							point_cloud: render::PointCloud::new(&self.state, &data),
-							segment: render::PointCloudProperty::new(&self.state, &segment),
+					// over the limit, loaded again if it is near enough
+					let evicted = match &self.world {
+						World::Interactive(interactive)
+							if self.display_settings.resident_points > 0
+								&& interactive.is_active(idx).not() =>
+						{
+							self.chunks.remove(&idx);
+							let resident = self
+								.chunks
+								.values()
+								.map(|chunk| chunk.point_cloud.instances as usize)
+								.sum::<usize>();
+							resident + data.len() > self.display_settings.resident_points
 						},
-					);
+						_ => false,
+					};
+					if evicted.not() {
+						// the interactive world sends its segments again after the device was lost
+						let chunk = match &self.world {
+							World::Interactive(_) => Chunk::new(&self.state, &data, &segment),
+							_ => Chunk::kept(&self.state, data, segment),
+						};
+						self.chunks.insert(idx, chunk);
+					}
 				},
 				Event::RemovePointCloud(idx) => {
 					self.chunks.remove(&idx);
 				},
+				Event::Saved(changes) => {
+					if let World::Interactive(interactive) = &mut self.world {
+						interactive.saved(changes);
+					}
+				},
 			}
 			work -= 1;
 			if work == 0 {
@@ -535,9 +1066,164 @@ impl Program {
 			}
 		}
 
+		if let World::Interactive(interactive) = &mut self.world {
+			interactive.autosave(
+				&self.display_settings.camera,
+				self.display_settings.settings(&self.eye_dome),
+			);
+		}
+
+		if self.update_residency() {
+			self.window.request_redraw();
+		}
+
+		let colors = match &self.world {
+			World::Interactive(interactive) => interactive.segment_colors(),
+			_ => None,
+		};
+		let mut changed = false;
+		for (idx, chunk) in self.chunks.iter_mut() {
+			let color = colors.as_ref().and_then(|colors| colors.get(idx).copied());
+			changed |= chunk.set_color(color, &self.state);
+		}
+		if changed {
+			self.window.request_redraw();
+		}
+
+		Ok(())
+	}
+
+	/// Keep the segments nearest to the camera on the GPU, limited by the resident points.
+	/// Active segments are always resident. Returns `true` if any segment changed.
+	fn update_residency(&mut self) -> bool {
+		let World::Interactive(interactive) = &self.world else {
+			return false;
+		};
+		let budget = self.display_settings.resident_points;
+		let position = self.display_settings.camera.position();
+
+		let mut order = interactive
+			.segments
+			.iter()
+			.filter(|(_, segment)| segment.points.is_empty().not())
+			.map(|(&idx, segment)| {
+				let center = na::center(&segment.min, &segment.max);
+				let distance = (center - position).norm_squared();
+				(interactive.is_active(idx).not(), distance, idx)
+			})
+			.collect::<Vec<_>>();
+		order.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+		let mut used = 0;
+		let mut uploads = 0;
+		let mut changed = false;
+		for (inactive, _, idx) in order {
+			let segment = &interactive.segments[&idx];
+			let hidden = inactive && self.display_settings.hidden(segment.points.len());
+			if hidden.not() {
+				used += segment.points.len();
+			}
+			let resident = hidden.not() && (budget == 0 || used <= budget || inactive.not());
+			match (resident, self.chunks.contains_key(&idx)) {
+				(true, false) if uploads < UPLOADS_PER_FRAME => {
+					let data = vec![idx; segment.points.len()];
+					self.chunks
+						.insert(idx, Chunk::new(&self.state, &segment.points, &data));
+					uploads += 1;
+					changed = true;
+				},
+				(true, false) => changed = true,
+				(false, true) => {
+					self.chunks.remove(&idx);
+					changed = true;
+				},
+				_ => {},
+			}
+		}
+		changed
+	}
+
+	/// Create a new device after the device was lost and all GPU resources again.
+	///
+	/// The segments of the Interactive phase are sent again, the other phases show their points
+	/// again as they are sent.
+	#[cfg(not(target_arch = "wasm32"))]
+	fn recover(&mut self) -> Result<(), Error> {
+		log::warn!("Recreate the lost device");
+		pollster::block_on(self.state.recover(&mut self.window))?;
+		let state = &self.state;
+
+		self.point_cloud_state = render::PointCloudState::new(state);
+		self.lines_state = render::LinesState::new(state);
+		self.eye_dome = render::EyeDome::new(
+			state,
+			self.window.config(),
+			self.window.depth_texture(),
+			self.eye_dome.strength,
+			self.eye_dome.radius,
+			self.eye_dome.sample_count,
+		);
+		let settings = &mut self.display_settings;
+		let environment = &settings.point_cloud_environment;
+		settings.point_cloud_environment = render::PointCloudEnvironment::new(
+			state,
+			environment.min,
+			environment.max,
+			environment.scale,
+		);
+		settings.lookup_white = Lookup::White.render(state);
+		let viewer = settings.settings(&self.eye_dome);
+		settings.apply(viewer, state, &mut self.eye_dome);
+		settings.camera.update_gpu(state);
+
+		// the font texture is only sent once per context, keep the memory of the old context
+		let egui = egui::Context::default();
+		egui.memory_mut(|memory| *memory = self.egui.memory(|memory| memory.clone()));
+		self.egui_winit = egui_winit::State::new(
+			egui.clone(),
+			egui.viewport_id(),
+			self.window.inner(),
+			Some(self.window.scale_factor() as f32),
+			None,
+			None,
+		);
+		self.egui = egui;
+		self.egui_wgpu =
+			egui_wgpu::Renderer::new(state.device(), state.surface_format(), None, 1, false);
+
+		self.chunks = std::mem::take(&mut self.chunks)
+			.into_iter()
+			.filter_map(|(idx, chunk)| Some((idx, chunk.recreate(state)?)))
+			.collect();
+		if let World::Interactive(interactive) = &mut self.world {
+			interactive.recreate_render(state);
+		}
+		self.window.request_redraw();
 		Ok(())
 	}
 
+	/// The device can not be recreated synchronously in the browser.
+	#[cfg(target_arch = "wasm32")]
+	fn recover(&mut self) -> Result<(), Error> {
+		Err(render::RenderError::DeviceLost.into())
+	}
+
+	/// Recreate the pipelines and render targets for the requested sample count.
+	fn update_sample_count(&mut self) {
+		self.state.sample_count = self.display_settings.sample_count;
+		self.point_cloud_state = render::PointCloudState::new(&self.state);
+		self.lines_state = render::LinesState::new(&self.state);
+		self.window.resized(&self.state);
+		self.eye_dome = render::EyeDome::new(
+			&self.state,
+			self.window.config(),
+			self.window.depth_texture(),
+			self.eye_dome.strength,
+			self.eye_dome.radius,
+			self.eye_dome.sample_count,
+		);
+	}
+
 	pub fn resized(&mut self) {
 		if self.window.inner_size().width == 0 || self.window.inner_size().height == 0 {
 			self.paused = true;
@@ -559,8 +1245,34 @@ impl Program {
 		match (button, state) {
 			(input::MouseButton::Left, input::State::Pressed) => {
 				self.mouse_start = Some(self.mouse.position());
+				if matches!(self.world, World::Interactive(_))
+					&& (self.keyboard.pressed(input::KeyCode::ControlLeft)
+						|| self.keyboard.pressed(input::KeyCode::ControlRight))
+				{
+					self.box_start = Some(self.mouse.position());
+				}
 			},
 			(input::MouseButton::Left, input::State::Released) => {
+				if let Some(start) = self.box_start.take() {
+					let World::Interactive(interactive) = &mut self.world else {
+						return;
+					};
+					let end = self.mouse.position();
+					let (min, max) = (start.inf(&end), start.sup(&end));
+					let world_to_screen = self
+						.display_settings
+						.camera
+						.world_to_screen(self.window.get_size());
+					interactive.select_inside(
+						|point| {
+							world_to_screen(point).is_some_and(|p| {
+								min.x <= p.x && p.x <= max.x && min.y <= p.y && p.y <= max.y
+							})
+						},
+						&self.state,
+					);
+					return;
+				}
 				let Some(start) = self.mouse_start else {
 					return;
 				};
@@ -571,11 +1283,14 @@ impl Program {
 				let World::Interactive(interactive) = &mut self.world else {
 					return;
 				};
+				let add = self.keyboard.pressed(input::KeyCode::ShiftLeft)
+					|| self.keyboard.pressed(input::KeyCode::ShiftRight);
 				interactive.click(
 					self.display_settings.camera.position(),
 					self.display_settings
 						.camera
 						.ray_direction(self.mouse.position(), self.window.get_size()),
+					add,
 					&self.display_settings,
 					&self.state,
 				);
@@ -601,9 +1316,60 @@ impl Program {
 		self.keyboard.update(key, state);
 	}
 
+	pub fn mouse_wheel(&mut self, delta: f32) {
+		self.window.request_redraw();
+		if let World::Interactive(interactive) = &mut self.world {
+			if (self.keyboard.pressed(input::KeyCode::ControlLeft)
+				|| self.keyboard.pressed(input::KeyCode::ControlRight))
+				&& interactive.scroll_radius(
+					delta,
+					self.display_settings.camera.position(),
+					self.display_settings
+						.camera
+						.ray_direction(self.mouse.position(), self.window.get_size()),
+					&self.display_settings,
+					&self.state,
+				) {
+				return;
+			}
+		}
+		self.display_settings.camera.scroll(delta, &self.state);
+	}
+
+	/// Two finger drag rotates, pinch zooms.
+	pub fn touch(&mut self, id: u64, phase: input::TouchPhase, position: na::Point2<f32>) {
+		let Some(gesture) = self.touches.update(id, phase, position) else {
+			return;
+		};
+		self.window.request_redraw();
+		self.display_settings
+			.camera
+			.rotate(gesture.drag, &self.state);
+		self.display_settings
+			.camera
+			.scroll((gesture.pinch - 1.0) * 10.0, &self.state);
+	}
+
+	/// Trackpad pinch, positive `delta` zooms in.
+	pub fn pinch(&mut self, delta: f32) {
+		self.window.request_redraw();
+		self.display_settings
+			.camera
+			.scroll(-delta * 10.0, &self.state);
+	}
+
+	/// Trackpad pan with two fingers rotates like a touch drag.
+	pub fn pan(&mut self, delta: na::Vector2<f32>) {
+		self.window.request_redraw();
+		self.display_settings.camera.rotate(delta, &self.state);
+	}
+
 	pub fn mouse_move(&mut self, position: na::Point2<f32>) {
 		self.window.request_redraw();
 		let delta = self.mouse.delta(position);
+		if self.box_start.is_some() {
+			return;
+		}
 		if self.mouse.pressed(input::MouseButton::Left) {
 			self.display_settings.camera.rotate(delta, &self.state);
 		} else if self.mouse.pressed(input::MouseButton::Right) {
@@ -618,6 +1384,19 @@ impl Program {
 				&self.state,
 				&self.display_settings,
 			);
+		} else if self.last_hover.elapsed().as_secs_f32() >= HOVER_INTERVAL {
+			let World::Interactive(interactive) = &mut self.world else {
+				return;
+			};
+			self.last_hover = web_time::Instant::now();
+			interactive.hover(
+				self.display_settings.camera.position(),
+				self.display_settings
+					.camera
+					.ray_direction(self.mouse.position(), self.window.get_size()),
+				&self.display_settings,
+				&self.state,
+			);
 		}
 	}
 }
@@ -0,0 +1,105 @@
+use crate::id;
+
+/// Minimal time between rate updates.
+const UPDATE_INTERVAL: f32 = 0.5;
+
+/// Weight for the newest rate measurement.
+const RATE_SMOOTHING: f32 = 0.3;
+
+/// Progress bar with elapsed time, remaining time and throughput.
+#[derive(Debug)]
+pub struct Progress {
+	unit: &'static str,
+	start: web_time::Instant,
+	last: web_time::Instant,
+	last_value: usize,
+	rate: Option<f32>,
+}
+
+impl Progress {
+	/// Create progress for `unit` per second.
+	pub fn new(unit: &'static str) -> Self {
+		let now = web_time::Instant::now();
+		Self {
+			unit,
+			start: now,
+			last: now,
+			last_value: 0,
+			rate: None,
+		}
+	}
+
+	/// Update with the current value.
+	/// The rate is based on the recent steps, a smaller value restarts the measurement.
+	fn update(&mut self, value: usize) {
+		if value < self.last_value {
+			*self = Self::new(self.unit);
+		}
+		let now = web_time::Instant::now();
+		let delta = (now - self.last).as_secs_f32();
+		if delta < UPDATE_INTERVAL {
+			return;
+		}
+		let rate = (value - self.last_value) as f32 / delta;
+		self.rate = Some(match self.rate {
+			Some(previous) => previous * (1.0 - RATE_SMOOTHING) + rate * RATE_SMOOTHING,
+			None => rate,
+		});
+		self.last = now;
+		self.last_value = value;
+	}
+
+	/// Draw progress bar and timings.
+	pub fn ui(&mut self, ui: &mut egui::Ui, value: usize, total: usize) {
+		self.update(value);
+		let progress = value as f32 / total as f32;
+		let remaining = match self.rate {
+			Some(rate) if rate > 0.0 => format_duration(total.saturating_sub(value) as f32 / rate),
+			_ => String::from("-"),
+		};
+		ui.add(
+			egui::ProgressBar::new(progress)
+				.rounding(egui::Rounding::ZERO)
+				.text(format!("{:.0}%", progress * 100.0)),
+		);
+		egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+			ui.label("Elapsed");
+			ui.label(format_duration(self.start.elapsed().as_secs_f32()));
+			ui.end_row();
+
+			ui.label("Remaining");
+			ui.label(remaining);
+			ui.end_row();
+
+			ui.label("Throughput");
+			ui.label(format!(
+				"{} {}/s",
+				format_amount(self.rate.unwrap_or(0.0)),
+				self.unit
+			));
+			ui.end_row();
+		});
+	}
+}
+
+/// Format seconds as `h:mm:ss` or `m:ss`.
+fn format_duration(seconds: f32) -> String {
+	let seconds = seconds as u64;
+	let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+	if hours > 0 {
+		format!("{}:{:02}:{:02}", hours, minutes, seconds)
+	} else {
+		format!("{}:{:02}", minutes, seconds)
+	}
+}
+
+/// Format amount with a metric prefix.
+fn format_amount(value: f32) -> String {
+	if value >= 1_000_000.0 {
+		format!("{:.1}M", value / 1_000_000.0)
+	} else if value >= 1_000.0 {
+		format!("{:.1}k", value / 1_000.0)
+	} else {
+		format!("{:.0}", value)
+	}
+}
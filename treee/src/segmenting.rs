@@ -1,27 +1,50 @@
 use crossbeam::atomic::AtomicCell;
 use nalgebra as na;
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use rayon::iter::{ParallelBridge, ParallelIterator};
 use std::{
 	collections::{HashMap, VecDeque},
 	ops::Not,
+	path::{Path, PathBuf},
 	sync::{
-		atomic::{AtomicUsize, Ordering},
+		atomic::{AtomicBool, AtomicUsize, Ordering},
 		Arc, Mutex,
 	},
+	time::Duration,
 };
 
-use crate::{id, interactive::DELETED_INDEX, loading::Loading, program::Event};
+use crate::{id, interactive::DELETED_INDEX, loading::Loading, program::Event, progress::Progress};
+
+mod debug;
 
 pub const DEFAULT_MAX_DISTANCE: f32 = 0.75;
 
+/// Default threshold for small segments in the statistics.
+const DEFAULT_MIN_SEGMENT_SIZE: usize = 1000;
+
+/// Parameters of a segmentation.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SegmentationSettings {
+	pub max_distance: f32,
+	pub seed: u64,
+}
+
 /// State for Segmenting phase.
 pub struct Segmenting {
 	pub shared: Arc<Shared>,
 	pub distance: f32,
+	seed: u64,
 	pub restart: crossbeam::channel::Sender<f32>,
+	/// Allows the next slice in step mode.
+	step: crossbeam::channel::Sender<()>,
 	pub total: usize,
 	pub world_offset: na::Point3<f64>,
+	/// Rotation applied to level the ground.
+	leveling: Option<na::UnitQuaternion<f32>>,
+	progress: Progress,
+	min_segment_size: usize,
+	/// Sorted point counts of the finished segmentation.
+	sizes: Option<Vec<usize>>,
 }
 
 /// Shared state for workers.
@@ -29,19 +52,39 @@ pub struct Shared {
 	pub done: AtomicCell<Option<HashMap<u32, Vec<na::Point3<f32>>>>>,
 	pub progress: AtomicUsize,
 	pub sender: crossbeam::channel::Sender<Event>,
+	/// Process the slices one at a time, each after a step.
+	pub stepping: AtomicBool,
 }
 
+/// Layer index, centroids from the previous slice, points and sender for the next slice.
+type Layer<'a> = (
+	usize,
+	crossbeam::channel::Receiver<HashMap<u32, Centroid>>,
+	&'a mut [na::Point3<f32>],
+	crossbeam::channel::Sender<HashMap<u32, Centroid>>,
+);
+
 impl Segmenting {
-	pub fn new(loading: Loading, max_distance: f32) -> (Self, crossbeam::channel::Receiver<Event>) {
+	/// Start the segmentation, the same `seed` gives the same segments.
+	///
+	/// With `debug_svg` a diagram for every slice is saved in the directory.
+	pub fn new(
+		loading: Loading,
+		max_distance: f32,
+		seed: u64,
+		debug_svg: Option<PathBuf>,
+	) -> (Self, crossbeam::channel::Receiver<Event>) {
 		let (sender, receiver) = crossbeam::channel::unbounded();
 
 		let (restart_sender, restart_reciever) = crossbeam::channel::unbounded();
 		restart_sender.send(max_distance).unwrap();
+		let (step_sender, step_receiver) = crossbeam::channel::unbounded();
 
 		let shared = Arc::new(Shared {
 			done: AtomicCell::new(None),
 			progress: AtomicUsize::new(0),
 			sender,
+			stepping: AtomicBool::new(false),
 		});
 		let total = loading
 			.shared
@@ -51,13 +94,22 @@ impl Segmenting {
 			.iter()
 			.map(|(_, slice)| slice.len())
 			.sum();
-		let world_offset = loading.shared.world_offset;
+		let world_offset = *loading.shared.world_offset.lock().unwrap();
+		let leveling = loading.leveling;
 		{
 			let shared = shared.clone();
 			rayon::spawn(move || {
 				while let Ok(distance) = restart_reciever.recv() {
 					shared.done.store(None);
-					segmentation(distance, &loading, &shared, &restart_reciever);
+					segmentation(
+						distance,
+						seed,
+						debug_svg.as_deref(),
+						&loading,
+						&shared,
+						&restart_reciever,
+						&step_receiver,
+					);
 				}
 			});
 		}
@@ -66,9 +118,15 @@ impl Segmenting {
 			Self {
 				shared,
 				distance: max_distance,
+				seed,
 				restart: restart_sender,
+				step: step_sender,
 				total,
 				world_offset,
+				leveling,
+				progress: Progress::new("points"),
+				min_segment_size: DEFAULT_MIN_SEGMENT_SIZE,
+				sizes: None,
 			},
 			receiver,
 		)
@@ -83,12 +141,73 @@ impl Segmenting {
 				.changed()
 			{
 				self.restart.send(self.distance).unwrap();
+				self.sizes = None;
 			};
 			ui.end_row();
+
+			ui.label("Min Points");
+			ui.add(egui::Slider::new(&mut self.min_segment_size, 1..=100_000).logarithmic(true));
+			ui.end_row();
+
+			ui.label("Step Mode");
+			let mut stepping = self.shared.stepping.load(Ordering::Relaxed);
+			if ui.checkbox(&mut stepping, "").changed() {
+				self.shared.stepping.store(stepping, Ordering::Relaxed);
+				if stepping {
+					// restart to process the slices in order
+					self.restart.send(self.distance).unwrap();
+					self.sizes = None;
+				}
+			}
+			ui.end_row();
 		});
 
 		ui.separator();
 		if let Some(segments) = self.shared.done.take() {
+			let sizes = self.sizes.get_or_insert_with(|| {
+				let mut sizes = segments
+					.iter()
+					.filter(|&(&idx, _)| idx != DELETED_INDEX)
+					.map(|(_, points)| points.len())
+					.collect::<Vec<_>>();
+				sizes.sort_unstable();
+				sizes
+			});
+			let unassigned = segments.get(&DELETED_INDEX).map_or(0, Vec::len);
+
+			ui.add_sized([ui.available_width(), 0.0], egui::Label::new("Statistics"));
+			egui::Grid::new(id!()).num_columns(2).show(ui, |ui| {
+				ui.label("Segments");
+				ui.label(format!("{}", sizes.len()));
+				ui.end_row();
+
+				if let (Some(&min), Some(&max)) = (sizes.first(), sizes.last()) {
+					ui.label("Min Points");
+					ui.label(format!("{}", min));
+					ui.end_row();
+
+					ui.label("Median Points");
+					ui.label(format!("{}", sizes[sizes.len() / 2]));
+					ui.end_row();
+
+					ui.label("Max Points");
+					ui.label(format!("{}", max));
+					ui.end_row();
+				}
+
+				ui.label("Small Segments");
+				ui.label(format!(
+					"{}",
+					sizes.partition_point(|&size| size < self.min_segment_size)
+				));
+				ui.end_row();
+
+				ui.label("Unassigned");
+				ui.label(format!("{}", unassigned));
+				ui.end_row();
+			});
+
+			ui.separator();
 			if ui
 				.add_sized([ui.available_width(), 0.0], egui::Button::new("Continue"))
 				.clicked()
@@ -96,27 +215,53 @@ impl Segmenting {
 				_ = self.shared.sender.send(Event::Segmented {
 					segments,
 					world_offset: self.world_offset,
+					leveling: self.leveling,
+					settings: SegmentationSettings {
+						max_distance: self.distance,
+						seed: self.seed,
+					},
 				});
 			} else {
 				self.shared.done.store(Some(segments));
 			}
 		} else {
-			let progress = self.shared.progress.load(Ordering::Relaxed) as f32 / self.total as f32;
-			ui.add(egui::ProgressBar::new(progress).rounding(egui::Rounding::ZERO));
+			let progress = self.shared.progress.load(Ordering::Relaxed);
+			self.progress.ui(ui, progress, self.total);
+			if self.shared.stepping.load(Ordering::Relaxed)
+				&& ui
+					.add_sized([ui.available_width(), 0.0], egui::Button::new("Step"))
+					.clicked()
+			{
+				_ = self.step.send(());
+			}
 		}
 	}
 }
 
 fn segmentation(
 	max_distance: f32,
+	seed: u64,
+	debug_svg: Option<&Path>,
 	loading: &Loading,
 	segmenting: &Shared,
 	reciever: &crossbeam::channel::Receiver<f32>,
+	steps: &crossbeam::channel::Receiver<()>,
 ) {
 	_ = segmenting.sender.send(Event::ClearPointClouds);
 
 	segmenting.progress.store(0, Ordering::Relaxed);
 	segmenting.done.store(None);
+	// steps for the previous run
+	while steps.try_recv().is_ok() {}
+
+	// only create the directory, diagrams from earlier runs are overwritten
+	let debug_svg = debug_svg.filter(|directory| match std::fs::create_dir_all(directory) {
+		Ok(()) => true,
+		Err(err) => {
+			log::warn!("Failed to create {}: {}", directory.display(), err);
+			false
+		},
+	});
 
 	let mut source_slices = loading.shared.slices.lock().unwrap();
 	let min = source_slices.iter().map(|(&idx, _)| idx).min().unwrap_or(0);
@@ -135,9 +280,9 @@ fn segmentation(
 	let (slices, last_reciever) = {
 		let (sender, mut receiver) = crossbeam::channel::bounded(1);
 		let mut slices = Vec::with_capacity(layers);
-		for slice in source_slices.into_iter().rev() {
+		for (layer, slice) in source_slices.into_iter().enumerate().rev() {
 			let (next_sender, next_receiver) = crossbeam::channel::bounded(1);
-			slices.push((receiver, slice.unwrap_or(&mut []), next_sender));
+			slices.push((layer, receiver, slice.unwrap_or(&mut []), next_sender));
 			receiver = next_receiver;
 		}
 
@@ -156,74 +301,100 @@ fn segmentation(
 	let segments = HashMap::<u32, Vec<na::Point3<f32>>>::new();
 	let segments = Mutex::new(segments);
 
-	// parallel loop, break if any worker returns true
-	let cancel = slices
-		.into_iter()
-		.par_bridge()
-		.any(|(c_receiver, slice, c_sender)| {
-			// recieved new distance, break
-			if reciever.is_empty().not() {
-				return true;
-			}
+	// work for a single slice, break if any worker returns true
+	let process = |(layer, c_receiver, slice, c_sender): Layer| {
+		// recieved new distance, break
+		if reciever.is_empty().not() {
+			return true;
+		}
 
-			// calculate trees for the points in the slice
-			let tree_set = TreeSet::new(slice, max_distance);
+		// seed for every slice, independent of the order of the workers
+		let mut rng = StdRng::seed_from_u64(seed.wrapping_add(layer as u64));
 
-			// combine trees with centers from the previous slice
-			let centroids = c_receiver.recv().unwrap();
-			let centroids = tree_set.tree_positions(centroids, max_distance);
-			let (indices, points) = centroids
-				.iter()
-				.map(|(&idx, &c)| (idx, c))
-				.unzip::<_, _, Vec<_>, Vec<_>>();
-			_ = c_sender.send(centroids);
-
-			// assign points to the centers of the current slice
-			let vor = voronator::VoronoiDiagram::new(&min, &max, &points).unwrap();
-			let mut trees = vor
-				.cells()
-				.iter()
-				.map(|cell| cell.points())
-				.map(|p| p.iter().map(|p| p.center).collect::<Vec<_>>())
-				.map(Tree::from_points)
-				.zip(indices)
-				.collect::<VecDeque<_>>();
-
-			let mut segment_data = Vec::with_capacity(slice.len());
-			for &p in slice.iter() {
-				let Some(idx) = trees
-					.iter_mut()
-					.position(|(tree, _)| tree.contains(na::Point2::new(p.x, p.z), 0.1))
-				else {
-					segment_data.push(0);
-					continue;
-				};
-				let elem = trees.remove(idx).unwrap();
-				segment_data.push(elem.1);
-
-				// hope next point is in the same segment
-				trees.push_front(elem);
-			}
+		// calculate trees for the points in the slice
+		let tree_set = TreeSet::new(slice, max_distance, &mut rng);
+		let debug_trees = debug_svg.map(|_| tree_set.trees.clone());
 
-			// save results
-			let mut segments = segments.lock().unwrap();
-			for (&idx, &p) in segment_data.iter().zip(slice.iter()) {
-				segments.entry(idx).or_default().push(p);
-			}
-			drop(segments);
+		// combine trees with centers from the previous slice
+		let centroids = c_receiver.recv().unwrap();
+		let centroids = tree_set.tree_positions(centroids, max_distance, &mut rng);
+		let mut sorted = centroids
+			.iter()
+			.map(|(&idx, &c)| (idx, c))
+			.collect::<Vec<_>>();
+		sorted.sort_unstable_by_key(|&(idx, _)| idx);
+		let (indices, points) = sorted.iter().copied().unzip::<_, _, Vec<_>, Vec<_>>();
+		_ = c_sender.send(centroids);
+
+		// assign points to the centers of the current slice
+		let vor = voronator::VoronoiDiagram::new(&min, &max, &points).unwrap();
+		let mut trees = vor
+			.cells()
+			.iter()
+			.map(|cell| cell.points())
+			.map(|p| p.iter().map(|p| p.center).collect::<Vec<_>>())
+			.map(Tree::from_points)
+			.zip(indices)
+			.collect::<VecDeque<_>>();
+
+		let mut segment_data = Vec::with_capacity(slice.len());
+		for &p in slice.iter() {
+			let Some(idx) = trees
+				.iter_mut()
+				.position(|(tree, _)| tree.contains(na::Point2::new(p.x, p.z), 0.1))
+			else {
+				segment_data.push(0);
+				continue;
+			};
+			let elem = trees.remove(idx).unwrap();
+			segment_data.push(elem.1);
 
-			if slice.is_empty().not() {
-				_ = segmenting.sender.send(Event::PointCloud {
-					idx: None,
-					data: slice.to_vec(),
-					segment: segment_data,
-				});
-			}
-			segmenting
-				.progress
-				.fetch_add(slice.len(), Ordering::Relaxed);
-			false
-		});
+			// hope next point is in the same segment
+			trees.push_front(elem);
+		}
+
+		if let (Some(directory), Some(debug_trees)) = (debug_svg, debug_trees) {
+			debug::save(
+				directory,
+				min.center,
+				max.center,
+				debug::Slice {
+					layer,
+					points: slice,
+					trees: &debug_trees,
+					centroids: &sorted,
+					cells: trees.make_contiguous(),
+				},
+			);
+		}
+
+		// save results
+		let mut segments = segments.lock().unwrap();
+		for (&idx, &p) in segment_data.iter().zip(slice.iter()) {
+			segments.entry(idx).or_default().push(p);
+		}
+		drop(segments);
+
+		if slice.is_empty().not() {
+			_ = segmenting.sender.send(Event::PointCloud {
+				idx: None,
+				data: slice.to_vec(),
+				segment: segment_data,
+			});
+		}
+		segmenting
+			.progress
+			.fetch_add(slice.len(), Ordering::Relaxed);
+		false
+	};
+	let cancel = if segmenting.stepping.load(Ordering::Relaxed) {
+		// one slice after another, every slice waits for a step
+		slices
+			.into_iter()
+			.any(|slice| wait_for_step(segmenting, steps, reciever) || process(slice))
+	} else {
+		slices.into_iter().par_bridge().any(process)
+	};
 	if cancel {
 		return;
 	}
@@ -234,6 +405,25 @@ fn segmentation(
 	segmenting.done.store(Some(segments));
 }
 
+/// Wait until the next step, returns `true` if the segmentation restarts.
+///
+/// Continues without waiting if the step mode is disabled.
+fn wait_for_step(
+	segmenting: &Shared,
+	steps: &crossbeam::channel::Receiver<()>,
+	reciever: &crossbeam::channel::Receiver<f32>,
+) -> bool {
+	while segmenting.stepping.load(Ordering::Relaxed) {
+		if reciever.is_empty().not() {
+			return true;
+		}
+		if steps.recv_timeout(Duration::from_millis(50)).is_ok() {
+			break;
+		}
+	}
+	false
+}
+
 /// A collection of trees.
 #[derive(Debug, Clone)]
 pub struct TreeSet {
@@ -421,8 +611,14 @@ impl TreeSet {
 		Self { trees: Vec::new() }
 	}
 
-	pub fn new(points: &mut [na::Point3<f32>], max_distance: f32) -> Self {
-		points.shuffle(&mut thread_rng());
+	pub fn new(points: &mut [na::Point3<f32>], max_distance: f32, rng: &mut impl Rng) -> Self {
+		// the loading order depends on the workers and earlier runs, sort before the seeded shuffle
+		points.sort_unstable_by(|a, b| {
+			a.x.total_cmp(&b.x)
+				.then(a.y.total_cmp(&b.y))
+				.then(a.z.total_cmp(&b.z))
+		});
+		points.shuffle(rng);
 
 		let mut trees = Self::new_empty();
 		for &point in points.iter() {
@@ -486,8 +682,12 @@ impl TreeSet {
 		self,
 		prev: HashMap<u32, Centroid>,
 		max_distance: f32,
+		rng: &mut impl Rng,
 	) -> HashMap<u32, Centroid> {
 		let mut res = HashMap::with_capacity(prev.len());
+		// fixed order, the iteration order of the map changes between runs
+		let mut prev = prev.into_iter().collect::<Vec<_>>();
+		prev.sort_unstable_by_key(|&(idx, _)| idx);
 		let mut centroids = self
 			.trees
 			.into_iter()
@@ -513,9 +713,9 @@ impl TreeSet {
 		}
 
 		for c in centroids {
-			let mut idx = rand::random();
+			let mut idx = rng.gen();
 			while idx == DELETED_INDEX || res.contains_key(&idx) {
-				idx = rand::random();
+				idx = rng.gen();
 			}
 			res.insert(idx, Centroid { center: c });
 		}
@@ -543,3 +743,27 @@ fn centroid(points: &[na::Point2<f32>]) -> (na::Point2<f32>, f32) {
 
 	(a + center / area, area / 2.0)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tree_set_independent_of_order() {
+		let mut rng = StdRng::seed_from_u64(0);
+		let mut points = (0..500)
+			.map(|_| na::point![rng.gen_range(0.0..10.0), 0.0, rng.gen_range(0.0..10.0)])
+			.collect::<Vec<_>>();
+		let mut reversed = points.iter().rev().copied().collect::<Vec<_>>();
+
+		let first = TreeSet::new(&mut points, 0.5, &mut StdRng::seed_from_u64(1));
+		let second = TreeSet::new(&mut reversed, 0.5, &mut StdRng::seed_from_u64(1));
+		let trees = |set: &TreeSet| {
+			set.trees
+				.iter()
+				.map(|tree| tree.points.clone())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(trees(&first), trees(&second));
+	}
+}
@@ -0,0 +1,140 @@
+use nalgebra as na;
+use std::{io::Write, path::Path};
+
+use super::{Centroid, Tree};
+
+/// Width of the diagram in pixels.
+const WIDTH: f32 = 1000.0;
+
+/// Space around the diagram in pixels.
+const MARGIN: f32 = 40.0;
+
+/// Maximum drawn points, larger slices only draw every n-th point.
+const MAX_POINTS: usize = 20_000;
+
+/// Content of a single slice.
+pub struct Slice<'a> {
+	pub layer: usize,
+	pub points: &'a [na::Point3<f32>],
+	/// Convex areas found in the slice.
+	pub trees: &'a [Tree],
+	/// Segment centers with their ids.
+	pub centroids: &'a [(u32, Centroid)],
+	/// Voronoi cells with the id of their segment.
+	pub cells: &'a [(Tree, u32)],
+}
+
+/// Write the slice as `slice_<layer>.svg` in `directory`, existing files are overwritten.
+///
+/// Errors are only logged, the segmentation continues without the diagram.
+pub fn save(directory: &Path, min: na::Point2<f32>, max: na::Point2<f32>, slice: Slice) {
+	let path = directory.join(format!("slice_{:04}.svg", slice.layer));
+	let res = std::fs::File::create(&path)
+		.and_then(|file| write_slice(std::io::BufWriter::new(file), min, max, slice));
+	if let Err(err) = res {
+		log::warn!("Failed to write {}: {}", path.display(), err);
+	}
+}
+
+/// Write the points, tree areas, Voronoi cells and centroids of a slice as SVG.
+///
+/// The view is from above with north up, `min` and `max` are the horizontal bounds.
+pub fn write_slice(
+	mut writer: impl Write,
+	min: na::Point2<f32>,
+	max: na::Point2<f32>,
+	slice: Slice,
+) -> Result<(), std::io::Error> {
+	let extent = (max - min).max().max(1.0);
+	let scale = WIDTH / extent;
+	let height = (max.y - min.y).max(1.0) * scale;
+	let to_svg = |p: na::Point2<f32>| {
+		(
+			MARGIN + (p.x - min.x) * scale,
+			MARGIN + (p.y - min.y) * scale,
+		)
+	};
+	let path = |points: &[na::Point2<f32>]| {
+		let mut d = String::new();
+		for (i, &p) in points.iter().enumerate() {
+			let (x, y) = to_svg(p);
+			d += &format!("{}{:.1} {:.1} ", if i == 0 { "M " } else { "L " }, x, y);
+		}
+		d + "Z"
+	};
+	let (total_width, total_height) = (WIDTH + 2.0 * MARGIN, height + 2.0 * MARGIN);
+
+	writeln!(
+		writer,
+		r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="sans-serif" font-size="10">"#,
+		total_width, total_height,
+	)?;
+	writeln!(writer, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+
+	writeln!(writer, r#"<g fill="gray">"#)?;
+	let step = slice.points.len().div_ceil(MAX_POINTS).max(1);
+	for p in slice.points.iter().step_by(step) {
+		let (x, y) = to_svg(na::point![p.x, p.z]);
+		writeln!(
+			writer,
+			r#"<rect x="{:.1}" y="{:.1}" width="1" height="1"/>"#,
+			x, y
+		)?;
+	}
+	writeln!(writer, "</g>")?;
+
+	writeln!(
+		writer,
+		r#"<g fill="none" stroke="steelblue" stroke-dasharray="4 2">"#
+	)?;
+	for (cell, idx) in slice.cells {
+		if cell.points.is_empty() {
+			continue;
+		}
+		writeln!(
+			writer,
+			r#"<path d="{}"><title>cell {}</title></path>"#,
+			path(&cell.points),
+			idx,
+		)?;
+	}
+	writeln!(writer, "</g>")?;
+
+	writeln!(
+		writer,
+		r#"<g fill="forestgreen" fill-opacity="0.3" stroke="darkgreen">"#
+	)?;
+	for tree in slice.trees {
+		writeln!(writer, r#"<path d="{}"/>"#, path(&tree.points))?;
+	}
+	writeln!(writer, "</g>")?;
+
+	writeln!(writer, r#"<g fill="crimson">"#)?;
+	for (idx, centroid) in slice.centroids {
+		let (x, y) = to_svg(centroid.center);
+		writeln!(
+			writer,
+			r#"<circle cx="{:.1}" cy="{:.1}" r="3"/><text x="{:.1}" y="{:.1}">{}</text>"#,
+			x,
+			y,
+			x + 5.0,
+			y + 3.0,
+			idx,
+		)?;
+	}
+	writeln!(writer, "</g>")?;
+
+	writeln!(
+		writer,
+		r#"<text x="{}" y="{}" font-size="12">Slice {}: {} points, {} trees, {} segments</text>"#,
+		MARGIN,
+		MARGIN / 2.0,
+		slice.layer,
+		slice.points.len(),
+		slice.trees.len(),
+		slice.centroids.len(),
+	)?;
+
+	writeln!(writer, "</svg>")?;
+	writer.flush()
+}
@@ -0,0 +1,47 @@
+use crate::id;
+
+const FEET_PER_METER: f32 = 3.280_84;
+const INCHES_PER_METER: f32 = 39.370_08;
+
+/// Units to display lengths, the values are always stored in meters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Units {
+	Metric,
+	Imperial,
+}
+
+impl Units {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Metric => "Metric",
+			Self::Imperial => "Imperial",
+		}
+	}
+
+	/// Format a length like a height, in meters or feet.
+	pub fn length(self, meters: f32) -> String {
+		match self {
+			Self::Metric => format!("{:.2}m", meters),
+			Self::Imperial => format!("{:.1}ft", meters * FEET_PER_METER),
+		}
+	}
+
+	/// Format a diameter, in meters or inches.
+	pub fn diameter(self, meters: f32) -> String {
+		match self {
+			Self::Metric => format!("{:.2}m", meters),
+			Self::Imperial => format!("{:.1}in", meters * INCHES_PER_METER),
+		}
+	}
+
+	pub fn ui(&mut self, ui: &mut egui::Ui) {
+		egui::ComboBox::from_id_salt(id!())
+			.selected_text(self.name())
+			.width(ui.available_width())
+			.show_ui(ui, |ui| {
+				for v in [Self::Metric, Self::Imperial] {
+					ui.selectable_value(self, v, v.name());
+				}
+			});
+	}
+}